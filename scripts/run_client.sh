@@ -10,8 +10,7 @@ SERVER=""
 USERNAME=""
 BACKGROUND_CONNECT=false
 VERBOSE=false
-EVENT_BASED=false
-GUI=false
+COMMAND=()
 
 while [[ $# -gt 0 ]]; do
   case $1 in
@@ -36,25 +35,26 @@ while [[ $# -gt 0 ]]; do
       export RUST_LOG=debug
       shift
       ;;
-    --event-based)
-      EVENT_BASED=true
+    --gui)
+      # The GUI starts unless a command is given
       shift
       ;;
-    --gui)
-      GUI=true
+    --)
       shift
+      COMMAND=("$@")
+      break
       ;;
     --help)
-      echo "Usage: $0 [options]"
+      echo "Usage: $0 [options] [-- COMMAND...]"
       echo "Options:"
       echo "  --auth=METHOD       Authentication method (native, password, psk)"
       echo "  --server=SERVER     Server address to connect to"
       echo "  --username=USER     Username for authentication"
       echo "  --background        Don't connect automatically on startup"
-      echo "  --event-based       Use the event-based UI implementation"
-      echo "  --gui               Use the graphical user interface"
+      echo "  --gui               Use the graphical user interface (the default)"
       echo "  --verbose           Enable verbose logging"
       echo "  --help              Show this help message"
+      echo "  -- COMMAND...       Run a client command such as exec or shell instead of the GUI"
       exit 0
       ;;
     *)
@@ -71,11 +71,6 @@ cargo build --release
 # Build the command line arguments
 ARGS=("--auth-method" "$AUTH_METHOD")
 
-# GUI is the default, only add --no-gui if GUI=false
-if [ "$GUI" = false ]; then
-  ARGS+=("--no-gui")
-fi
-
 if [ -n "$SERVER" ]; then
   ARGS+=("--server" "$SERVER")
 fi
@@ -92,13 +87,11 @@ if [ "$VERBOSE" = true ]; then
   ARGS+=("--verbose")
 fi
 
-if [ "$EVENT_BASED" = true ]; then
-  ARGS+=("--event-based")
+# A command runs without the GUI, and must never fall back to opening it
+if [ ${#COMMAND[@]} -gt 0 ]; then
+  ARGS+=("--no-gui" "${COMMAND[@]}")
 fi
 
-# Note: The GUI flag is now always included by default
-# The --gui flag parameter is now just used to show intent in the script
-
 # Run the client with the specified options
 echo "Running with options: ${ARGS[@]}"
 ./target/release/rust_rcp_client "${ARGS[@]}"
@@ -0,0 +1,298 @@
+//! Connection and authentication audit log
+//!
+//! Connects, authentication attempts, reconnects and disconnects are appended
+//! to a JSON lines file, `audit.log` in the state directory by default:
+//!
+//! ```text
+//! {"timestamp":"2024-05-01T09:30:00.123+00:00","event":"auth","server":"rcp.example.com:8717","username":"alice","method":"password","result":"failure","reason":"rejected by the server"}
+//! ```
+//!
+//! Once the file grows past the configured size it is rotated to `audit.log.1`,
+//! `audit.log.2` and so on, keeping a fixed number of old files.
+
+use crate::config::{paths, AuditConfig, ClientConfig, ServerConfig};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Kind of audited event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEvent {
+    /// Connection to a server
+    Connect,
+
+    /// Authentication attempt with one method
+    Auth,
+
+    /// Connection to a server again in the same session
+    Reconnect,
+
+    /// Connection closed by the client
+    Disconnect,
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditEvent::Connect => write!(f, "connect"),
+            AuditEvent::Auth => write!(f, "auth"),
+            AuditEvent::Reconnect => write!(f, "reconnect"),
+            AuditEvent::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// Whether an audited event succeeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditResult {
+    Success,
+    Failure,
+}
+
+impl fmt::Display for AuditResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditResult::Success => write!(f, "success"),
+            AuditResult::Failure => write!(f, "failure"),
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the event happened, in RFC 3339 format
+    pub timestamp: String,
+
+    /// What happened
+    pub event: AuditEvent,
+
+    /// Server as `address:port`, or the path of a Unix socket
+    pub server: String,
+
+    /// User the event was for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Authentication method
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+
+    /// Whether the event succeeded
+    pub result: AuditResult,
+
+    /// Why the event failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditRecord {
+    /// Create a successful record of an event happening now
+    pub fn new(event: AuditEvent, server: &str) -> Self {
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            event,
+            server: server.to_string(),
+            username: None,
+            method: None,
+            result: AuditResult::Success,
+            reason: None,
+        }
+    }
+
+    /// Set the user the event was for
+    pub fn with_username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// Set the authentication method
+    pub fn with_method(mut self, method: &str) -> Self {
+        self.method = Some(method.to_string());
+        self
+    }
+
+    /// Mark the event as failed for the given reason
+    pub fn failed(mut self, reason: impl fmt::Display) -> Self {
+        self.result = AuditResult::Failure;
+        self.reason = Some(reason.to_string());
+        self
+    }
+}
+
+/// Name of the configured server as recorded in the log
+pub fn server_name(config: &ServerConfig) -> String {
+    match &config.socket_path {
+        Some(path) => path.clone(),
+        None => format!("{}:{}", config.address, config.port),
+    }
+}
+
+/// Record a connect, reconnect or disconnect of the configured server
+///
+/// Does nothing if auditing is disabled; failures to write are logged.
+pub fn record_connection<T>(config: &ClientConfig, event: AuditEvent, result: &Result<T>) {
+    let log = match AuditLog::from_config(&config.audit) {
+        Some(log) => log,
+        None => return,
+    };
+
+    let mut record = AuditRecord::new(event, &server_name(&config.server));
+    if let Some(username) = &config.auth.username {
+        record = record.with_username(username);
+    }
+    log.log(match result {
+        Ok(_) => record,
+        Err(e) => record.failed(format!("{:#}", e)),
+    });
+}
+
+/// Append-only audit log with size-based rotation
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Create a log writing to the given file, rotated at 1 MiB keeping 5 old files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: 1024 * 1024,
+            max_files: 5,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Rotate the log once it reaches `max_size` bytes, keeping `max_files` old logs
+    pub fn with_rotation(mut self, max_size: u64, max_files: usize) -> Self {
+        self.max_size = max_size;
+        self.max_files = max_files;
+        self
+    }
+
+    /// Default location of the audit log
+    pub fn default_path() -> PathBuf {
+        paths::moved_file(&paths::state_dir(), "audit.log")
+    }
+
+    /// Create the log described by the configuration, or `None` if auditing is disabled
+    pub fn from_config(config: &AuditConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        let path = config
+            .path
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(Self::default_path);
+        Some(Self::new(path).with_rotation(config.max_size_kb * 1024, config.max_files))
+    }
+
+    /// Path of the current log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the `index`th rotated log
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    /// Move the current log to `.1`, `.1` to `.2` and so on, dropping the oldest
+    fn rotate(&self) -> Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path).context("Failed to remove audit log");
+        }
+
+        let oldest = self.rotated_path(self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest).context("Failed to remove old audit log")?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))
+                    .context("Failed to rotate audit log")?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1)).context("Failed to rotate audit log")
+    }
+
+    /// Append a record to the log
+    pub fn record(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create audit log directory")?;
+        }
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            if metadata.len() > 0 && metadata.len() + line.len() as u64 > self.max_size {
+                self.rotate()?;
+            }
+        }
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .context("Failed to write audit log")?;
+        Ok(())
+    }
+
+    /// Append a record, logging rather than returning any error
+    pub fn log(&self, record: AuditRecord) {
+        if let Err(e) = self.record(&record) {
+            log::error!(
+                "Failed to record {} event in audit log: {:#}",
+                record.event,
+                e
+            );
+        }
+    }
+
+    /// Read all records, oldest first, including rotated logs
+    ///
+    /// Lines that cannot be parsed are skipped.
+    pub fn read(&self) -> Result<Vec<AuditRecord>> {
+        let mut files: Vec<PathBuf> = (1..=self.max_files)
+            .rev()
+            .map(|index| self.rotated_path(index))
+            .collect();
+        files.push(self.path.clone());
+
+        let mut records = Vec::new();
+        for file in files.iter().filter(|f| f.exists()) {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read audit log {}", file.display()))?;
+            records.extend(
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok()),
+            );
+        }
+        Ok(records)
+    }
+}
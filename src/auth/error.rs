@@ -15,6 +15,10 @@ pub enum AuthError {
     #[error("Authentication method not supported: {0}")]
     UnsupportedMethod(String),
 
+    /// Every method tried during negotiation failed
+    #[error("No authentication method succeeded ({0})")]
+    NoAcceptableMethod(String),
+
     /// Authentication timed out
     #[error("Authentication timed out")]
     Timeout,
@@ -27,6 +31,22 @@ pub enum AuthError {
     #[error("Failed to load credentials: {0}")]
     KeyringError(#[from] keyring::Error),
 
+    /// Secret referenced from the configuration could not be read
+    #[error("Failed to resolve secret reference: {0}")]
+    SecretReference(String),
+
+    /// Credential store backend failed
+    #[error("Credential store error: {0}")]
+    CredentialStore(String),
+
+    /// Server asked for a second factor but there is no way to obtain one
+    #[error("Server requires a second factor but no code source is available")]
+    SecondFactorUnavailable,
+
+    /// OAuth2 identity provider failed or refused
+    #[error("OAuth error: {0}")]
+    OAuth(String),
+
     /// Authentication blocked by system policy
     #[error("Authentication blocked by system policy")]
     PolicyBlocked,
@@ -0,0 +1,168 @@
+//! Multi-step authentication exchange
+//!
+//! The client sends an `auth` message and waits for the server's reply:
+//!
+//! - `{"success": true}`: authenticated
+//! - `{"success": false, "message": "..."}`: rejected
+//! - `{"success": false, "second_factor": {"type": "totp", "message": "...",
+//!   "attempts_remaining": 2}}`: the server wants a second factor
+//!
+//! A second factor is answered with another `auth` message of the form
+//! `{"step": "second_factor", "method": "totp", "code": "123456"}`, after
+//! which the server replies in the same format again.
+//!
+//! An accepted reply may carry a session token, see [`crate::auth::session`].
+//!
+//! Before authenticating, the client may ask which methods the server accepts
+//! with `{"step": "methods", "username": "..."}`; the server answers
+//! `{"success": true, "methods": ["psk", "password"]}`.
+
+use crate::auth::{AuthError, AuthOptions, SecondFactorChallenge, SessionToken};
+use crate::protocol::{Client, Message, MessageType, ProtocolError};
+use anyhow::Result;
+use serde_json::{json, Value};
+
+/// Seconds to wait for the server to answer an authentication step
+pub const AUTH_REPLY_TIMEOUT_SECS: u64 = 30;
+
+/// Maximum number of second factor codes submitted in one exchange
+const MAX_SECOND_FACTOR_ATTEMPTS: u32 = 3;
+
+/// Outcome of a single authentication step
+enum StepReply {
+    Accepted(Option<SessionToken>),
+    Rejected(String),
+    SecondFactor(Value),
+}
+
+/// Send the first authentication message and drive the exchange to completion
+pub async fn run(
+    client: &Client,
+    payload: Value,
+    username: &str,
+    options: &AuthOptions,
+) -> Result<bool> {
+    let mut reply = send_step(client, payload).await?;
+    let mut attempts = 0;
+
+    loop {
+        match reply {
+            StepReply::Accepted(session) => {
+                if let (Some(token), Some(store), Some(target)) =
+                    (session, &options.sessions, &options.target)
+                {
+                    log::debug!("Server issued a session token");
+                    store.insert(username, target, token);
+                }
+                return Ok(true);
+            }
+            StepReply::Rejected(reason) => {
+                log::warn!("Authentication rejected: {}", reason);
+                return Ok(false);
+            }
+            StepReply::SecondFactor(details) => {
+                if attempts >= MAX_SECOND_FACTOR_ATTEMPTS {
+                    log::warn!("Giving up after {} second factor attempts", attempts);
+                    return Ok(false);
+                }
+                attempts += 1;
+
+                let challenge = SecondFactorChallenge::from_payload(&details, username);
+                log::info!("Server requested a second factor ({})", challenge.kind);
+
+                let handler = options
+                    .second_factor
+                    .as_deref()
+                    .ok_or(AuthError::SecondFactorUnavailable)?;
+                let code = handler.one_time_code(&challenge).await?;
+
+                reply = send_step(
+                    client,
+                    json!({
+                        "username": username,
+                        "step": "second_factor",
+                        "method": challenge.kind.to_string(),
+                        "code": code,
+                    }),
+                )
+                .await?;
+            }
+        }
+    }
+}
+
+/// Ask the server which authentication methods it accepts
+///
+/// Returns `None` if the server does not advertise its methods.
+pub async fn query_methods(client: &Client, username: &str) -> Result<Option<Vec<String>>> {
+    let reply = send(
+        client,
+        json!({
+            "username": username,
+            "step": "methods",
+        }),
+    )
+    .await?;
+
+    if reply.message_type == MessageType::Error {
+        return Ok(None);
+    }
+
+    Ok(reply
+        .payload
+        .get("methods")
+        .and_then(|v| v.as_array())
+        .map(|methods| {
+            methods
+                .iter()
+                .filter_map(|m| m.as_str())
+                .map(|m| m.to_string())
+                .collect()
+        }))
+}
+
+/// Send one authentication message and wait for the server's reply
+async fn send(client: &Client, payload: Value) -> Result<Message> {
+    client
+        .send(Message::new(MessageType::Auth, payload))
+        .await?;
+
+    client
+        .wait_for_reply(AUTH_REPLY_TIMEOUT_SECS)
+        .await
+        .map_err(|e| match e.downcast_ref::<ProtocolError>() {
+            Some(ProtocolError::Timeout) => AuthError::Timeout.into(),
+            _ => e,
+        })
+}
+
+/// Send one authentication message and classify the server's reply
+async fn send_step(client: &Client, payload: Value) -> Result<StepReply> {
+    let reply = send(client, payload).await?;
+    Ok(classify(&reply))
+}
+
+/// Interpret an authentication reply from the server
+fn classify(reply: &Message) -> StepReply {
+    let payload = &reply.payload;
+    let message = payload
+        .get("message")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Authentication rejected by server")
+        .to_string();
+
+    if reply.message_type == MessageType::Error {
+        return StepReply::Rejected(message);
+    }
+
+    if let Some(details) = payload.get("second_factor").filter(|v| !v.is_null()) {
+        return StepReply::SecondFactor(details.clone());
+    }
+
+    match payload.get("success").and_then(|v| v.as_bool()) {
+        Some(true) => {
+            StepReply::Accepted(payload.get("session").and_then(SessionToken::from_payload))
+        }
+        _ => StepReply::Rejected(message),
+    }
+}
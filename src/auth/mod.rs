@@ -1,17 +1,41 @@
+use crate::audit::AuditLog;
+use crate::config::OAuthConfig;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 mod error;
+pub mod exchange;
 mod native;
+pub mod negotiate;
+mod oauth;
 mod password;
+mod prompt;
 mod psk;
+mod second_factor;
+mod secret;
+mod secret_ref;
+pub mod session;
+pub mod store;
+pub mod totp;
 
 pub use error::AuthError;
-pub use native::NativeAuthProvider;
+pub use native::{NativeAuthProvider, DEFAULT_ASSERTION_SOCKET};
+pub use oauth::{Endpoints, OAuthAuthProvider};
 pub use password::PasswordAuthProvider;
+pub use prompt::{
+    DeviceCodePrompt, PromptSecondFactor, Prompter, ScriptedPrompter, SecretKind, SecretPrompt,
+    TerminalPrompter,
+};
 pub use psk::PskAuthProvider;
+pub use second_factor::{SecondFactorChallenge, SecondFactorHandler, SecondFactorKind, StoredTotp};
+pub use secret::Secret;
+pub use secret_ref::SecretRef;
+pub use session::{SessionStore, SessionToken};
+pub use store::CredentialStore;
 
 /// Authentication method
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -28,6 +52,9 @@ pub enum AuthMethod {
 
     /// Public key authentication
     PublicKey,
+
+    /// OAuth2 device authorization with an identity provider
+    OAuth2,
 }
 
 impl fmt::Display for AuthMethod {
@@ -37,6 +64,7 @@ impl fmt::Display for AuthMethod {
             AuthMethod::Psk => write!(f, "psk"),
             AuthMethod::Native => write!(f, "native"),
             AuthMethod::PublicKey => write!(f, "publickey"),
+            AuthMethod::OAuth2 => write!(f, "oauth2"),
         }
     }
 }
@@ -49,23 +77,32 @@ impl AuthMethod {
             "psk" => Some(Self::Psk),
             "native" => Some(Self::Native),
             "publickey" => Some(Self::PublicKey),
+            "oauth2" | "oauth" => Some(Self::OAuth2),
             _ => None,
         }
     }
 }
 
 /// Authentication credentials
+///
+/// Passwords and keys are [`Secret`]s, so `Debug` output never contains them.
 #[derive(Debug, Clone)]
 pub enum Credentials {
     /// Username and password
-    Password { username: String, password: String },
+    Password { username: String, password: Secret },
 
     /// Pre-shared key
-    Psk { key: String },
+    Psk { key: Secret },
 
     /// Native OS credentials
     Native { username: String, token: Vec<u8> },
 
+    /// OAuth2 access token
+    OAuth2 {
+        username: String,
+        access_token: Secret,
+    },
+
     /// Public key credentials
     PublicKey {
         username: String,
@@ -86,16 +123,222 @@ pub trait AuthProvider: Send + Sync {
     async fn get_credentials(&self) -> Result<Credentials>;
 }
 
+/// Server that credentials and sessions belong to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AuthTarget {
+    /// Server address
+    pub address: String,
+
+    /// Server port
+    pub port: u16,
+}
+
+impl AuthTarget {
+    /// Create a new target for the given server
+    pub fn new(address: &str, port: u16) -> Self {
+        Self {
+            address: address.to_string(),
+            port,
+        }
+    }
+
+    /// Credential store key for a secret of the given kind, e.g. `password:alice@host:8717`
+    pub fn credential_key(&self, kind: &str, username: &str) -> String {
+        format!("{}:{}@{}:{}", kind, username, self.address, self.port)
+    }
+}
+
+/// Options shared by all authentication providers
+#[derive(Clone)]
+pub struct AuthOptions {
+    /// Where stored secrets are looked up and saved
+    pub store: Arc<dyn CredentialStore>,
+
+    /// How to ask the user for secrets that are not stored
+    pub prompter: Option<Arc<dyn Prompter>>,
+
+    /// Whether to offer saving secrets entered at a prompt
+    pub save_credentials: bool,
+
+    /// Source of codes when the server asks for a second factor
+    pub second_factor: Option<Arc<dyn SecondFactorHandler>>,
+
+    /// Server being authenticated against
+    pub target: Option<AuthTarget>,
+
+    /// Store for server-issued session tokens
+    pub sessions: Option<Arc<SessionStore>>,
+
+    /// Socket of the daemon signing native auth assertions for remote servers
+    pub native_helper: Option<PathBuf>,
+
+    /// Identity provider for the OAuth2 device flow
+    pub oauth: Option<OAuthConfig>,
+
+    /// Where authentication attempts are recorded
+    pub audit: Option<Arc<AuditLog>>,
+
+    /// Pre-shared key from the configuration, resolved when it is used
+    pub psk: Option<SecretRef>,
+
+    /// Password entered before connecting, used before the credential store
+    pub password: Option<Secret>,
+}
+
+impl Default for AuthOptions {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(store::KeyringStore::new(store::SERVICE_NAME)),
+            prompter: None,
+            save_credentials: false,
+            second_factor: None,
+            target: None,
+            sessions: None,
+            native_helper: None,
+            oauth: None,
+            audit: None,
+            psk: None,
+            password: None,
+        }
+    }
+}
+
+impl AuthOptions {
+    /// Use the given credential store
+    pub fn with_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Ask the user for missing secrets with the given prompter
+    pub fn with_prompter(mut self, prompter: Arc<dyn Prompter>) -> Self {
+        self.prompter = Some(prompter);
+        self
+    }
+
+    /// Offer to save secrets entered at a prompt
+    pub fn with_save_credentials(mut self, save: bool) -> Self {
+        self.save_credentials = save;
+        self
+    }
+
+    /// Set the server being authenticated against
+    pub fn with_target(mut self, target: AuthTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Keep session tokens in the given store
+    pub fn with_sessions(mut self, sessions: Arc<SessionStore>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Ask the daemon at the given socket for native auth assertions
+    pub fn with_native_helper(mut self, socket: impl Into<PathBuf>) -> Self {
+        self.native_helper = Some(socket.into());
+        self
+    }
+
+    /// Sign in with the given identity provider for the OAuth2 method
+    pub fn with_oauth(mut self, config: OAuthConfig) -> Self {
+        self.oauth = Some(config);
+        self
+    }
+
+    /// Record authentication attempts in the given audit log
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Use the pre-shared key from the configuration before the credential store
+    pub fn with_psk(mut self, psk: SecretRef) -> Self {
+        self.psk = Some(psk);
+        self
+    }
+
+    /// Use a password entered before connecting instead of the stored one
+    pub fn with_password(mut self, password: Secret) -> Self {
+        self.password = Some(password);
+        self
+    }
+
+    /// Set the second factor handler
+    pub fn with_second_factor(mut self, handler: Arc<dyn SecondFactorHandler>) -> Self {
+        self.second_factor = Some(handler);
+        self
+    }
+
+    /// Build options from the client configuration, asking the user with `prompter`
+    ///
+    /// Second factor codes are also asked for with `prompter`, unless
+    /// `totp_from_keyring` is enabled and a TOTP secret is in the credential
+    /// store.
+    pub fn from_config(config: &crate::config::ClientConfig, prompter: Arc<dyn Prompter>) -> Self {
+        let store = store::from_config(&config.auth);
+        let prompt_codes = Arc::new(PromptSecondFactor::new(prompter.clone()));
+        let target = AuthTarget::new(&config.server.address, config.server.port);
+        let handler: Arc<dyn SecondFactorHandler> = if config.auth.totp_from_keyring {
+            Arc::new(
+                StoredTotp::new(store.clone())
+                    .with_target(target.clone())
+                    .with_fallback(prompt_codes),
+            )
+        } else {
+            prompt_codes
+        };
+
+        let options = Self::default()
+            .with_store(store)
+            .with_prompter(prompter)
+            .with_save_credentials(config.auth.save_credentials)
+            .with_second_factor(handler)
+            .with_target(target);
+
+        let options = match &config.auth.native_helper_socket {
+            Some(socket) => options.with_native_helper(socket),
+            None => options,
+        };
+        let options = match &config.auth.oauth {
+            Some(oauth) => options.with_oauth(oauth.clone()),
+            None => options,
+        };
+        let options = match &config.auth.psk {
+            Some(psk) => options.with_psk(psk.clone()),
+            None => options,
+        };
+        match AuditLog::from_config(&config.audit) {
+            Some(audit) => options.with_audit(Arc::new(audit)),
+            None => options,
+        }
+    }
+}
+
 /// Create an authentication provider based on the method
 pub fn create_provider(method: AuthMethod, username: &str) -> Box<dyn AuthProvider> {
+    create_provider_with_options(method, username, AuthOptions::default())
+}
+
+/// Create an authentication provider based on the method with the given options
+pub fn create_provider_with_options(
+    method: AuthMethod,
+    username: &str,
+    options: AuthOptions,
+) -> Box<dyn AuthProvider> {
     match method {
-        AuthMethod::Password => Box::new(PasswordAuthProvider::new(username)),
-        AuthMethod::Psk => Box::new(PskAuthProvider::new()),
-        AuthMethod::Native => Box::new(NativeAuthProvider::new(username)),
+        AuthMethod::Password => Box::new(PasswordAuthProvider::new(username).with_options(options)),
+        AuthMethod::Psk => Box::new(
+            PskAuthProvider::new()
+                .with_username(username)
+                .with_options(options),
+        ),
+        AuthMethod::Native => Box::new(NativeAuthProvider::new(username).with_options(options)),
+        AuthMethod::OAuth2 => Box::new(OAuthAuthProvider::new(username).with_options(options)),
         AuthMethod::PublicKey => {
             // Not implemented yet, fall back to password auth
             log::warn!("Public key authentication not implemented yet, falling back to password");
-            Box::new(PasswordAuthProvider::new(username))
+            Box::new(PasswordAuthProvider::new(username).with_options(options))
         }
     }
 }
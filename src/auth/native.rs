@@ -1,4 +1,4 @@
-use crate::auth::{AuthError, AuthMethod, AuthProvider, Credentials};
+use crate::auth::{exchange, AuthError, AuthMethod, AuthOptions, AuthProvider, Credentials};
 use crate::protocol::Client;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -11,9 +11,17 @@ use windows::Win32::System::Com as win_com;
 #[cfg(unix)]
 use nix::unistd;
 
+/// Socket of the local daemon that signs native auth assertions, unless configured
+pub const DEFAULT_ASSERTION_SOCKET: &str = "/run/rcp/authd.sock";
+
+/// Seconds to wait for the assertion daemon to answer
+#[cfg(target_os = "linux")]
+const ASSERTION_TIMEOUT_SECS: u64 = 10;
+
 /// Native OS authentication provider
 pub struct NativeAuthProvider {
     username: String,
+    options: AuthOptions,
 }
 
 impl NativeAuthProvider {
@@ -21,9 +29,16 @@ impl NativeAuthProvider {
     pub fn new(username: &str) -> Self {
         Self {
             username: username.to_string(),
+            options: AuthOptions::default(),
         }
     }
 
+    /// Set the shared authentication options
+    pub fn with_options(mut self, options: AuthOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get the current OS username if no specific username was provided
     fn get_os_username() -> Result<String, AuthError> {
         // First try to get the username from the environment
@@ -83,9 +98,16 @@ impl NativeAuthProvider {
             return Ok(token);
         }
 
-        #[cfg(all(unix, not(target_os = "macos")))]
+        #[cfg(target_os = "linux")]
+        {
+            // Remote servers cannot see our peer credentials, so a local
+            // daemon that can vouches for us with a signed assertion
+            return self.request_assertion().await.map(String::into_bytes);
+        }
+
+        #[cfg(all(unix, not(any(target_os = "macos", target_os = "linux"))))]
         {
-            // Linux/Unix implementation would use PAM or similar
+            // Other Unix implementations would use PAM or similar
             // For now, just simulate with a random token
             use rand::{thread_rng, Rng};
             let mut token = vec![0u8; 32];
@@ -101,41 +123,168 @@ impl NativeAuthProvider {
             ))
         }
     }
-}
 
-#[async_trait]
-impl AuthProvider for NativeAuthProvider {
-    fn method(&self) -> AuthMethod {
-        AuthMethod::Native
+    /// Identity of this process for a server on the other end of a Unix socket
+    ///
+    /// The server checks these against the socket's `SO_PEERCRED`, so they
+    /// cannot be claimed for another user.
+    #[cfg(target_os = "linux")]
+    fn peer_credentials(&self) -> Result<serde_json::Value, AuthError> {
+        let uid = unistd::getuid();
+        let user = unistd::User::from_uid(uid)
+            .map_err(|e| AuthError::OsAuthFailure(format!("Failed to look up uid {}: {}", uid, e)))?
+            .ok_or_else(|| AuthError::OsAuthFailure(format!("No user with uid {}", uid)))?;
+
+        if !self.username.is_empty() && self.username != user.name {
+            return Err(AuthError::OsAuthFailure(format!(
+                "Running as {}, cannot authenticate as {} over a local socket",
+                user.name, self.username
+            )));
+        }
+
+        Ok(json!({
+            "username": user.name,
+            "method": "native",
+            "mechanism": "peercred",
+            "uid": uid.as_raw(),
+            "gid": unistd::getgid().as_raw(),
+            "pid": unistd::getpid().as_raw(),
+        }))
     }
 
-    async fn authenticate(&self, client: &Client) -> Result<bool> {
-        let credentials = self.get_credentials().await?;
+    /// Ask the local assertion daemon to vouch for us to the target server
+    ///
+    /// The daemon identifies the caller by `SO_PEERCRED` and answers a
+    /// `{"username", "server", "port"}` line with `{"assertion": "..."}` or
+    /// `{"error": "..."}`.
+    #[cfg(target_os = "linux")]
+    async fn request_assertion(&self) -> Result<String, AuthError> {
+        use std::time::Duration;
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::UnixStream;
 
+        let socket = self
+            .options
+            .native_helper
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ASSERTION_SOCKET.into());
+        let failure = |e: std::io::Error| {
+            AuthError::OsAuthFailure(format!(
+                "Assertion daemon at {} failed: {}",
+                socket.display(),
+                e
+            ))
+        };
+
+        let username = if self.username.is_empty() {
+            Self::get_os_username()?
+        } else {
+            self.username.clone()
+        };
+        let (server, port) = match &self.options.target {
+            Some(target) => (target.address.clone(), target.port),
+            None => {
+                return Err(AuthError::OsAuthFailure(
+                    "No server to request an assertion for".to_string(),
+                ))
+            }
+        };
+
+        let mut request = json!({
+            "username": username,
+            "server": server,
+            "port": port,
+        })
+        .to_string();
+        request.push('\n');
+
+        // A stalled daemon must not hang the connect
+        let exchange = async {
+            let mut stream = UnixStream::connect(&socket).await?;
+            stream.write_all(request.as_bytes()).await?;
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).await?;
+            Ok(line)
+        };
+        let line = tokio::time::timeout(Duration::from_secs(ASSERTION_TIMEOUT_SECS), exchange)
+            .await
+            .map_err(|_| {
+                AuthError::OsAuthFailure(format!(
+                    "Assertion daemon at {} did not answer within {} seconds",
+                    socket.display(),
+                    ASSERTION_TIMEOUT_SECS
+                ))
+            })?
+            .map_err(failure)?;
+        let reply: serde_json::Value = serde_json::from_str(&line).map_err(|e| {
+            AuthError::OsAuthFailure(format!("Malformed reply from assertion daemon: {}", e))
+        })?;
+
+        if let Some(assertion) = reply.get("assertion").and_then(|a| a.as_str()) {
+            return Ok(assertion.to_string());
+        }
+        Err(AuthError::OsAuthFailure(
+            reply
+                .get("error")
+                .and_then(|e| e.as_str())
+                .unwrap_or("assertion daemon gave no assertion")
+                .to_string(),
+        ))
+    }
+
+    /// Build the first authentication message
+    ///
+    /// Local servers reached over a Unix socket get our peer credentials;
+    /// others get an assertion from the local daemon.
+    #[cfg(target_os = "linux")]
+    async fn auth_payload(&self, client: &Client) -> Result<serde_json::Value> {
+        if client.unix_socket().is_some() {
+            return Ok(self.peer_credentials()?);
+        }
+
+        let (username, token) = match self.get_credentials().await? {
+            Credentials::Native { username, token } => (username, token),
+            _ => return Err(AuthError::InvalidCredentials.into()),
+        };
+
+        Ok(json!({
+            "username": username,
+            "method": "native",
+            "mechanism": "assertion",
+            "credentials": String::from_utf8_lossy(&token),
+        }))
+    }
+
+    /// Build the first authentication message
+    #[cfg(not(target_os = "linux"))]
+    async fn auth_payload(&self, _client: &Client) -> Result<serde_json::Value> {
         // Extract username and token
-        let (username, token) = match credentials {
+        let (username, token) = match self.get_credentials().await? {
             Credentials::Native { username, token } => (username, token),
             _ => return Err(AuthError::InvalidCredentials.into()),
         };
 
-        // Send authentication message
-        let auth_message = crate::protocol::Message::new(
-            crate::protocol::MessageType::Auth,
-            json!({
-                "username": username,
-                "credentials": token,
-                "method": "native",
-                "os": os_info::get().os_type().to_string(),
-            }),
-        );
+        Ok(json!({
+            "username": username,
+            "credentials": token,
+            "method": "native",
+            "os": os_info::get().os_type().to_string(),
+        }))
+    }
+}
 
-        client.send(auth_message).await?;
+#[async_trait]
+impl AuthProvider for NativeAuthProvider {
+    fn method(&self) -> AuthMethod {
+        AuthMethod::Native
+    }
 
-        // Wait for response with a timeout
-        // TODO: Implement response handling in the Client
-        // For now, assume authentication was successful
+    async fn authenticate(&self, client: &Client) -> Result<bool> {
+        let payload = self.auth_payload(client).await?;
+        let username = payload["username"].as_str().unwrap_or_default().to_string();
 
-        Ok(true)
+        // Send authentication message and complete any follow-up steps
+        exchange::run(client, payload, &username, &self.options).await
     }
 
     async fn get_credentials(&self) -> Result<Credentials> {
@@ -0,0 +1,242 @@
+//! Authentication method negotiation
+//!
+//! With `auth.method = "auto"`, the client asks the server which methods it
+//! accepts and tries them in the order given by `auth.methods`, stopping at the
+//! first success. Servers that do not advertise their methods are assumed to
+//! accept all of them.
+//!
+//! Every method tried is recorded in the audit log, if one is configured.
+
+use crate::audit::{AuditEvent, AuditRecord};
+use crate::auth::{
+    create_provider_with_options, exchange, session, AuthError, AuthMethod, AuthOptions,
+};
+use crate::config::AuthConfig;
+use crate::protocol::{Client, ProtocolError};
+use anyhow::Result;
+use std::fmt;
+
+/// Method name that enables negotiation
+pub const AUTO_METHOD: &str = "auto";
+
+/// Outcome of one method during negotiation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    /// The server accepted the method
+    Succeeded,
+
+    /// The server rejected the credentials
+    Rejected,
+
+    /// The method failed before or during the exchange
+    Failed(String),
+
+    /// The server does not accept the method, so it was skipped
+    NotOffered,
+
+    /// This client cannot use the method, so it was skipped
+    Unsupported,
+}
+
+impl fmt::Display for AttemptOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttemptOutcome::Succeeded => write!(f, "succeeded"),
+            AttemptOutcome::Rejected => write!(f, "rejected by the server"),
+            AttemptOutcome::Failed(reason) => write!(f, "failed: {}", reason),
+            AttemptOutcome::NotOffered => write!(f, "not offered by the server"),
+            AttemptOutcome::Unsupported => write!(f, "not supported by this client"),
+        }
+    }
+}
+
+/// A method considered during negotiation
+#[derive(Debug, Clone)]
+pub struct MethodAttempt {
+    /// Method that was considered
+    pub method: AuthMethod,
+
+    /// What happened
+    pub outcome: AttemptOutcome,
+}
+
+/// Record of every method considered during negotiation
+#[derive(Debug, Clone, Default)]
+pub struct NegotiationReport {
+    /// Methods in the order they were considered
+    pub attempts: Vec<MethodAttempt>,
+}
+
+impl NegotiationReport {
+    /// Method that succeeded, if any
+    pub fn succeeded(&self) -> Option<AuthMethod> {
+        self.attempts
+            .iter()
+            .find(|a| a.outcome == AttemptOutcome::Succeeded)
+            .map(|a| a.method)
+    }
+
+    fn record(&mut self, method: AuthMethod, outcome: AttemptOutcome) {
+        log::info!("Authentication method {}: {}", method, outcome);
+        self.attempts.push(MethodAttempt { method, outcome });
+    }
+}
+
+impl fmt::Display for NegotiationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.attempts.is_empty() {
+            return write!(f, "no methods configured");
+        }
+
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", attempt.method, attempt.outcome)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a preference list of method names
+pub fn parse_preference(methods: &[String]) -> Result<Vec<AuthMethod>, AuthError> {
+    methods
+        .iter()
+        .map(|name| {
+            AuthMethod::from_str(name).ok_or_else(|| AuthError::UnsupportedMethod(name.clone()))
+        })
+        .collect()
+}
+
+/// Whether this client has a provider for the method
+fn is_supported(method: AuthMethod) -> bool {
+    !matches!(method, AuthMethod::PublicKey)
+}
+
+/// Whether an error means the connection is unusable for further attempts
+fn is_fatal(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<ProtocolError>().is_some()
+        || matches!(
+            error.downcast_ref::<AuthError>(),
+            Some(AuthError::Timeout) | Some(AuthError::Protocol(_))
+        )
+}
+
+/// Record an authentication attempt in the audit log
+fn audit(options: &AuthOptions, username: &str, method: &str, result: &Result<bool>) {
+    let log = match &options.audit {
+        Some(log) => log,
+        None => return,
+    };
+
+    let server = options
+        .target
+        .as_ref()
+        .map(|t| format!("{}:{}", t.address, t.port))
+        .unwrap_or_default();
+    let record = AuditRecord::new(AuditEvent::Auth, &server)
+        .with_username(username)
+        .with_method(method);
+
+    log.log(match result {
+        Ok(true) => record,
+        Ok(false) => record.failed(AttemptOutcome::Rejected),
+        Err(e) => record.failed(format!("{:#}", e)),
+    });
+}
+
+/// Try the preferred methods that the server accepts until one succeeds
+pub async fn negotiate(
+    client: &Client,
+    username: &str,
+    preference: &[AuthMethod],
+    options: &AuthOptions,
+) -> Result<NegotiationReport> {
+    let offered = exchange::query_methods(client, username)
+        .await?
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| AuthMethod::from_str(name))
+                .collect::<Vec<_>>()
+        });
+
+    match &offered {
+        Some(offered) => log::info!(
+            "Server accepts: {}",
+            offered
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => log::info!("Server did not advertise its methods, trying all preferred"),
+    }
+
+    let mut report = NegotiationReport::default();
+    for &method in preference {
+        if !is_supported(method) {
+            report.record(method, AttemptOutcome::Unsupported);
+            continue;
+        }
+        if offered.as_ref().is_some_and(|o| !o.contains(&method)) {
+            report.record(method, AttemptOutcome::NotOffered);
+            continue;
+        }
+
+        let provider = create_provider_with_options(method, username, options.clone());
+        let result = provider.authenticate(client).await;
+        audit(options, username, &method.to_string(), &result);
+        match result {
+            Ok(true) => {
+                report.record(method, AttemptOutcome::Succeeded);
+                break;
+            }
+            Ok(false) => report.record(method, AttemptOutcome::Rejected),
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => report.record(method, AttemptOutcome::Failed(e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Authenticate as described by the configuration
+///
+/// A stored session token is presented first. Then either the configured
+/// method is used, or with `method = "auto"` the methods are negotiated; if
+/// none succeeds the error lists what was tried and why it failed.
+pub async fn authenticate(
+    client: &Client,
+    config: &AuthConfig,
+    username: &str,
+    options: &AuthOptions,
+) -> Result<bool> {
+    if session::try_resume(client, username, options).await? {
+        audit(options, username, "session", &Ok(true));
+        return Ok(true);
+    }
+
+    if config.method.eq_ignore_ascii_case(AUTO_METHOD) {
+        let preference = parse_preference(&config.methods)?;
+        let report = negotiate(client, username, &preference, options).await?;
+
+        return match report.succeeded() {
+            Some(method) => {
+                log::info!("Authenticated with method: {}", method);
+                Ok(true)
+            }
+            None => Err(AuthError::NoAcceptableMethod(report.to_string()).into()),
+        };
+    }
+
+    let method = AuthMethod::from_str(&config.method)
+        .ok_or_else(|| AuthError::UnsupportedMethod(config.method.clone()))?;
+
+    log::info!("Authenticating with method: {}", method);
+    let result = create_provider_with_options(method, username, options.clone())
+        .authenticate(client)
+        .await;
+    audit(options, username, &method.to_string(), &result);
+    result
+}
@@ -0,0 +1,386 @@
+//! OAuth2 device authorization (RFC 8628)
+//!
+//! The client asks the identity provider for a device code, shows the user
+//! where to sign in, and polls the token endpoint until sign-in is approved.
+//! The access token is sent to the RCP server as
+//! `{"method": "oauth2", "username": "...", "credentials": "<access token>"}`.
+//!
+//! Refresh tokens are kept in the credential store under
+//! `oauth2-refresh:<username>@<server>:<port>` and tried before starting a new
+//! device flow.
+
+use crate::auth::{
+    exchange, AuthError, AuthMethod, AuthOptions, AuthProvider, Credentials, DeviceCodePrompt,
+    Secret,
+};
+use crate::config::OAuthConfig;
+use crate::protocol::Client;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Grant type for polling the token endpoint
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Seconds between polls when the identity provider does not say
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Seconds to wait for the identity provider to answer a request
+const HTTP_TIMEOUT_SECS: u64 = 30;
+
+/// Endpoints of the identity provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    /// Where device codes are requested
+    pub device_authorization: String,
+
+    /// Where tokens are requested
+    pub token: String,
+}
+
+/// Relevant part of the OpenID Connect discovery document
+#[derive(Deserialize)]
+struct Discovery {
+    device_authorization_endpoint: Option<String>,
+    token_endpoint: String,
+}
+
+/// Device authorization response
+#[derive(Deserialize)]
+struct DeviceAuthorization {
+    device_code: Secret,
+    user_code: String,
+    verification_uri: String,
+    #[serde(default)]
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default)]
+    interval: Option<u64>,
+}
+
+/// Token endpoint response, either tokens or an error
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    access_token: Option<Secret>,
+    #[serde(default)]
+    refresh_token: Option<Secret>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+impl TokenResponse {
+    /// Error text for a failed request
+    fn error_message(&self) -> String {
+        match (&self.error, &self.error_description) {
+            (Some(error), Some(description)) => format!("{}: {}", error, description),
+            (Some(error), None) => error.clone(),
+            _ => "token endpoint returned no access token".to_string(),
+        }
+    }
+}
+
+/// OAuth2 device authorization provider
+pub struct OAuthAuthProvider {
+    username: String,
+    options: AuthOptions,
+}
+
+impl OAuthAuthProvider {
+    /// Create a new OAuth2 provider for the given username
+    pub fn new(username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            options: AuthOptions::default(),
+        }
+    }
+
+    /// Set the shared authentication options
+    pub fn with_options(mut self, options: AuthOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Identity provider configuration
+    fn config(&self) -> Result<&OAuthConfig, AuthError> {
+        self.options
+            .oauth
+            .as_ref()
+            .ok_or_else(|| AuthError::OAuth("No identity provider configured".to_string()))
+    }
+
+    /// Credential store key for this user's refresh token on the target server
+    fn store_key(&self) -> String {
+        match &self.options.target {
+            Some(target) => target.credential_key("oauth2-refresh", &self.username),
+            None => format!("oauth2-refresh:{}", self.username),
+        }
+    }
+
+    /// Remove the saved refresh token from the credential store
+    pub async fn forget_refresh_token(&self) -> Result<(), AuthError> {
+        self.options.store.delete(&self.store_key())
+    }
+
+    /// Find the endpoints, from the configuration or by discovery
+    pub async fn endpoints(&self, http: &reqwest::Client) -> Result<Endpoints, AuthError> {
+        let config = self.config()?;
+        if let (Some(device_authorization), Some(token)) = (
+            &config.device_authorization_endpoint,
+            &config.token_endpoint,
+        ) {
+            return Ok(Endpoints {
+                device_authorization: device_authorization.clone(),
+                token: token.clone(),
+            });
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer_url.trim_end_matches('/')
+        );
+        let discovery: Discovery = http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AuthError::OAuth(format!("Discovery at {} failed: {}", url, e)))?
+            .json()
+            .await
+            .map_err(|e| AuthError::OAuth(format!("Malformed discovery document: {}", e)))?;
+
+        let device_authorization = config
+            .device_authorization_endpoint
+            .clone()
+            .or(discovery.device_authorization_endpoint)
+            .ok_or_else(|| {
+                AuthError::OAuth(format!(
+                    "{} does not support the device flow",
+                    config.issuer_url
+                ))
+            })?;
+
+        Ok(Endpoints {
+            device_authorization,
+            token: config
+                .token_endpoint
+                .clone()
+                .unwrap_or(discovery.token_endpoint),
+        })
+    }
+
+    /// Post a form to the token endpoint
+    async fn request_token(
+        http: &reqwest::Client,
+        endpoint: &str,
+        form: &[(&str, &str)],
+    ) -> Result<TokenResponse, AuthError> {
+        let response = http
+            .post(endpoint)
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| AuthError::OAuth(format!("Request to {} failed: {}", endpoint, e)))?;
+
+        // Errors come back as JSON with a 400 status, so the status is not checked
+        let status = response.status();
+        response.json().await.map_err(|e| {
+            AuthError::OAuth(format!(
+                "Malformed token response ({}) from {}: {}",
+                status, endpoint, e
+            ))
+        })
+    }
+
+    /// Exchange a stored refresh token for an access token
+    async fn refresh(
+        &self,
+        http: &reqwest::Client,
+        endpoints: &Endpoints,
+    ) -> Result<Option<TokenResponse>, AuthError> {
+        let refresh_token = match self.options.store.get(&self.store_key())? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        let config = self.config()?;
+        let response = Self::request_token(
+            http,
+            &endpoints.token,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.expose()),
+                ("client_id", &config.client_id),
+            ],
+        )
+        .await?;
+
+        if response.access_token.is_some() {
+            return Ok(Some(response));
+        }
+
+        // The refresh token expired or was revoked; sign in again
+        log::info!("Refresh token not accepted: {}", response.error_message());
+        if let Err(e) = self.forget_refresh_token().await {
+            log::warn!("Failed to remove refresh token: {}", e);
+        }
+        Ok(None)
+    }
+
+    /// Run the device flow, showing the user where to sign in
+    async fn device_flow(
+        &self,
+        http: &reqwest::Client,
+        endpoints: &Endpoints,
+    ) -> Result<TokenResponse, AuthError> {
+        let config = self.config()?;
+        let mut form = vec![("client_id", config.client_id.as_str())];
+        if let Some(scope) = &config.scope {
+            form.push(("scope", scope));
+        }
+
+        let authorization: DeviceAuthorization = http
+            .post(&endpoints.device_authorization)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AuthError::OAuth(format!("Device authorization failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AuthError::OAuth(format!("Malformed device authorization: {}", e)))?;
+
+        let prompt = DeviceCodePrompt {
+            verification_uri: authorization.verification_uri.clone(),
+            verification_uri_complete: authorization.verification_uri_complete.clone(),
+            user_code: authorization.user_code.clone(),
+        };
+        match &self.options.prompter {
+            Some(prompter) => prompter.show_device_code(Some(&prompt)).await,
+            None => log::warn!("{}", prompt),
+        }
+
+        let result = self.poll(http, endpoints, &authorization).await;
+
+        if let Some(prompter) = &self.options.prompter {
+            prompter.show_device_code(None).await;
+        }
+        result
+    }
+
+    /// Poll the token endpoint until the user has signed in
+    async fn poll(
+        &self,
+        http: &reqwest::Client,
+        endpoints: &Endpoints,
+        authorization: &DeviceAuthorization,
+    ) -> Result<TokenResponse, AuthError> {
+        let config = self.config()?;
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = authorization
+            .interval
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS)
+            .max(1);
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if Instant::now() >= deadline {
+                return Err(AuthError::OAuth(
+                    "Sign-in was not completed in time".to_string(),
+                ));
+            }
+
+            let response = Self::request_token(
+                http,
+                &endpoints.token,
+                &[
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("device_code", authorization.device_code.expose()),
+                    ("client_id", &config.client_id),
+                ],
+            )
+            .await?;
+
+            if response.access_token.is_some() {
+                return Ok(response);
+            }
+
+            match response.error.as_deref() {
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += 5,
+                Some("access_denied") => return Err(AuthError::UserDeclined),
+                Some("expired_token") => {
+                    return Err(AuthError::OAuth(
+                        "Sign-in was not completed in time".to_string(),
+                    ))
+                }
+                _ => return Err(AuthError::OAuth(response.error_message())),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for OAuthAuthProvider {
+    fn method(&self) -> AuthMethod {
+        AuthMethod::OAuth2
+    }
+
+    async fn authenticate(&self, client: &Client) -> Result<bool> {
+        let (username, access_token) = match self.get_credentials().await? {
+            Credentials::OAuth2 {
+                username,
+                access_token,
+            } => (username, access_token),
+            _ => return Err(AuthError::InvalidCredentials.into()),
+        };
+
+        // Send authentication message and complete any follow-up steps
+        let payload = json!({
+            "username": username,
+            "method": "oauth2",
+            "credentials": access_token.expose(),
+        });
+
+        exchange::run(client, payload, &username, &self.options).await
+    }
+
+    async fn get_credentials(&self) -> Result<Credentials> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| AuthError::OAuth(e.to_string()))?;
+        let endpoints = self.endpoints(&http).await?;
+
+        let response = match self.refresh(&http, &endpoints).await? {
+            Some(response) => response,
+            None => self.device_flow(&http, &endpoints).await?,
+        };
+
+        // A sign-in that completed is used even if the token cannot be kept,
+        // as with a read-only store
+        if let Some(refresh_token) = &response.refresh_token {
+            if let Err(e) = self
+                .options
+                .store
+                .set(&self.store_key(), refresh_token.expose())
+            {
+                log::warn!("Failed to save refresh token: {}", e);
+            }
+        }
+
+        let access_token = response
+            .access_token
+            .ok_or_else(|| AuthError::OAuth("No access token issued".to_string()))?;
+
+        Ok(Credentials::OAuth2 {
+            username: self.username.clone(),
+            access_token,
+        })
+    }
+}
@@ -1,4 +1,7 @@
-use crate::auth::{AuthError, AuthMethod, AuthProvider, Credentials};
+use crate::auth::{
+    exchange, store, AuthError, AuthMethod, AuthOptions, AuthProvider, Credentials, Secret,
+    SecretKind, SecretPrompt,
+};
 use crate::protocol::Client;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,7 +10,8 @@ use serde_json::json;
 /// Password authentication provider
 pub struct PasswordAuthProvider {
     username: String,
-    password: Option<String>,
+    password: Option<Secret>,
+    options: AuthOptions,
 }
 
 impl PasswordAuthProvider {
@@ -16,40 +20,97 @@ impl PasswordAuthProvider {
         Self {
             username: username.to_string(),
             password: None,
+            options: AuthOptions::default(),
         }
     }
 
     /// Set the password for this provider
     pub fn with_password(mut self, password: &str) -> Self {
-        self.password = Some(password.to_string());
+        self.password = Some(Secret::new(password));
         self
     }
 
-    /// Get the password from the keyring if available
-    async fn get_password_from_keyring(&self) -> Result<Option<String>, keyring::Error> {
-        let service = "rcp-client";
-        let entry = keyring::Entry::new(service, &self.username)?;
-        match entry.get_password() {
-            Ok(password) => Ok(Some(password)),
-            Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(e),
+    /// Set the shared authentication options
+    pub fn with_options(mut self, options: AuthOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Credential store key for this user's password on the target server
+    ///
+    /// Without a target the password is keyed by username only.
+    fn store_key(&self) -> String {
+        match &self.options.target {
+            Some(target) => target.credential_key("password", &self.username),
+            None => self.username.clone(),
         }
     }
 
-    /// Save the password to the keyring
-    async fn save_password_to_keyring(&self, password: &str) -> Result<(), keyring::Error> {
-        let service = "rcp-client";
-        let entry = keyring::Entry::new(service, &self.username)?;
-        entry.set_password(password)
+    /// Get the password from the credential store if available
+    async fn get_password_from_store(&self) -> Result<Option<Secret>, AuthError> {
+        store::get_or_migrate(
+            self.options.store.as_ref(),
+            &self.store_key(),
+            &self.username,
+        )
+    }
+
+    /// Save the password to the credential store
+    pub async fn save_password_to_store(&self, password: &Secret) -> Result<(), AuthError> {
+        self.options.store.set(&self.store_key(), password.expose())
+    }
+
+    /// Remove the saved password from the credential store
+    pub async fn forget_password(&self) -> Result<(), AuthError> {
+        self.options.store.delete(&self.store_key())
     }
 
     /// Prompt the user for a password
-    async fn prompt_for_password(&self) -> Result<String, AuthError> {
-        // In a real implementation, this would show a GUI dialog
-        // For now, just return an error
-        Err(AuthError::Other(
-            "Password dialog not implemented".to_string(),
-        ))
+    async fn prompt_for_password(&self) -> Result<Secret, AuthError> {
+        let prompter =
+            self.options.prompter.as_ref().ok_or_else(|| {
+                AuthError::Other(format!("No stored password for {}", self.username))
+            })?;
+
+        let prompt = SecretPrompt::new(
+            SecretKind::Password,
+            &format!("Password for {}", self.username),
+        );
+        prompter.prompt_secret(&prompt).await
+    }
+
+    /// Offer to save a password entered at the prompt
+    async fn offer_to_save(&self, password: &Secret) -> Result<(), AuthError> {
+        let prompter = match &self.options.prompter {
+            Some(prompter) if self.options.save_credentials => prompter,
+            _ => return Ok(()),
+        };
+
+        let question = format!(
+            "Save the password for {} in the {} store?",
+            self.username,
+            self.options.store.name()
+        );
+        if prompter.confirm(&question).await? {
+            self.save_password_to_store(password).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Find the password, returning whether it was entered at the prompt
+    async fn resolve_password(&self) -> Result<(Secret, bool), AuthError> {
+        // If we already have a password, use it
+        if let Some(password) = self.password.as_ref().or(self.options.password.as_ref()) {
+            return Ok((password.clone(), false));
+        }
+
+        // Try to get the password from the credential store
+        match self.get_password_from_store().await? {
+            Some(password) => Ok((password, false)),
+            // Prompt the user for a password
+            None => Ok((self.prompt_for_password().await?, true)),
+        }
     }
 }
 
@@ -60,59 +121,32 @@ impl AuthProvider for PasswordAuthProvider {
     }
 
     async fn authenticate(&self, client: &Client) -> Result<bool> {
-        let credentials = self.get_credentials().await?;
-
-        // Extract username and password
-        let (username, password) = match credentials {
-            Credentials::Password { username, password } => (username, password),
-            _ => return Err(AuthError::InvalidCredentials.into()),
-        };
+        let (password, prompted) = self.resolve_password().await?;
 
-        // Send authentication message
-        let auth_message = crate::protocol::Message::new(
-            crate::protocol::MessageType::Auth,
-            json!({
-                "username": username,
-                "credentials": password,
-                "method": "password",
-            }),
-        );
+        // Send authentication message and complete any follow-up steps
+        let payload = json!({
+            "username": self.username,
+            "credentials": password.expose(),
+            "method": "password",
+        });
 
-        client.send(auth_message).await?;
+        let authenticated = exchange::run(client, payload, &self.username, &self.options).await?;
 
-        // Wait for response with a timeout
-        // TODO: Implement response handling in the Client
-        // For now, assume authentication was successful
+        // Only offer to save passwords the server accepted
+        if authenticated && prompted {
+            if let Err(e) = self.offer_to_save(&password).await {
+                log::warn!("Failed to save password: {}", e);
+            }
+        }
 
-        Ok(true)
+        Ok(authenticated)
     }
 
     async fn get_credentials(&self) -> Result<Credentials> {
-        // If we already have a password, use it
-        if let Some(password) = &self.password {
-            return Ok(Credentials::Password {
-                username: self.username.clone(),
-                password: password.clone(),
-            });
-        }
-
-        // Try to get the password from the keyring
-        match self.get_password_from_keyring().await {
-            Ok(Some(password)) => {
-                return Ok(Credentials::Password {
-                    username: self.username.clone(),
-                    password,
-                });
-            }
-            Ok(None) => {
-                // Prompt the user for a password
-                let password = self.prompt_for_password().await?;
-                Ok(Credentials::Password {
-                    username: self.username.clone(),
-                    password,
-                })
-            }
-            Err(e) => Err(AuthError::KeyringError(e).into()),
-        }
+        let (password, _) = self.resolve_password().await?;
+        Ok(Credentials::Password {
+            username: self.username.clone(),
+            password,
+        })
     }
 }
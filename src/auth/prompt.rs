@@ -0,0 +1,228 @@
+use crate::auth::{AuthError, SecondFactorChallenge, SecondFactorHandler, Secret};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// Kind of secret being asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    /// Account password
+    Password,
+
+    /// Pre-shared key
+    Psk,
+
+    /// Second factor code
+    OneTimeCode,
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretKind::Password => write!(f, "password"),
+            SecretKind::Psk => write!(f, "pre-shared key"),
+            SecretKind::OneTimeCode => write!(f, "one-time code"),
+        }
+    }
+}
+
+/// A request for a secret from the user
+#[derive(Debug, Clone)]
+pub struct SecretPrompt {
+    /// Kind of secret being asked for
+    pub kind: SecretKind,
+
+    /// Text to show to the user
+    pub message: String,
+}
+
+impl SecretPrompt {
+    /// Create a new prompt
+    pub fn new(kind: SecretKind, message: &str) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Where to sign in during the OAuth2 device flow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCodePrompt {
+    /// Page where the user enters the code
+    pub verification_uri: String,
+
+    /// Page with the code already filled in, if the identity provider has one
+    pub verification_uri_complete: Option<String>,
+
+    /// Code to enter on the page
+    pub user_code: String,
+}
+
+impl fmt::Display for DeviceCodePrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "To sign in, open {} and enter the code {}",
+            self.verification_uri, self.user_code
+        )
+    }
+}
+
+/// Interaction with the user during authentication
+#[async_trait]
+pub trait Prompter: Send + Sync {
+    /// Ask for a secret; the input must not be echoed
+    ///
+    /// Returns `AuthError::UserDeclined` if the user cancels.
+    async fn prompt_secret(&self, prompt: &SecretPrompt) -> Result<Secret, AuthError>;
+
+    /// Ask a yes/no question
+    async fn confirm(&self, question: &str) -> Result<bool, AuthError>;
+
+    /// Show where to sign in, or `None` once sign-in has finished or failed
+    async fn show_device_code(&self, prompt: Option<&DeviceCodePrompt>) {
+        if let Some(prompt) = prompt {
+            log::info!("{}", prompt);
+        }
+    }
+}
+
+/// Prompter reading from the terminal
+pub struct TerminalPrompter;
+
+#[async_trait]
+impl Prompter for TerminalPrompter {
+    async fn prompt_secret(&self, prompt: &SecretPrompt) -> Result<Secret, AuthError> {
+        let message = format!("{}: ", prompt.message);
+
+        let secret = tokio::task::spawn_blocking(move || rpassword::prompt_password(message))
+            .await
+            .map_err(|e| AuthError::Other(e.to_string()))?
+            .map(Secret::new)
+            .map_err(|e| AuthError::Other(format!("Failed to read input: {}", e)))?;
+
+        if secret.is_empty() {
+            Err(AuthError::UserDeclined)
+        } else {
+            Ok(secret)
+        }
+    }
+
+    async fn confirm(&self, question: &str) -> Result<bool, AuthError> {
+        let question = question.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "{} [y/N]: ", question);
+            let _ = stderr.flush();
+
+            let mut line = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(|e| AuthError::Other(format!("Failed to read input: {}", e)))?;
+
+            Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"))
+        })
+        .await
+        .map_err(|e| AuthError::Other(e.to_string()))?
+    }
+
+    async fn show_device_code(&self, prompt: Option<&DeviceCodePrompt>) {
+        if let Some(prompt) = prompt {
+            eprintln!("{}", prompt);
+            if let Some(uri) = &prompt.verification_uri_complete {
+                eprintln!("Or open {}", uri);
+            }
+            eprintln!("Waiting for sign-in...");
+        }
+    }
+}
+
+/// Prompter answering from a script, for tests
+///
+/// Secrets and confirmations are answered in order; running out of answers
+/// behaves like the user cancelling. The prompts, questions and device codes
+/// that were shown can be inspected with [`ScriptedPrompter::asked`].
+#[derive(Default)]
+pub struct ScriptedPrompter {
+    secrets: Mutex<VecDeque<Secret>>,
+    confirmations: Mutex<VecDeque<bool>>,
+    asked: Mutex<Vec<String>>,
+}
+
+impl ScriptedPrompter {
+    /// Create a new prompter with no answers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an answer for the next secret prompt
+    pub fn with_secret(self, secret: &str) -> Self {
+        self.secrets.lock().unwrap().push_back(Secret::new(secret));
+        self
+    }
+
+    /// Queue an answer for the next confirmation
+    pub fn with_confirmation(self, answer: bool) -> Self {
+        self.confirmations.lock().unwrap().push_back(answer);
+        self
+    }
+
+    /// Messages of all prompts and questions shown so far
+    pub fn asked(&self) -> Vec<String> {
+        self.asked.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Prompter for ScriptedPrompter {
+    async fn prompt_secret(&self, prompt: &SecretPrompt) -> Result<Secret, AuthError> {
+        self.asked.lock().unwrap().push(prompt.message.clone());
+        self.secrets
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(AuthError::UserDeclined)
+    }
+
+    async fn confirm(&self, question: &str) -> Result<bool, AuthError> {
+        self.asked.lock().unwrap().push(question.to_string());
+        Ok(self
+            .confirmations
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(false))
+    }
+
+    async fn show_device_code(&self, prompt: Option<&DeviceCodePrompt>) {
+        if let Some(prompt) = prompt {
+            self.asked.lock().unwrap().push(prompt.to_string());
+        }
+    }
+}
+
+/// Second factor handler that asks the user through a [`Prompter`]
+pub struct PromptSecondFactor {
+    prompter: Arc<dyn Prompter>,
+}
+
+impl PromptSecondFactor {
+    /// Create a new handler using the given prompter
+    pub fn new(prompter: Arc<dyn Prompter>) -> Self {
+        Self { prompter }
+    }
+}
+
+#[async_trait]
+impl SecondFactorHandler for PromptSecondFactor {
+    async fn one_time_code(&self, challenge: &SecondFactorChallenge) -> Result<String, AuthError> {
+        let prompt = SecretPrompt::new(SecretKind::OneTimeCode, &challenge.prompt());
+        let code = self.prompter.prompt_secret(&prompt).await?;
+        Ok(code.expose().trim().to_string())
+    }
+}
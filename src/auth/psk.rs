@@ -1,4 +1,7 @@
-use crate::auth::{AuthError, AuthMethod, AuthProvider, Credentials};
+use crate::auth::{
+    exchange, store, AuthError, AuthMethod, AuthOptions, AuthProvider, Credentials, Secret,
+    SecretKind, SecretPrompt,
+};
 use crate::protocol::Client;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -6,48 +9,112 @@ use serde_json::json;
 
 /// Pre-shared key authentication provider
 pub struct PskAuthProvider {
-    key: Option<String>,
+    username: String,
+    key: Option<Secret>,
+    options: AuthOptions,
 }
 
 impl PskAuthProvider {
     /// Create a new PSK authentication provider
     pub fn new() -> Self {
-        Self { key: None }
+        Self {
+            username: String::new(),
+            key: None,
+            options: AuthOptions::default(),
+        }
+    }
+
+    /// Set the user the key is stored for
+    pub fn with_username(mut self, username: &str) -> Self {
+        self.username = username.to_string();
+        self
     }
 
     /// Set the pre-shared key
     pub fn with_key(mut self, key: &str) -> Self {
-        self.key = Some(key.to_string());
+        self.key = Some(Secret::new(key));
         self
     }
 
-    /// Try to load the PSK from the keyring
-    async fn load_key_from_keyring(&self) -> Result<Option<String>, keyring::Error> {
-        let service = "rcp-client";
-        let username = "psk"; // Using "psk" as the username for the keyring
+    /// Set the shared authentication options
+    pub fn with_options(mut self, options: AuthOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-        let entry = keyring::Entry::new(service, username)?;
-        match entry.get_password() {
-            Ok(key) => Ok(Some(key)),
-            Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(e),
+    /// Credential store key for the PSK of the target server
+    ///
+    /// Without a target a single PSK is stored under `psk`.
+    fn store_key(&self) -> String {
+        match &self.options.target {
+            Some(target) => target.credential_key("psk", &self.username),
+            None => "psk".to_string(),
         }
     }
 
-    /// Save the PSK to the keyring
-    async fn save_key_to_keyring(&self, key: &str) -> Result<(), keyring::Error> {
-        let service = "rcp-client";
-        let username = "psk"; // Using "psk" as the username for the keyring
+    /// Try to load the PSK from the credential store
+    async fn load_key_from_store(&self) -> Result<Option<Secret>, AuthError> {
+        store::get_or_migrate(self.options.store.as_ref(), &self.store_key(), "psk")
+    }
 
-        let entry = keyring::Entry::new(service, username)?;
-        entry.set_password(key)
+    /// Save the PSK to the credential store
+    pub async fn save_key_to_store(&self, key: &Secret) -> Result<(), AuthError> {
+        self.options.store.set(&self.store_key(), key.expose())
+    }
+
+    /// Remove the saved PSK from the credential store
+    pub async fn forget_key(&self) -> Result<(), AuthError> {
+        self.options.store.delete(&self.store_key())
     }
 
     /// Prompt the user for a PSK
-    async fn prompt_for_key(&self) -> Result<String, AuthError> {
-        // In a real implementation, this would show a GUI dialog
-        // For now, just return an error
-        Err(AuthError::Other("PSK dialog not implemented".to_string()))
+    async fn prompt_for_key(&self) -> Result<Secret, AuthError> {
+        let prompter = self
+            .options
+            .prompter
+            .as_ref()
+            .ok_or_else(|| AuthError::Other("No stored pre-shared key".to_string()))?;
+
+        let prompt = SecretPrompt::new(SecretKind::Psk, "Pre-shared key");
+        prompter.prompt_secret(&prompt).await
+    }
+
+    /// Offer to save a PSK entered at the prompt
+    async fn offer_to_save(&self, key: &Secret) -> Result<(), AuthError> {
+        let prompter = match &self.options.prompter {
+            Some(prompter) if self.options.save_credentials => prompter,
+            _ => return Ok(()),
+        };
+
+        let question = format!(
+            "Save the pre-shared key in the {} store?",
+            self.options.store.name()
+        );
+        if prompter.confirm(&question).await? {
+            self.save_key_to_store(key).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Find the PSK, returning whether it was entered at the prompt
+    async fn resolve_key(&self) -> Result<(Secret, bool), AuthError> {
+        // If we already have a key, use it
+        if let Some(key) = &self.key {
+            return Ok((key.clone(), false));
+        }
+
+        // Then the key, or where to find it, from the configuration
+        if let Some(psk) = &self.options.psk {
+            return Ok((psk.resolve().await?, false));
+        }
+
+        // Try to get the key from the credential store
+        match self.load_key_from_store().await? {
+            Some(key) => Ok((key, false)),
+            // Prompt the user for a key
+            None => Ok((self.prompt_for_key().await?, true)),
+        }
     }
 }
 
@@ -58,49 +125,28 @@ impl AuthProvider for PskAuthProvider {
     }
 
     async fn authenticate(&self, client: &Client) -> Result<bool> {
-        let credentials = self.get_credentials().await?;
-
-        // Extract PSK
-        let key = match credentials {
-            Credentials::Psk { key } => key,
-            _ => return Err(AuthError::InvalidCredentials.into()),
-        };
+        let (key, prompted) = self.resolve_key().await?;
 
-        // Send authentication message
-        let auth_message = crate::protocol::Message::new(
-            crate::protocol::MessageType::Auth,
-            json!({
-                "credentials": key,
-                "method": "psk",
-            }),
-        );
+        // Send authentication message and complete any follow-up steps
+        let payload = json!({
+            "credentials": key.expose(),
+            "method": "psk",
+        });
 
-        client.send(auth_message).await?;
+        let authenticated = exchange::run(client, payload, &self.username, &self.options).await?;
 
-        // Wait for response with a timeout
-        // TODO: Implement response handling in the Client
-        // For now, assume authentication was successful
+        // Only offer to save keys the server accepted
+        if authenticated && prompted {
+            if let Err(e) = self.offer_to_save(&key).await {
+                log::warn!("Failed to save pre-shared key: {}", e);
+            }
+        }
 
-        Ok(true)
+        Ok(authenticated)
     }
 
     async fn get_credentials(&self) -> Result<Credentials> {
-        // If we already have a key, use it
-        if let Some(key) = &self.key {
-            return Ok(Credentials::Psk { key: key.clone() });
-        }
-
-        // Try to get the key from the keyring
-        match self.load_key_from_keyring().await {
-            Ok(Some(key)) => {
-                return Ok(Credentials::Psk { key });
-            }
-            Ok(None) => {
-                // Prompt the user for a key
-                let key = self.prompt_for_key().await?;
-                Ok(Credentials::Psk { key })
-            }
-            Err(e) => Err(AuthError::KeyringError(e).into()),
-        }
+        let (key, _) = self.resolve_key().await?;
+        Ok(Credentials::Psk { key })
     }
 }
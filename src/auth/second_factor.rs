@@ -0,0 +1,187 @@
+use crate::auth::{store, totp, AuthError, AuthTarget, CredentialStore};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Kind of second factor requested by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondFactorKind {
+    /// Time-based one-time password from an authenticator app
+    Totp,
+
+    /// One-time code delivered out of band (e-mail, SMS, ...)
+    OneTimeCode,
+}
+
+impl fmt::Display for SecondFactorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecondFactorKind::Totp => write!(f, "totp"),
+            SecondFactorKind::OneTimeCode => write!(f, "otp"),
+        }
+    }
+}
+
+impl SecondFactorKind {
+    /// Parse a second factor kind from a string
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "totp" => Some(Self::Totp),
+            "otp" | "code" => Some(Self::OneTimeCode),
+            _ => None,
+        }
+    }
+}
+
+/// A second factor challenge sent by the server during authentication
+#[derive(Debug, Clone)]
+pub struct SecondFactorChallenge {
+    /// Kind of code the server expects
+    pub kind: SecondFactorKind,
+
+    /// Username the challenge is for
+    pub username: String,
+
+    /// Optional message from the server to show to the user
+    pub message: Option<String>,
+
+    /// Remaining attempts, if the server reports them
+    pub attempts_remaining: Option<u32>,
+}
+
+impl SecondFactorChallenge {
+    /// Parse a challenge from the `second_factor` object of an auth reply
+    pub fn from_payload(value: &Value, username: &str) -> Self {
+        let kind = value
+            .get("type")
+            .and_then(|v| v.as_str())
+            .and_then(SecondFactorKind::from_name)
+            .unwrap_or(SecondFactorKind::OneTimeCode);
+
+        Self {
+            kind,
+            username: username.to_string(),
+            message: value
+                .get("message")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            attempts_remaining: value
+                .get("attempts_remaining")
+                .and_then(|v| v.as_u64())
+                .map(|n| n as u32),
+        }
+    }
+
+    /// Text to show when asking the user for a code
+    pub fn prompt(&self) -> String {
+        let mut prompt = match &self.message {
+            Some(message) => message.clone(),
+            None => match self.kind {
+                SecondFactorKind::Totp => "Enter the code from your authenticator app".to_string(),
+                SecondFactorKind::OneTimeCode => "Enter the one-time code".to_string(),
+            },
+        };
+
+        if let Some(attempts) = self.attempts_remaining {
+            prompt.push_str(&format!(" ({} attempts left)", attempts));
+        }
+
+        prompt
+    }
+}
+
+/// Source of second factor codes
+#[async_trait]
+pub trait SecondFactorHandler: Send + Sync {
+    /// Obtain a code for the given challenge
+    async fn one_time_code(&self, challenge: &SecondFactorChallenge) -> Result<String, AuthError>;
+}
+
+/// Generate TOTP codes from a secret in the credential store
+///
+/// The secret is stored per user and server under a key such as
+/// `totp:alice@rcp.example.com:8717`; a secret saved by older versions under
+/// `totp:<username>` is moved there on first use. Challenges that are not
+/// TOTP, or users without a stored secret, are passed on to the fallback
+/// handler.
+pub struct StoredTotp {
+    store: Arc<dyn CredentialStore>,
+    target: Option<AuthTarget>,
+    fallback: Option<Arc<dyn SecondFactorHandler>>,
+}
+
+impl StoredTotp {
+    /// Create a new TOTP generator reading secrets from `store`
+    pub fn new(store: Arc<dyn CredentialStore>) -> Self {
+        Self {
+            store,
+            target: None,
+            fallback: None,
+        }
+    }
+
+    /// Set the server the secrets are stored for
+    pub fn with_target(mut self, target: AuthTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Set the handler used when no code can be generated
+    pub fn with_fallback(mut self, fallback: Arc<dyn SecondFactorHandler>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Credential store key for this user's secret on the target server
+    ///
+    /// Without a target the secret is keyed `totp:<username>`.
+    fn key(&self, username: &str) -> String {
+        match &self.target {
+            Some(target) => target.credential_key("totp", username),
+            None => Self::legacy_key(username),
+        }
+    }
+
+    fn legacy_key(username: &str) -> String {
+        format!("totp:{}", username)
+    }
+
+    /// Store a base32 TOTP secret for the given user
+    pub fn store_secret(&self, username: &str, secret: &str) -> Result<(), AuthError> {
+        // Reject secrets we would not be able to use later
+        totp::decode_secret(secret)?;
+        self.store.set(&self.key(username), secret)
+    }
+
+    /// Remove the stored TOTP secret for the given user
+    pub fn remove_secret(&self, username: &str) -> Result<(), AuthError> {
+        self.store.delete(&self.key(username))
+    }
+}
+
+#[async_trait]
+impl SecondFactorHandler for StoredTotp {
+    async fn one_time_code(&self, challenge: &SecondFactorChallenge) -> Result<String, AuthError> {
+        if challenge.kind == SecondFactorKind::Totp {
+            let username = &challenge.username;
+            match store::get_or_migrate(
+                self.store.as_ref(),
+                &self.key(username),
+                &Self::legacy_key(username),
+            ) {
+                Ok(Some(secret)) => {
+                    log::debug!("Generating TOTP code from stored secret");
+                    return totp::generate(secret.expose());
+                }
+                Ok(None) => log::debug!("No TOTP secret stored for {}", challenge.username),
+                Err(e) => log::warn!("Failed to load TOTP secret: {}", e),
+            }
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.one_time_code(challenge).await,
+            None => Err(AuthError::SecondFactorUnavailable),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::auth::store::shell;
+use crate::auth::{AuthError, Secret};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::str::FromStr;
+
+/// A secret-valued setting: the secret itself, or where to get it
+///
+/// In config files a reference is written as a string with a prefix:
+///
+/// | Setting | Secret |
+/// |---------|--------|
+/// | `keyring:rcp-client/prod-psk` | Password of account `prod-psk` of service `rcp-client` in the OS keyring |
+/// | `env:RCP_PSK` | Value of the environment variable |
+/// | `file:/run/secrets/psk` | Content of the file, without a trailing newline |
+/// | `cmd:vault read -field=psk secret/rcp` | Output of the command, without a trailing newline |
+/// | `literal:env:abc` | The rest of the string, for secrets that look like a reference |
+///
+/// Any other string is the secret itself. References are resolved each time
+/// the secret is used and are written back to the config file unchanged, so
+/// resolved secrets never end up on disk.
+#[derive(Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// The secret itself
+    Value(Secret),
+
+    /// Password stored in the OS keyring
+    Keyring { service: String, account: String },
+
+    /// Environment variable
+    Env(String),
+
+    /// File holding the secret
+    File(PathBuf),
+
+    /// Shell command printing the secret
+    Command(String),
+}
+
+impl SecretRef {
+    /// Whether the setting refers to a secret kept elsewhere
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, SecretRef::Value(_))
+    }
+
+    /// Get the secret
+    ///
+    /// Keyring lookups, files and commands are resolved on the blocking
+    /// thread pool. A command's stderr goes to the terminal, so that a
+    /// password manager can ask the user to unlock it.
+    pub async fn resolve(&self) -> Result<Secret, AuthError> {
+        if matches!(self, SecretRef::Value(_) | SecretRef::Env(_)) {
+            return self.resolve_blocking();
+        }
+        let reference = self.clone();
+        tokio::task::spawn_blocking(move || reference.resolve_blocking())
+            .await
+            .map_err(|e| AuthError::SecretReference(format!("{}: {}", self, e)))?
+    }
+
+    fn resolve_blocking(&self) -> Result<Secret, AuthError> {
+        match self {
+            SecretRef::Value(secret) => Ok(secret.clone()),
+            SecretRef::Env(var) => std::env::var(var)
+                .map(Secret::new)
+                .map_err(|_| AuthError::SecretReference(format!("{} is not set", var))),
+            SecretRef::Keyring { service, account } => keyring::Entry::new(service, account)
+                .and_then(|entry| entry.get_password())
+                .map(Secret::new)
+                .map_err(|e| AuthError::SecretReference(format!("{}: {}", self, e))),
+            SecretRef::File(path) => std::fs::read_to_string(path)
+                .map(|content| Secret::new(trim_newline(&content)))
+                .map_err(|e| AuthError::SecretReference(format!("{}: {}", path.display(), e))),
+            SecretRef::Command(command) => {
+                let output = shell(command)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .map_err(|e| AuthError::SecretReference(format!("{}: {}", self, e)))?;
+                let stdout = zeroize::Zeroizing::new(output.stdout);
+                if !output.status.success() {
+                    return Err(AuthError::SecretReference(format!(
+                        "{} exited with {}",
+                        self, output.status
+                    )));
+                }
+                Ok(Secret::new(trim_newline(&String::from_utf8_lossy(&stdout))))
+            }
+        }
+    }
+}
+
+/// Whether a string would be read as a reference, or needs `literal:` to be read as itself
+fn has_reference_prefix(value: &str) -> bool {
+    value
+        .split_once(':')
+        .is_some_and(|(prefix, _)| matches!(prefix, "keyring" | "env" | "file" | "cmd" | "literal"))
+}
+
+fn trim_newline(value: &str) -> &str {
+    value.trim_end_matches(['\n', '\r'])
+}
+
+impl FromStr for SecretRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, rest) = match s.split_once(':') {
+            Some(parts) => parts,
+            None => return Ok(SecretRef::Value(Secret::new(s))),
+        };
+
+        match prefix {
+            "keyring" => match rest.split_once('/') {
+                Some((service, account)) if !service.is_empty() && !account.is_empty() => {
+                    Ok(SecretRef::Keyring {
+                        service: service.to_string(),
+                        account: account.to_string(),
+                    })
+                }
+                _ => Err(format!(
+                    "keyring reference {:?} must be keyring:<service>/<account>",
+                    s
+                )),
+            },
+            "env" if !rest.is_empty() => Ok(SecretRef::Env(rest.to_string())),
+            "file" if !rest.is_empty() => Ok(SecretRef::File(PathBuf::from(rest))),
+            "cmd" if !rest.trim().is_empty() => Ok(SecretRef::Command(rest.to_string())),
+            "env" | "file" | "cmd" => Err(format!("secret reference {:?} is empty", s)),
+            "literal" => Ok(SecretRef::Value(Secret::new(rest))),
+            _ => Ok(SecretRef::Value(Secret::new(s))),
+        }
+    }
+}
+
+/// Shows references as written in the config file, and secret values redacted
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Value(secret) => write!(f, "{}", secret),
+            SecretRef::Keyring { service, account } => write!(f, "keyring:{}/{}", service, account),
+            SecretRef::Env(var) => write!(f, "env:{}", var),
+            SecretRef::File(path) => write!(f, "file:{}", path.display()),
+            SecretRef::Command(command) => write!(f, "cmd:{}", command),
+        }
+    }
+}
+
+impl fmt::Debug for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretRef({})", self)
+    }
+}
+
+impl From<Secret> for SecretRef {
+    fn from(secret: Secret) -> Self {
+        SecretRef::Value(secret)
+    }
+}
+
+impl Serialize for SecretRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SecretRef::Value(secret) if has_reference_prefix(secret.expose()) => {
+                let literal = zeroize::Zeroizing::new(format!("literal:{}", secret.expose()));
+                serializer.serialize_str(&literal)
+            }
+            SecretRef::Value(secret) => secret.serialize(serializer),
+            reference => serializer.serialize_str(&reference.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Secret::deserialize(deserializer)?;
+        value.expose().parse().map_err(serde::de::Error::custom)
+    }
+}
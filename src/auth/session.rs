@@ -0,0 +1,262 @@
+//! Server-issued session tokens
+//!
+//! After a successful authentication the server may return a session token in
+//! the reply payload:
+//!
+//! `{"success": true, "session": {"token": "...", "expires_at": 1700000000}}`
+//!
+//! The token is kept in memory (and optionally in the credential store) and
+//! presented on reconnect with
+//! `{"method": "session", "username": "...", "token": "..."}` so that the
+//! provider flow does not have to run again. Tokens are refreshed with the same
+//! message plus `"step": "refresh"` shortly before they expire.
+
+use crate::auth::{exchange, AuthOptions, AuthProvider, AuthTarget, CredentialStore, Secret};
+use crate::protocol::Client;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Refresh tokens this many seconds before they expire
+pub const REFRESH_MARGIN_SECS: u64 = 120;
+
+/// A session token issued by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionToken {
+    /// Opaque token value
+    pub token: Secret,
+
+    /// Expiry as UNIX time in seconds, if the server reported one
+    pub expires_at: Option<u64>,
+}
+
+impl SessionToken {
+    /// Parse a token from the `session` object of an auth reply
+    pub fn from_payload(value: &Value) -> Option<Self> {
+        let token = value.get("token").and_then(|v| v.as_str())?;
+        if token.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            token: Secret::new(token),
+            expires_at: value.get("expires_at").and_then(|v| v.as_u64()),
+        })
+    }
+
+    /// Whether the token has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| now() >= at)
+    }
+
+    /// Whether the token should be refreshed now
+    pub fn needs_refresh(&self) -> bool {
+        self.expires_at
+            .is_some_and(|at| now() + REFRESH_MARGIN_SECS >= at)
+    }
+}
+
+/// Store for session tokens, keyed by user and server
+///
+/// Tokens always live in memory for the lifetime of the store. When
+/// persistence is enabled they are also written to a credential store so that
+/// a new client process can resume the session.
+#[derive(Default)]
+pub struct SessionStore {
+    persist: Option<Arc<dyn CredentialStore>>,
+    tokens: Mutex<HashMap<String, SessionToken>>,
+}
+
+impl SessionStore {
+    /// Create a new in-memory session store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a session store as configured in `auth.persist_session`
+    pub fn from_config(config: &crate::config::AuthConfig) -> Self {
+        let sessions = Self::new();
+        if config.persist_session {
+            sessions.persist_to(crate::auth::store::from_config(config))
+        } else {
+            sessions
+        }
+    }
+
+    /// Also keep tokens in the given credential store
+    pub fn persist_to(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.persist = Some(store);
+        self
+    }
+
+    fn key(username: &str, target: &AuthTarget) -> String {
+        target.credential_key("session", username)
+    }
+
+    /// Get the stored token, if any and not expired
+    pub fn get(&self, username: &str, target: &AuthTarget) -> Option<SessionToken> {
+        let key = Self::key(username, target);
+
+        let mut token = self.tokens.lock().unwrap().get(&key).cloned();
+        if let (None, Some(store)) = (&token, &self.persist) {
+            token = load_persisted(store.as_ref(), &key);
+            if let Some(token) = &token {
+                self.tokens
+                    .lock()
+                    .unwrap()
+                    .insert(key.clone(), token.clone());
+            }
+        }
+
+        match token {
+            Some(token) if token.is_expired() => {
+                log::debug!("Stored session token has expired");
+                self.remove(username, target);
+                None
+            }
+            other => other,
+        }
+    }
+
+    /// Store a token
+    pub fn insert(&self, username: &str, target: &AuthTarget, token: SessionToken) {
+        let key = Self::key(username, target);
+
+        if let Some(store) = &self.persist {
+            let value = Zeroizing::new(serde_json::to_string(&token).unwrap_or_default());
+            if let Err(e) = store.set(&key, &value) {
+                log::warn!("Failed to save session token: {}", e);
+            }
+        }
+
+        self.tokens.lock().unwrap().insert(key, token);
+    }
+
+    /// Forget the token for the given user and server
+    pub fn remove(&self, username: &str, target: &AuthTarget) {
+        let key = Self::key(username, target);
+        self.tokens.lock().unwrap().remove(&key);
+
+        if let Some(store) = &self.persist {
+            if let Err(e) = store.delete(&key) {
+                log::warn!("Failed to remove session token: {}", e);
+            }
+        }
+    }
+}
+
+fn load_persisted(store: &dyn CredentialStore, key: &str) -> Option<SessionToken> {
+    match store.get(key) {
+        Ok(value) => value.and_then(|value| serde_json::from_str(value.expose()).ok()),
+        Err(e) => {
+            log::warn!("Failed to load session token: {}", e);
+            None
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Try to resume a stored session
+///
+/// Returns `Ok(false)` if the options carry no session store or target, there
+/// is no stored token, or the server rejects it. A rejected token is discarded.
+pub async fn try_resume(client: &Client, username: &str, options: &AuthOptions) -> Result<bool> {
+    let (store, target) = match (&options.sessions, &options.target) {
+        (Some(store), Some(target)) => (store, target),
+        _ => return Ok(false),
+    };
+
+    let token = match store.get(username, target) {
+        Some(token) => token,
+        None => return Ok(false),
+    };
+
+    log::info!("Resuming session for {}", username);
+    match resume(client, username, &token, options).await {
+        Ok(true) => {
+            refresh_if_needed(client, username, options).await?;
+            return Ok(true);
+        }
+        Ok(false) => log::info!("Session token rejected, running full authentication"),
+        Err(e) => log::warn!("Session resumption failed: {}", e),
+    }
+
+    store.remove(username, target);
+    Ok(false)
+}
+
+/// Resume a stored session, falling back to the provider's full flow
+///
+/// If there is no usable session token, this is the same as
+/// `provider.authenticate(client)`.
+pub async fn resume_or_authenticate(
+    client: &Client,
+    provider: &dyn AuthProvider,
+    username: &str,
+    options: &AuthOptions,
+) -> Result<bool> {
+    if try_resume(client, username, options).await? {
+        return Ok(true);
+    }
+
+    provider.authenticate(client).await
+}
+
+/// Refresh the stored session token if it is about to expire
+///
+/// Returns `Ok(false)` if there was nothing to refresh or the server refused.
+pub async fn refresh_if_needed(
+    client: &Client,
+    username: &str,
+    options: &AuthOptions,
+) -> Result<bool> {
+    let (store, target) = match (&options.sessions, &options.target) {
+        (Some(store), Some(target)) => (store, target),
+        _ => return Ok(false),
+    };
+
+    let token = match store.get(username, target) {
+        Some(token) if token.needs_refresh() => token,
+        _ => return Ok(false),
+    };
+
+    log::debug!("Refreshing session token for {}", username);
+    let payload = json!({
+        "username": username,
+        "method": "session",
+        "step": "refresh",
+        "token": token.token.expose(),
+    });
+
+    let refreshed = exchange::run(client, payload, username, options).await?;
+    if !refreshed {
+        store.remove(username, target);
+    }
+
+    Ok(refreshed)
+}
+
+async fn resume(
+    client: &Client,
+    username: &str,
+    token: &SessionToken,
+    options: &AuthOptions,
+) -> Result<bool> {
+    let payload = json!({
+        "username": username,
+        "method": "session",
+        "token": token.token.expose(),
+    });
+
+    exchange::run(client, payload, username, options).await
+}
@@ -0,0 +1,66 @@
+use crate::auth::store::CredentialStore;
+use crate::auth::{AuthError, Secret};
+
+/// Read-only credential store backed by environment variables
+///
+/// The key is upper-cased, characters other than ASCII letters and digits are
+/// replaced by `_`, and the prefix is prepended, so with the default prefix the
+/// PSK is read from `RCP_SECRET_PSK`.
+pub struct EnvStore {
+    prefix: String,
+}
+
+impl EnvStore {
+    /// Create a new environment store with the given variable prefix
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Name of the environment variable holding the secret for `key`
+    pub fn variable_name(&self, key: &str) -> String {
+        let suffix: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}{}", self.prefix, suffix)
+    }
+}
+
+impl CredentialStore for EnvStore {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError> {
+        match std::env::var(self.variable_name(key)) {
+            Ok(secret) if !secret.is_empty() => Ok(Some(Secret::new(secret))),
+            _ => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, _secret: &str) -> Result<(), AuthError> {
+        Err(AuthError::CredentialStore(format!(
+            "environment store is read-only, set {} instead",
+            self.variable_name(key)
+        )))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AuthError> {
+        Err(AuthError::CredentialStore(format!(
+            "environment store is read-only, unset {} instead",
+            self.variable_name(key)
+        )))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,131 @@
+use crate::auth::store::CredentialStore;
+use crate::auth::{AuthError, Secret};
+use crate::config::paths;
+use age::secrecy::SecretString;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// Credential store kept in an age passphrase-encrypted file
+///
+/// Secrets are stored as a JSON object encrypted with the passphrase read from
+/// the configured environment variable. This works on headless machines that
+/// have no keyring daemon.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    passphrase_env: String,
+    // Serializes read-modify-write cycles within this process
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    /// Create a new encrypted file store
+    pub fn new<P: AsRef<Path>>(path: P, passphrase_env: &str) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            passphrase_env: passphrase_env.to_string(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Default location of the encrypted credentials file
+    pub fn default_path() -> PathBuf {
+        paths::moved_file(&paths::data_dir(), "credentials.age")
+    }
+
+    fn passphrase(&self) -> Result<SecretString, AuthError> {
+        match std::env::var(&self.passphrase_env) {
+            Ok(passphrase) if !passphrase.is_empty() => Ok(SecretString::from(passphrase)),
+            _ => Err(AuthError::CredentialStore(format!(
+                "set {} to unlock {:?}",
+                self.passphrase_env, self.path
+            ))),
+        }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Secret>, AuthError> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let ciphertext = fs::read(&self.path).map_err(|e| {
+            AuthError::CredentialStore(format!("failed to read {:?}: {}", self.path, e))
+        })?;
+
+        let identity = age::scrypt::Identity::new(self.passphrase()?);
+        let plaintext = age::decrypt(&identity, &ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|e| {
+                AuthError::CredentialStore(format!("failed to decrypt {:?}: {}", self.path, e))
+            })?;
+
+        serde_json::from_slice(&plaintext).map_err(|e| {
+            AuthError::CredentialStore(format!("corrupt credentials file {:?}: {}", self.path, e))
+        })
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, Secret>) -> Result<(), AuthError> {
+        let plaintext = serde_json::to_vec(secrets)
+            .map(Zeroizing::new)
+            .map_err(|e| AuthError::CredentialStore(e.to_string()))?;
+
+        let recipient = age::scrypt::Recipient::new(self.passphrase()?);
+        let ciphertext = age::encrypt(&recipient, &plaintext)
+            .map_err(|e| AuthError::CredentialStore(format!("failed to encrypt: {}", e)))?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                AuthError::CredentialStore(format!("failed to create {:?}: {}", parent, e))
+            })?;
+        }
+
+        // Write next to the target and rename so a crash cannot truncate the file
+        let tmp_path = self.path.with_extension("age.tmp");
+        fs::write(&tmp_path, ciphertext)
+            .and_then(|_| restrict_permissions(&tmp_path))
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                AuthError::CredentialStore(format!("failed to write {:?}: {}", self.path, e))
+            })
+    }
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.load()?.remove(key))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AuthError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+        secrets.insert(key.to_string(), Secret::new(secret));
+        self.save(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AuthError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+        if secrets.remove(key).is_some() {
+            self.save(&secrets)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use crate::auth::store::CredentialStore;
+use crate::auth::{AuthError, Secret};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tokio::runtime::RuntimeFlavor;
+use zeroize::Zeroizing;
+
+/// Credential store backed by an external helper command
+///
+/// Works like git credential helpers: the command is run through the shell
+/// with the operation (`get`, `store` or `erase`) appended as an argument, and
+/// receives a request of `name=value` lines on stdin, ended by a blank line:
+///
+/// ```text
+/// method=password
+/// username=alice
+/// server=rcp.example.com
+/// port=8717
+/// key=password:alice@rcp.example.com:8717
+/// ```
+///
+/// `store` requests also carry a `secret=` line. For `get`, the helper prints
+/// `secret=<value>` on stdout, or nothing if it has no secret. A non-zero exit
+/// status is reported as an error. The helper's stderr is the client's, so
+/// that prompts to unlock a vault or key reach the user.
+pub struct HelperStore {
+    command: String,
+}
+
+impl HelperStore {
+    /// Create a new helper store running the given command
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+
+    /// Build the request describing `key`
+    ///
+    /// Keys of the form `kind:username@server:port` are split into their
+    /// fields; other keys are only passed as `key=`.
+    pub fn request(key: &str) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+
+        if let Some((kind, rest)) = key.split_once(':') {
+            fields.push(("method".to_string(), kind.to_string()));
+
+            match rest.rsplit_once('@') {
+                Some((username, server)) => {
+                    fields.push(("username".to_string(), username.to_string()));
+                    match server.rsplit_once(':') {
+                        Some((address, port)) if port.parse::<u16>().is_ok() => {
+                            fields.push(("server".to_string(), address.to_string()));
+                            fields.push(("port".to_string(), port.to_string()));
+                        }
+                        _ => fields.push(("server".to_string(), server.to_string())),
+                    }
+                }
+                None => fields.push(("username".to_string(), rest.to_string())),
+            }
+        }
+
+        fields.push(("key".to_string(), key.to_string()));
+        fields
+    }
+
+    /// Run the helper for one operation and return its stdout
+    fn run(
+        &self,
+        operation: &str,
+        key: &str,
+        secret: Option<&str>,
+    ) -> Result<Zeroizing<String>, AuthError> {
+        // A line break would end a field early and start another one
+        let fields = Self::request(key);
+        let has_line_break = |value: &str| value.contains(['\n', '\r']);
+        if fields
+            .iter()
+            .any(|(name, value)| has_line_break(name) || has_line_break(value))
+            || secret.is_some_and(has_line_break)
+        {
+            return Err(AuthError::CredentialStore(format!(
+                "cannot pass {:?} to credential helper: line breaks are not allowed",
+                key
+            )));
+        }
+
+        let mut input = Zeroizing::new(String::new());
+        for (name, value) in fields {
+            input.push_str(&format!("{}={}\n", name, value));
+        }
+        if let Some(secret) = secret {
+            input.push_str(&format!("secret={}\n", secret));
+        }
+        input.push('\n');
+
+        let command_line = format!("{} {}", self.command, operation);
+        without_blocking_runtime(|| self.spawn(&command_line, operation, &input))
+    }
+
+    /// Run the helper with `input` on stdin and return its stdout
+    fn spawn(
+        &self,
+        command_line: &str,
+        operation: &str,
+        input: &str,
+    ) -> Result<Zeroizing<String>, AuthError> {
+        let mut child = shell(command_line)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| {
+                AuthError::CredentialStore(format!(
+                    "failed to run credential helper {}: {}",
+                    self.command, e
+                ))
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // The helper may exit without reading its input
+            let _ = stdin.write_all(input.as_bytes());
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| AuthError::CredentialStore(format!("credential helper failed: {}", e)))?;
+
+        // Its stdout could hold a secret, so only the status is reported
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            return Err(AuthError::CredentialStore(format!(
+                "credential helper {} {} exited with {}",
+                self.command, operation, output.status
+            )));
+        }
+
+        Ok(Zeroizing::new(
+            String::from_utf8_lossy(&stdout).into_owned(),
+        ))
+    }
+}
+
+/// Run blocking work, such as a helper waiting for the user, from code that
+/// may be running on the async runtime
+///
+/// The [`CredentialStore`] methods are synchronous, so on a multi-threaded
+/// runtime the worker hands its other tasks to another thread while `f`
+/// runs, instead of stalling them.
+pub(crate) fn without_blocking_runtime<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(windows)]
+pub(crate) fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+impl CredentialStore for HelperStore {
+    fn name(&self) -> &'static str {
+        "helper"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError> {
+        let output = self.run("get", key, None)?;
+        Ok(output
+            .lines()
+            .find_map(|line| line.strip_prefix("secret="))
+            .filter(|secret| !secret.is_empty())
+            .map(Secret::new))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AuthError> {
+        self.run("store", key, Some(secret)).map(|_| ())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AuthError> {
+        self.run("erase", key, None).map(|_| ())
+    }
+}
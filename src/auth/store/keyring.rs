@@ -0,0 +1,45 @@
+use crate::auth::store::CredentialStore;
+use crate::auth::{AuthError, Secret};
+
+/// Credential store backed by the OS keyring
+pub struct KeyringStore {
+    service: String,
+}
+
+impl KeyringStore {
+    /// Create a new keyring store for the given service name
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+        }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, AuthError> {
+        Ok(keyring::Entry::new(&self.service, key)?)
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError> {
+        match self.entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(Secret::new(secret))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AuthError> {
+        Ok(self.entry(key)?.set_password(secret)?)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AuthError> {
+        match self.entry(key)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::auth::store::CredentialStore;
+use crate::auth::{AuthError, Secret};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Credential store that only keeps secrets in memory
+#[derive(Default)]
+pub struct MemoryStore {
+    secrets: Mutex<HashMap<String, Secret>>,
+}
+
+impl MemoryStore {
+    /// Create a new, empty memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a secret, for setting up tests
+    pub fn with_secret(self, key: &str, secret: &str) -> Self {
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Secret::new(secret));
+        self
+    }
+}
+
+impl CredentialStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError> {
+        Ok(self.secrets.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AuthError> {
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Secret::new(secret));
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AuthError> {
+        self.secrets.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
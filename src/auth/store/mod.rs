@@ -0,0 +1,155 @@
+//! Credential storage backends
+//!
+//! Providers look up secrets through the [`CredentialStore`] trait by a key
+//! naming the kind of secret, the user and the server, for example
+//! `password:alice@rcp.example.com:8717` (see
+//! [`AuthTarget::credential_key`](crate::auth::AuthTarget::credential_key)).
+//! The backend is selected with `[auth.store]` in the configuration.
+
+use crate::auth::{AuthError, Secret};
+use crate::config::AuthConfig;
+use std::sync::Arc;
+
+mod env;
+mod file;
+mod helper;
+mod keyring;
+mod memory;
+
+pub use self::keyring::KeyringStore;
+pub use env::EnvStore;
+pub use file::EncryptedFileStore;
+pub(crate) use helper::shell;
+pub use helper::HelperStore;
+pub use memory::MemoryStore;
+
+/// Service name used for stored secrets
+pub const SERVICE_NAME: &str = "rcp-client";
+
+/// Storage for secrets such as passwords, pre-shared keys and tokens
+pub trait CredentialStore: Send + Sync {
+    /// Short name of the backend, used in logs
+    fn name(&self) -> &'static str;
+
+    /// Look up the secret stored under `key`
+    fn get(&self, key: &str) -> Result<Option<Secret>, AuthError>;
+
+    /// Store `secret` under `key`, replacing any existing value
+    fn set(&self, key: &str, secret: &str) -> Result<(), AuthError>;
+
+    /// Remove the secret stored under `key`; removing a missing key is not an error
+    fn delete(&self, key: &str) -> Result<(), AuthError>;
+
+    /// Whether `set` and `delete` always fail
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Look up `key`, falling back to a secret stored under `legacy_key`
+///
+/// Older versions stored one password per user (keyed by username) and a
+/// single pre-shared key (keyed `psk`). Such an entry is moved to `key` the
+/// first time it is looked up, so it belongs to the first server it is used
+/// with and is not handed to every other server. Read-only stores, such as
+/// the environment store, cannot be migrated and keep returning the legacy
+/// entry. Failing to move it does not fail the lookup.
+pub fn get_or_migrate(
+    store: &dyn CredentialStore,
+    key: &str,
+    legacy_key: &str,
+) -> Result<Option<Secret>, AuthError> {
+    if let Some(secret) = store.get(key)? {
+        return Ok(Some(secret));
+    }
+
+    let secret = match store.get(legacy_key)? {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    if store.is_read_only() {
+        return Ok(Some(secret));
+    }
+
+    log::info!("Moving stored secret {} to {}", legacy_key, key);
+    match store.set(key, secret.expose()) {
+        Ok(()) => {
+            if let Err(e) = store.delete(legacy_key) {
+                log::warn!("Failed to remove stored secret {}: {}", legacy_key, e);
+            }
+        }
+        Err(e) => log::warn!(
+            "Failed to move stored secret {} to {}: {}",
+            legacy_key,
+            key,
+            e
+        ),
+    }
+
+    Ok(Some(secret))
+}
+
+/// Credential store backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreBackend {
+    /// OS keyring (Secret Service, Keychain, Credential Manager)
+    Keyring,
+
+    /// Passphrase-encrypted file under the data directory
+    File,
+
+    /// Read-only environment variables, for CI
+    Env,
+
+    /// In-memory store, for tests
+    Memory,
+}
+
+impl StoreBackend {
+    /// Parse a backend name from a string
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "keyring" => Some(Self::Keyring),
+            "file" => Some(Self::File),
+            "env" => Some(Self::Env),
+            "memory" => Some(Self::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// Create the credential store selected in the configuration
+///
+/// A configured credential helper takes precedence over `[auth.store]`.
+pub fn from_config(auth: &AuthConfig) -> Arc<dyn CredentialStore> {
+    if let Some(helper) = auth.credential_helper.as_deref().filter(|h| !h.is_empty()) {
+        return Arc::new(HelperStore::new(helper));
+    }
+
+    let config = &auth.store;
+    let backend = match StoreBackend::from_name(&config.backend) {
+        Some(backend) => backend,
+        None => {
+            log::warn!(
+                "Unknown credential store: {}, falling back to keyring",
+                config.backend
+            );
+            StoreBackend::Keyring
+        }
+    };
+
+    match backend {
+        StoreBackend::Keyring => Arc::new(KeyringStore::new(SERVICE_NAME)),
+        StoreBackend::File => {
+            let path = config
+                .path
+                .as_ref()
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(EncryptedFileStore::default_path);
+            Arc::new(EncryptedFileStore::new(path, &config.passphrase_env))
+        }
+        StoreBackend::Env => Arc::new(EnvStore::new(&config.env_prefix)),
+        StoreBackend::Memory => Arc::new(MemoryStore::new()),
+    }
+}
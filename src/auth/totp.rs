@@ -0,0 +1,60 @@
+use crate::auth::AuthError;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default TOTP time step in seconds (RFC 6238)
+pub const DEFAULT_STEP_SECS: u64 = 30;
+
+/// Default number of digits in a generated code
+pub const DEFAULT_DIGITS: u32 = 6;
+
+/// Decode a base32 TOTP secret as shown by authenticator apps
+///
+/// Whitespace and padding are ignored and the secret is case-insensitive.
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>, AuthError> {
+    let cleaned: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, &cleaned)
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| AuthError::Other("Invalid TOTP secret".to_string()))
+}
+
+/// Generate an HOTP code for the given counter (RFC 4226)
+pub fn hotp(key: &[u8], counter: u64, digits: u32) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    let code = binary % 10u32.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// Generate a TOTP code for the given UNIX time (RFC 6238)
+pub fn totp_at(key: &[u8], unix_time: u64, step_secs: u64, digits: u32) -> String {
+    hotp(key, unix_time / step_secs, digits)
+}
+
+/// Generate a TOTP code for the current time using the default parameters
+pub fn generate(secret: &str) -> Result<String, AuthError> {
+    let key = decode_secret(secret)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    Ok(totp_at(&key, now, DEFAULT_STEP_SECS, DEFAULT_DIGITS))
+}
@@ -0,0 +1,445 @@
+//! Headless commands for scripts
+//!
+//! `exec` connects, authenticates, sends one command, prints the response and
+//! exits, without needing a display server. The exit status tells the
+//! classes of failure apart:
+//!
+//! | Status | Class | Meaning |
+//! |--------|-------|---------|
+//! | 0 | | The command succeeded |
+//! | 1 | `failure` | Any other error, such as an invalid configuration |
+//! | 2 | `usage` | Invalid arguments, such as params that are not JSON |
+//! | 3 | `connect` | The server could not be reached |
+//! | 4 | `auth` | Authentication failed or was rejected |
+//! | 5 | `timeout` | The server did not answer in time |
+//! | 6 | `server` | The server reported an error |
+//!
+//! With `--output json`, `jsonl` or `yaml`, responses, errors and events are
+//! written to stdout as [`OutputRecord`]s, tagged by `type`:
+//!
+//! ```json
+//! {"type": "response", "command": "status", "data": {"uptime": 42}}
+//! {"type": "error", "class": "server", "kind": "server_error", "code": 404, "message": "...", "exit_code": 6}
+//! {"type": "event", "message_type": "event", "timestamp": 1700000000, "payload": {}}
+//! ```
+//!
+//! `kind` is the [`ProtocolError`] variant, if the error came from the
+//! protocol, and `code` the error code sent by the server, if any. A
+//! configuration that cannot be loaded or is invalid is reported the same way,
+//! as a `failure`. The default `raw` output prints the response data alone and
+//! errors as text on stderr; `table` lays out lists of objects as columns.
+
+use crate::audit::{self, AuditEvent};
+use crate::auth::{self, AuthError};
+use crate::config::ClientConfig;
+use crate::protocol::{self, Message, MessageType, ProtocolError};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Read;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Exit status of errors without a class of their own
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit status of invalid arguments
+pub const EXIT_USAGE: i32 = 2;
+
+/// Exit status when the server could not be reached
+pub const EXIT_CONNECT: i32 = 3;
+
+/// Exit status when authentication failed
+pub const EXIT_AUTH: i32 = 4;
+
+/// Exit status when the server did not answer in time
+pub const EXIT_TIMEOUT: i32 = 5;
+
+/// Exit status when the server reported an error
+pub const EXIT_SERVER: i32 = 6;
+
+/// Seconds to wait for the response to a command unless `--timeout` is given
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// A failed headless command, by the step that failed
+#[derive(Debug, Error)]
+pub enum ExecError {
+    /// The params were not valid JSON
+    #[error("Invalid params: {0}")]
+    InvalidParams(String),
+
+    /// The server could not be reached
+    #[error("Failed to connect: {0:#}")]
+    Connect(anyhow::Error),
+
+    /// Authentication failed or was rejected
+    #[error("Authentication failed: {0:#}")]
+    Auth(anyhow::Error),
+
+    /// Sending the command or waiting for its response failed
+    #[error("{0:#}")]
+    Command(anyhow::Error),
+
+    /// The configuration could not be loaded or is invalid
+    #[error("{0:#}")]
+    Config(anyhow::Error),
+}
+
+impl ExecError {
+    /// Class of the failure
+    ///
+    /// A server that stops answering is a timeout at any step.
+    pub fn class(&self) -> FailureClass {
+        if matches!(self.protocol_error(), Some(ProtocolError::Timeout)) {
+            return FailureClass::Timeout;
+        }
+        match self {
+            ExecError::InvalidParams(_) => FailureClass::Usage,
+            ExecError::Connect(_) => FailureClass::Connect,
+            ExecError::Auth(e) if matches!(e.downcast_ref(), Some(AuthError::Timeout)) => {
+                FailureClass::Timeout
+            }
+            ExecError::Auth(_) => FailureClass::Auth,
+            ExecError::Command(_) => match self.protocol_error() {
+                Some(ProtocolError::ServerError { .. }) => FailureClass::Server,
+                _ => FailureClass::Failure,
+            },
+            ExecError::Config(_) => FailureClass::Failure,
+        }
+    }
+
+    /// Exit status for the failure
+    pub fn exit_code(&self) -> i32 {
+        self.class().exit_code()
+    }
+
+    /// The protocol error behind the failure, if there is one
+    pub fn protocol_error(&self) -> Option<&ProtocolError> {
+        match self {
+            ExecError::InvalidParams(_) | ExecError::Config(_) => None,
+            ExecError::Connect(e) | ExecError::Auth(e) | ExecError::Command(e) => e.downcast_ref(),
+        }
+    }
+}
+
+/// Class of a failed command, each with its own exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// Invalid arguments
+    Usage,
+
+    /// The server could not be reached
+    Connect,
+
+    /// Authentication failed or was rejected
+    Auth,
+
+    /// The server did not answer in time
+    Timeout,
+
+    /// The server reported an error
+    Server,
+
+    /// Any other error
+    Failure,
+}
+
+impl FailureClass {
+    /// Exit status for the class
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureClass::Usage => EXIT_USAGE,
+            FailureClass::Connect => EXIT_CONNECT,
+            FailureClass::Auth => EXIT_AUTH,
+            FailureClass::Timeout => EXIT_TIMEOUT,
+            FailureClass::Server => EXIT_SERVER,
+            FailureClass::Failure => EXIT_FAILURE,
+        }
+    }
+}
+
+/// How CLI commands print their results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// The response data alone, and errors as text on stderr
+    #[default]
+    Raw,
+
+    /// One pretty-printed JSON record per result
+    Json,
+
+    /// One JSON record per line
+    Jsonl,
+
+    /// One YAML document per result
+    Yaml,
+
+    /// Lists of objects as columns, and objects as key-value rows
+    Table,
+}
+
+/// A response, error or event, as written with structured output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputRecord {
+    /// Data of a successful command
+    Response { command: String, data: Value },
+
+    /// A failed command
+    Error {
+        class: FailureClass,
+        kind: Option<&'static str>,
+        code: Option<u32>,
+        message: String,
+        exit_code: i32,
+    },
+
+    /// A message from the server that is not the reply to a command
+    Event {
+        message_type: MessageType,
+        timestamp: u64,
+        payload: Value,
+    },
+}
+
+impl OutputRecord {
+    /// Record of a failed command
+    pub fn error(error: &ExecError) -> Self {
+        let protocol_error = error.protocol_error();
+        OutputRecord::Error {
+            class: error.class(),
+            kind: protocol_error.map(ProtocolError::kind),
+            code: match protocol_error {
+                Some(ProtocolError::ServerError { code, .. }) => *code,
+                _ => None,
+            },
+            message: error.to_string(),
+            exit_code: error.exit_code(),
+        }
+    }
+
+    /// Record of a message that is not the reply to a command
+    pub fn event(message: &Message) -> Self {
+        OutputRecord::Event {
+            message_type: message.message_type,
+            timestamp: message.timestamp,
+            payload: message.payload.clone(),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Text of a record, without a trailing newline
+    pub fn render(self, record: &OutputRecord) -> String {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(record).unwrap_or_default(),
+            OutputFormat::Jsonl => serde_json::to_string(record).unwrap_or_default(),
+            OutputFormat::Yaml => {
+                let yaml = serde_yaml::to_string(record).unwrap_or_default();
+                format!("---\n{}", yaml.trim_end())
+            }
+            OutputFormat::Raw | OutputFormat::Table => match record {
+                OutputRecord::Response { data, .. } if self == OutputFormat::Table => table(data),
+                OutputRecord::Response { data, .. } => match data {
+                    Value::String(text) => text.clone(),
+                    data => serde_json::to_string_pretty(data).unwrap_or_default(),
+                },
+                OutputRecord::Error { message, .. } => format!("Error: {}", message),
+                OutputRecord::Event {
+                    message_type,
+                    payload,
+                    ..
+                } => format!("* {} {}", message_type, payload),
+            },
+        }
+    }
+
+    /// Whether the format writes records for programs, rather than text for people
+    pub fn is_structured(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::Yaml
+        )
+    }
+
+    /// Whether a record goes to stderr rather than stdout
+    ///
+    /// Structured formats write every record to stdout, so that one stream
+    /// holds every record, and messages for people to stderr.
+    pub fn to_stderr(self, record: &OutputRecord) -> bool {
+        !self.is_structured() && matches!(record, OutputRecord::Error { .. })
+    }
+
+    /// Print a record to stdout or stderr
+    pub fn print(self, record: &OutputRecord) {
+        let text = self.render(record);
+        if self.to_stderr(record) {
+            eprintln!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+    }
+}
+
+/// Lay out a list of objects as columns, an object as key-value rows, and
+/// anything else as a single value
+fn table(data: &Value) -> String {
+    let (headers, rows): (Vec<String>, Vec<Vec<String>>) = match data {
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            let mut headers: Vec<String> = Vec::new();
+            for item in items.iter().filter_map(Value::as_object) {
+                for key in item.keys() {
+                    if !headers.contains(key) {
+                        headers.push(key.clone());
+                    }
+                }
+            }
+            let rows = items
+                .iter()
+                .map(|item| {
+                    headers
+                        .iter()
+                        .map(|key| item.get(key).map(cell).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            (headers, rows)
+        }
+        Value::Object(fields) => (
+            vec!["KEY".to_string(), "VALUE".to_string()],
+            fields
+                .iter()
+                .map(|(key, value)| vec![key.clone(), cell(value)])
+                .collect(),
+        ),
+        Value::Array(items) => (
+            vec!["VALUE".to_string()],
+            items.iter().map(|item| vec![cell(item)]).collect(),
+        ),
+        other => return cell(other),
+    };
+
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![line(&headers)];
+    lines.push(line(
+        &widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>(),
+    ));
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// Text of a table cell: strings without quotes, anything else as JSON
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Parse command params given as JSON, or read them from stdin for `-`
+///
+/// No params is an empty object.
+pub fn parse_params(params: Option<&str>) -> Result<Value, ExecError> {
+    let text = match params {
+        None => return Ok(Value::Object(Default::default())),
+        Some("-") => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| ExecError::InvalidParams(format!("failed to read stdin: {}", e)))?;
+            text
+        }
+        Some(text) => text.to_string(),
+    };
+    serde_json::from_str(&text).map_err(|e| ExecError::InvalidParams(e.to_string()))
+}
+
+/// Connect to the configured server and authenticate
+///
+/// Missing secrets are read from the terminal. The connection is recorded in
+/// the audit log.
+pub async fn connect(
+    config: &ClientConfig,
+    sessions: Arc<auth::SessionStore>,
+) -> Result<protocol::Client, ExecError> {
+    let client = protocol::Client::connect_with_config(&config.server, None).await;
+    audit::record_connection(config, AuditEvent::Connect, &client);
+    let client = client.map_err(ExecError::Connect)?;
+
+    let username = config.auth.username_or_current();
+    let options = auth_options(config, sessions);
+
+    match auth::negotiate::authenticate(&client, &config.auth, &username, &options).await {
+        Ok(true) => Ok(client),
+        Ok(false) => Err(ExecError::Auth(anyhow::anyhow!("rejected by the server"))),
+        Err(e) => Err(ExecError::Auth(e)),
+    }
+}
+
+/// Refresh the session token of a connection if it is about to expire
+///
+/// A failed refresh is only logged: the connection stays usable, and the next
+/// connect authenticates in full.
+pub async fn refresh_session(
+    client: &protocol::Client,
+    config: &ClientConfig,
+    sessions: Arc<auth::SessionStore>,
+) {
+    let username = config.auth.username_or_current();
+    let options = auth_options(config, sessions);
+    match auth::session::refresh_if_needed(client, &username, &options).await {
+        Ok(true) => log::debug!("Session token refreshed"),
+        Ok(false) => {}
+        Err(e) => log::warn!("Failed to refresh session token: {:#}", e),
+    }
+}
+
+fn auth_options(config: &ClientConfig, sessions: Arc<auth::SessionStore>) -> auth::AuthOptions {
+    auth::AuthOptions::from_config(config, Arc::new(auth::TerminalPrompter)).with_sessions(sessions)
+}
+
+/// Connect, run one command and disconnect, returning the response data
+pub async fn exec(
+    config: &ClientConfig,
+    command: &str,
+    params: Value,
+    timeout_secs: u64,
+) -> Result<Value, ExecError> {
+    let sessions = Arc::new(auth::SessionStore::from_config(&config.auth));
+    let client = connect(config, sessions.clone()).await?;
+    refresh_session(&client, config, sessions).await;
+    let result = client
+        .execute(command, params, timeout_secs)
+        .await
+        .map_err(ExecError::Command);
+
+    let closed = client.close().await;
+    audit::record_connection(config, AuditEvent::Disconnect, &closed);
+    if let Err(e) = closed {
+        log::warn!("Failed to close the connection: {:#}", e);
+    }
+    result
+}
@@ -2,10 +2,10 @@
 pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1";
 
 /// Default server port
-pub const DEFAULT_SERVER_PORT: u16 = 8716;
+pub const DEFAULT_SERVER_PORT: u16 = 8717;
 
 /// Default authentication method
-pub const DEFAULT_AUTH_METHOD: &str = "password";
+pub const DEFAULT_AUTH_METHOD: &str = "auto";
 
 /// Default theme
 pub const DEFAULT_UI_THEME: &str = "default";
@@ -0,0 +1,323 @@
+//! Editing a config file by dotted key
+//!
+//! [`ConfigEditor`] backs the `config get`, `set` and `unset` subcommands and
+//! the GUI's Save Config. Values are parsed as the type of the setting they
+//! replace, and keys that [`ClientConfig`] does not have are refused, naming
+//! the closest known key. Only the keys set in the file are written back;
+//! comments are not kept.
+
+use crate::config::layers::{collect_leaves, merge_table, parse_env_value};
+use crate::config::migrate::{read_config_table, CONFIG_VERSION};
+use crate::config::storage::write_locked;
+use crate::config::validate::suggest_name;
+use crate::config::{ClientConfig, ConfigIssue, ConfigSource, Severity};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// A config file opened for changes
+#[derive(Debug, Clone)]
+pub struct ConfigEditor {
+    path: PathBuf,
+    original: Table,
+    table: Table,
+}
+
+impl ConfigEditor {
+    /// Open a config file, which does not need to exist yet
+    pub async fn open(path: &Path) -> Result<Self> {
+        let table = if path.exists() {
+            read_config_table(path, false, true).await?
+        } else {
+            let mut table = Table::new();
+            table.insert("version".to_string(), Value::Integer(CONFIG_VERSION.into()));
+            table
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            original: table.clone(),
+            table,
+        })
+    }
+
+    /// Path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Value of a dotted key set in the file
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        lookup(&self.table, key)
+    }
+
+    /// Every value set in the file, by dotted key
+    pub fn entries(&self) -> Vec<(String, Value)> {
+        let mut entries = Vec::new();
+        collect_leaves(&self.table, "", &mut entries);
+        entries
+    }
+
+    /// Set a dotted key, parsing `raw` as the type of the setting
+    ///
+    /// Lists are given as comma-separated items, and booleans as `true` or
+    /// `false` (or `yes`/`no`, `on`/`off`, `1`/`0`).
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<()> {
+        check_key(key)?;
+        if key == "version" {
+            bail!("version is set by the client when it upgrades the file");
+        }
+
+        let defaults = default_table();
+        let expected = lookup(&defaults, key);
+        let candidates = match expected {
+            Some(Value::Table(_)) => bail!("{} is a section; set one of its keys", key),
+            Some(current) => vec![parse_env_value(Some(current), raw)
+                .with_context(|| format!("{} must be {}", key, type_name(current)))?],
+            None => untyped_candidates(raw),
+        };
+
+        // Keys the config does not have are dropped when it is read back
+        let mut last_error = None;
+        for value in candidates {
+            let mut table = self.table.clone();
+            insert(&mut table, key, value)?;
+            match with_defaults(&table) {
+                Ok(config) if has_key(&config, key) => {
+                    self.table = table;
+                    return Ok(());
+                }
+                Ok(_) => bail!("unknown key {} ({})", key, suggest_key(key)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(e).with_context(|| format!("Invalid value for {}", key)),
+            None => bail!("no value given for {}", key),
+        }
+    }
+
+    /// Remove a dotted key, returning whether it was set
+    ///
+    /// Sections left empty are removed too.
+    pub fn unset(&mut self, key: &str) -> bool {
+        remove(&mut self.table, key)
+    }
+
+    /// Configuration the file describes, on top of the built-in defaults
+    pub fn config(&self) -> Result<ClientConfig> {
+        with_defaults(&self.table).with_context(|| format!("Invalid config file: {:?}", self.path))
+    }
+
+    /// Problems found by validation that the changes added
+    pub fn new_issues(&self) -> Result<Vec<ConfigIssue>> {
+        let before = with_defaults(&self.original)
+            .map(|config| config.validate().issues)
+            .unwrap_or_default();
+        let after = self.config()?.validate().issues;
+        Ok(after
+            .into_iter()
+            .filter(|issue| !before.contains(issue))
+            .collect())
+    }
+
+    /// Write the file back
+    pub async fn save(&self) -> Result<()> {
+        let content = toml::to_string_pretty(&self.table).context("Failed to serialize config")?;
+        write_locked(self.path.clone(), content.into_bytes())
+            .await
+            .with_context(|| format!("Failed to write config file: {:?}", self.path))
+    }
+}
+
+/// Server and login settings that differ between `current` and `edited`, as
+/// dotted keys for the user config file
+///
+/// If a profile is applied to `current`, the keys are in that profile. An
+/// unset username is `None`, which removes the setting.
+pub fn changed_settings(
+    current: &ClientConfig,
+    edited: &ClientConfig,
+) -> Vec<(String, Option<String>)> {
+    let prefix = match &current.active_profile {
+        Some(name) => format!("profiles.{}.", name),
+        None => String::new(),
+    };
+
+    let settings = |config: &ClientConfig| {
+        [
+            ("server.address", Some(config.server.address.clone())),
+            ("server.port", Some(config.server.port.to_string())),
+            ("server.use_tls", Some(config.server.use_tls.to_string())),
+            ("auth.method", Some(config.auth.method.clone())),
+            ("auth.username", config.auth.username.clone()),
+            (
+                "auth.save_credentials",
+                Some(config.auth.save_credentials.to_string()),
+            ),
+        ]
+    };
+    settings(current)
+        .into_iter()
+        .zip(settings(edited))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|(_, (key, new))| (format!("{}{}", prefix, key), new))
+        .collect()
+}
+
+/// Set or, for `None`, remove each dotted key in the config file at `path`
+///
+/// The file is only written if the changes add no validation errors;
+/// otherwise the errors are returned. Other keys in the file are kept as
+/// they are.
+pub async fn update_config_file(path: &Path, changes: &[(String, Option<String>)]) -> Result<()> {
+    let mut editor = ConfigEditor::open(path).await?;
+    for (key, value) in changes {
+        match value {
+            Some(raw) => editor.set(key, raw)?,
+            None => {
+                editor.unset(key);
+            }
+        }
+    }
+
+    let errors: Vec<String> = editor
+        .new_issues()?
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(ToString::to_string)
+        .collect();
+    if !errors.is_empty() {
+        bail!("{}", errors.join("; "));
+    }
+    editor.save().await
+}
+
+/// The built-in defaults as a TOML table
+fn default_table() -> Table {
+    match Value::try_from(ClientConfig::default()) {
+        Ok(Value::Table(table)) => table,
+        _ => Table::new(),
+    }
+}
+
+/// Configuration of a file's settings merged over the defaults, as in [`ConfigLayers`]
+///
+/// [`ConfigLayers`]: crate::config::ConfigLayers
+fn with_defaults(table: &Table) -> Result<ClientConfig, toml::de::Error> {
+    let mut values = default_table();
+    merge_table(
+        &mut values,
+        table.clone(),
+        "",
+        &ConfigSource::Default,
+        &mut BTreeMap::new(),
+    );
+    Value::Table(values).try_into()
+}
+
+/// Whether a key survives a round trip through `ClientConfig`, which drops unknown keys
+fn has_key(config: &ClientConfig, key: &str) -> bool {
+    match Value::try_from(config) {
+        Ok(Value::Table(table)) => lookup(&table, key).is_some(),
+        _ => false,
+    }
+}
+
+fn suggest_key(key: &str) -> String {
+    let mut keys = Vec::new();
+    collect_leaves(&default_table(), "", &mut keys);
+    let names: Vec<&str> = keys.iter().map(|(name, _)| name.as_str()).collect();
+    let suggestion = suggest_name(key, &names);
+    if suggestion.starts_with("did you mean") {
+        suggestion
+    } else {
+        "`config list --effective` shows every key".to_string()
+    }
+}
+
+/// Values `raw` could mean for a setting without a default, most specific first
+fn untyped_candidates(raw: &str) -> Vec<Value> {
+    let mut candidates = Vec::new();
+    if let Ok(value) = parse_env_value(Some(&Value::Boolean(false)), raw) {
+        candidates.push(value);
+    }
+    if let Ok(integer) = raw.trim().parse::<i64>() {
+        candidates.push(Value::Integer(integer));
+    }
+    if let Ok(float) = raw.trim().parse::<f64>() {
+        candidates.push(Value::Float(float));
+    }
+    candidates.push(Value::String(raw.to_string()));
+    if let Ok(list) = parse_env_value(Some(&Value::Array(Vec::new())), raw) {
+        candidates.push(list);
+    }
+    candidates
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "a string",
+        Value::Integer(_) => "an integer",
+        Value::Float(_) => "a number",
+        Value::Boolean(_) => "true or false",
+        Value::Datetime(_) => "a date",
+        Value::Array(_) => "a comma-separated list",
+        Value::Table(_) => "a section",
+    }
+}
+
+fn check_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.split('.').any(str::is_empty) {
+        bail!(
+            "invalid key {:?}; use a dotted key such as server.port",
+            key
+        );
+    }
+    Ok(())
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    let (parents, last) = match key.rsplit_once('.') {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, key),
+    };
+
+    let mut current = table;
+    for part in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        current = match current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(table) => table,
+            _ => bail!("{} is not a section", part),
+        };
+    }
+    current.insert(last.to_string(), value);
+    Ok(())
+}
+
+fn remove(table: &mut Table, key: &str) -> bool {
+    match key.split_once('.') {
+        None => table.remove(key).is_some(),
+        Some((first, rest)) => {
+            let removed = match table.get_mut(first) {
+                Some(Value::Table(child)) => remove(child, rest),
+                _ => false,
+            };
+            if matches!(table.get(first), Some(Value::Table(child)) if child.is_empty()) {
+                table.remove(first);
+            }
+            removed
+        }
+    }
+}
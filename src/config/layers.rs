@@ -0,0 +1,612 @@
+//! Layered configuration
+//!
+//! The effective configuration is built from these layers, each overriding
+//! the ones before it:
+//!
+//! 1. built-in defaults
+//! 2. the system config, `/etc/rcp_client/config.toml`
+//! 3. the user config, `config.toml` in the config directory (see [`paths`])
+//! 4. the project config, the nearest `.rcp.toml` in the working directory or
+//!    one of its parents
+//! 5. the selected profile
+//! 6. `RCP_*` environment variables, such as `RCP_SERVER_PORT=9000`
+//! 7. command-line flags
+//!
+//! Files only need to contain the keys they change. The layer that set each
+//! value is kept, so `--show-config` can report where a setting came from.
+//!
+//! A project config comes with the directory it is found in, which may not be
+//! the user's own. It can choose the server and login, but settings that hold
+//! secrets, run commands, or decide where secrets are kept and which server
+//! identity is trusted ([`PROJECT_RESTRICTED_KEYS`]) are ignored with a
+//! warning, also inside its profiles. Likewise, `file:` and `cmd:` secret
+//! references are only read from the system and user configs.
+
+use crate::auth::SecretRef;
+use crate::config::migrate::read_config_table;
+use crate::config::paths;
+use crate::config::ClientConfig;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Name of the project config file
+pub const PROJECT_CONFIG_FILE: &str = ".rcp.toml";
+
+/// Prefix of the environment variables that override config values
+pub const ENV_PREFIX: &str = "RCP_";
+
+/// Tables that environment variables can set keys in, longest first
+const ENV_TABLES: &[&str] = &["auth.store", "auth.oauth", "server", "audit", "auth", "ui"];
+
+/// Top-level keys that environment variables can set
+const ENV_TOP_LEVEL_KEYS: &[&str] = &["default_profile"];
+
+/// Keys a project config cannot set, at the top level or in a profile
+pub const PROJECT_RESTRICTED_KEYS: &[&str] = &[
+    "auth.psk",
+    "auth.credential_helper",
+    "auth.native_helper_socket",
+    "auth.store",
+    "auth.oauth",
+    "server.socket_path",
+    "server.verify_server",
+    "server.client_cert_path",
+    "server.client_key_path",
+    "server.known_servers_path",
+    "audit.path",
+];
+
+/// Settings holding a [`SecretRef`], at the top level or in a profile
+const SECRET_KEYS: &[&str] = &["auth.psk"];
+
+/// Layer an effective config value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+
+    /// System-wide config file
+    System(PathBuf),
+
+    /// User config file
+    User(PathBuf),
+
+    /// Project config file
+    Project(PathBuf),
+
+    /// Environment variable
+    Environment(String),
+
+    /// Named profile
+    Profile(String),
+
+    /// Command-line flag
+    CommandLine,
+}
+
+impl ConfigSource {
+    /// Whether `file:` and `cmd:` secret references set in this layer are used
+    pub fn allows_command_secrets(&self) -> bool {
+        matches!(
+            self,
+            ConfigSource::Default | ConfigSource::System(_) | ConfigSource::User(_)
+        )
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::System(path) => write!(f, "system config {}", path.display()),
+            ConfigSource::User(path) => write!(f, "user config {}", path.display()),
+            ConfigSource::Project(path) => write!(f, "project config {}", path.display()),
+            ConfigSource::Environment(var) => write!(f, "environment variable {}", var),
+            ConfigSource::Profile(name) => write!(f, "profile {}", name),
+            ConfigSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// Path of the system-wide config file
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData")
+            .map(|dir| PathBuf::from(dir).join("rcp_client").join("config.toml"))
+    } else {
+        Some(PathBuf::from("/etc/rcp_client/config.toml"))
+    }
+}
+
+/// Default path of the user config file
+pub fn user_config_path() -> PathBuf {
+    paths::config_dir().join("config.toml")
+}
+
+/// Find the nearest project config file in `start` or one of its parents
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Configuration values merged from several layers
+#[derive(Debug, Clone)]
+pub struct ConfigLayers {
+    values: Table,
+    origins: BTreeMap<String, ConfigSource>,
+    profile: Option<String>,
+    command_line: Table,
+    user_config: Option<PathBuf>,
+    working_dir: Option<PathBuf>,
+    files: Vec<PathBuf>,
+    recover: bool,
+}
+
+impl ConfigLayers {
+    /// Start from the built-in defaults
+    pub fn new() -> Self {
+        let values = match Value::try_from(ClientConfig::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+
+        Self {
+            values,
+            origins: BTreeMap::new(),
+            profile: None,
+            command_line: Table::new(),
+            user_config: None,
+            working_dir: None,
+            files: Vec::new(),
+            recover: false,
+        }
+    }
+
+    /// Load the system, user and project configs and the environment
+    ///
+    /// `user_config` replaces the default user config path, and `working_dir`
+    /// is where the search for a project config starts. Missing files are
+    /// skipped, and damaged ones are read from their backup.
+    pub async fn load(user_config: Option<&Path>, working_dir: Option<&Path>) -> Result<Self> {
+        Self::load_files(user_config, working_dir, true).await
+    }
+
+    async fn load_files(
+        user_config: Option<&Path>,
+        working_dir: Option<&Path>,
+        recover: bool,
+    ) -> Result<Self> {
+        let mut layers = Self::new();
+        layers.recover = recover;
+        layers.user_config = user_config.map(Path::to_path_buf);
+        layers.working_dir = working_dir.map(Path::to_path_buf);
+
+        if let Some(path) = system_config_path() {
+            layers
+                .merge_file(&path, ConfigSource::System(path.clone()))
+                .await?;
+            layers.files.push(path);
+        }
+
+        let user_path = user_config
+            .map(Path::to_path_buf)
+            .unwrap_or_else(user_config_path);
+        layers
+            .merge_file(&user_path, ConfigSource::User(user_path.clone()))
+            .await?;
+        layers.files.push(user_path);
+
+        if let Some(path) = working_dir.and_then(find_project_config) {
+            layers
+                .merge_file(&path, ConfigSource::Project(path.clone()))
+                .await?;
+            layers.files.push(path);
+        }
+
+        layers.merge_env(std::env::vars())?;
+        Ok(layers)
+    }
+
+    /// Load the files and environment again, keeping the profile and command-line flags
+    ///
+    /// Unlike [`ConfigLayers::load`], a file that cannot be parsed is an error,
+    /// so a broken edit is reported rather than replaced by the backup.
+    pub async fn reload(&self) -> Result<Self> {
+        let mut layers = Self::load_files(
+            self.user_config.as_deref(),
+            self.working_dir.as_deref(),
+            false,
+        )
+        .await?;
+        layers.profile = self.profile.clone();
+        layers.command_line = self.command_line.clone();
+        Ok(layers)
+    }
+
+    /// Config files read by [`ConfigLayers::load`], including ones that do not exist yet
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Merge a config file, returning false if it does not exist
+    pub async fn merge_file(&mut self, path: &Path, source: ConfigSource) -> Result<bool> {
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        // Only the user's own config file is rewritten when it is upgraded
+        let persist = matches!(source, ConfigSource::User(_));
+        let mut table = read_config_table(path, self.recover, persist).await?;
+        if let ConfigSource::Project(path) = &source {
+            restrict_project_config(&mut table, path);
+        }
+        self.merge(table, &source);
+        log::debug!("Merged {}", source);
+        Ok(true)
+    }
+
+    /// Merge the values of a TOML table over the current ones
+    ///
+    /// `file:` and `cmd:` secret references are dropped with a warning unless
+    /// the source is the system or user config.
+    pub fn merge(&mut self, mut table: Table, source: &ConfigSource) {
+        if !source.allows_command_secrets() {
+            reject_command_secrets(&mut table, source);
+        }
+        merge_table(&mut self.values, table, "", source, &mut self.origins);
+    }
+
+    /// Merge `RCP_*` variables, such as `RCP_SERVER_PORT` or `RCP_AUTH_STORE_BACKEND`
+    ///
+    /// Variables that do not name a config key are ignored. Values are parsed
+    /// as the type of the value they replace; lists are separated by commas.
+    pub fn merge_env<I>(&mut self, vars: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (var, raw) in vars {
+            let key = match env_var_key(&var) {
+                Some(key) => key,
+                None => continue,
+            };
+            let value = parse_env_value(self.get(&key), &raw)
+                .with_context(|| format!("Invalid value for {}", var))?;
+            self.set(&key, value, ConfigSource::Environment(var));
+        }
+        Ok(())
+    }
+
+    /// Set one value by its dotted key, such as `server.port`
+    pub fn set(&mut self, key: &str, value: Value, source: ConfigSource) {
+        let mut table = Table::new();
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let last = parts.pop().unwrap_or(key);
+        table.insert(last.to_string(), value);
+        for part in parts.into_iter().rev() {
+            let mut parent = Table::new();
+            parent.insert(part.to_string(), Value::Table(table));
+            table = parent;
+        }
+        self.merge(table, &source);
+    }
+
+    /// Set a value from a command-line flag, applied after the profile and environment
+    pub fn set_command_line(&mut self, key: &str, value: impl Into<Value>) {
+        self.command_line.insert(key.to_string(), value.into());
+    }
+
+    /// Select a profile, overriding `default_profile`
+    pub fn select_profile(&mut self, name: Option<&str>) {
+        self.profile = name.map(str::to_string);
+    }
+
+    /// Current value of a dotted key, before any profile or command-line flag
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let mut parts = key.split('.');
+        let mut value = self.values.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    /// Values set by `RCP_*` variables that are applied over the profile
+    ///
+    /// `default_profile` chooses the profile, so it is not among them.
+    fn env_values(&self) -> impl Iterator<Item = (&String, &Value)> + '_ {
+        self.origins.iter().filter_map(|(key, source)| {
+            if !matches!(source, ConfigSource::Environment(_))
+                || ENV_TOP_LEVEL_KEYS.contains(&key.as_str())
+            {
+                return None;
+            }
+            self.get(key).map(|value| (key, value))
+        })
+    }
+
+    /// Build the effective configuration with the profile, environment and
+    /// command-line flags applied
+    pub fn resolve(&self) -> Result<ClientConfig> {
+        let base: ClientConfig = Value::Table(self.values.clone())
+            .try_into()
+            .context("Invalid configuration")?;
+        let mut config = base.select_profile(self.profile.as_deref())?;
+
+        // Environment variables and command-line flags override the profile
+        let overrides: Vec<_> = self.env_values().chain(&self.command_line).collect();
+        if !overrides.is_empty() {
+            let mut values = Value::try_from(&config)?;
+            for (key, value) in overrides {
+                set_value(&mut values, key, value.clone());
+            }
+            let mut updated: ClientConfig = values
+                .try_into()
+                .context("Invalid environment or command-line configuration")?;
+            updated.carry_profile_from(&config);
+            config = updated;
+        }
+
+        Ok(config)
+    }
+
+    /// Every effective value with the layer it came from, sorted by key
+    pub fn explain(&self) -> Result<Vec<(String, Value, ConfigSource)>> {
+        let config = self.resolve()?;
+        let mut origins = self.origins.clone();
+
+        if let Some(name) = &config.active_profile {
+            if let Some(profile) = config.profiles.get(name) {
+                let mut sections = Table::new();
+                sections.insert("server".to_string(), Value::try_from(&profile.server)?);
+                sections.insert("auth".to_string(), Value::try_from(&profile.auth)?);
+                record_leaves(
+                    &sections,
+                    "",
+                    &ConfigSource::Profile(name.clone()),
+                    &mut origins,
+                );
+            }
+        }
+        for (key, _) in self.env_values() {
+            origins.insert(key.clone(), self.origins[key].clone());
+        }
+        for key in self.command_line.keys() {
+            origins.insert(key.clone(), ConfigSource::CommandLine);
+        }
+
+        let mut values = Table::new();
+        if let Value::Table(mut table) = Value::try_from(&config)? {
+            // Profiles are listed as definitions, not as effective values
+            table.remove("profiles");
+            values = table;
+        }
+
+        let mut leaves = Vec::new();
+        collect_leaves(&values, "", &mut leaves);
+        Ok(leaves
+            .into_iter()
+            .map(|(key, value)| {
+                let source = origins.get(&key).cloned().unwrap_or(ConfigSource::Default);
+                (key, value, source)
+            })
+            .collect())
+    }
+
+    /// Layer the effective value of a dotted key came from
+    pub fn origin(&self, key: &str) -> ConfigSource {
+        if self.command_line.contains_key(key) {
+            return ConfigSource::CommandLine;
+        }
+        self.origins
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+}
+
+impl Default for ConfigLayers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recursively merge `from` into `into`, recording the source of each value
+pub(crate) fn merge_table(
+    into: &mut Table,
+    from: Table,
+    prefix: &str,
+    source: &ConfigSource,
+    origins: &mut BTreeMap<String, ConfigSource>,
+) {
+    for (key, value) in from {
+        let path = join_key(prefix, &key);
+        match (into.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => {
+                merge_table(existing, table, &path, source, origins);
+            }
+            (_, value) => {
+                if let Value::Table(table) = &value {
+                    record_leaves(table, &path, source, origins);
+                } else {
+                    origins.insert(path, source.clone());
+                }
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Remove the [`PROJECT_RESTRICTED_KEYS`] from a project config, warning about each one
+fn restrict_project_config(table: &mut Table, path: &Path) {
+    let mut ignored = Vec::new();
+    for key in PROJECT_RESTRICTED_KEYS {
+        if remove_value(table, key).is_some() {
+            ignored.push(key.to_string());
+        }
+    }
+    if let Some(Value::Table(profiles)) = table.get_mut("profiles") {
+        for (name, profile) in profiles.iter_mut() {
+            let Value::Table(profile) = profile else {
+                continue;
+            };
+            for key in PROJECT_RESTRICTED_KEYS {
+                if remove_value(profile, key).is_some() {
+                    ignored.push(format!("profiles.{}.{}", name, key));
+                }
+            }
+        }
+    }
+
+    for key in ignored {
+        log::warn!(
+            "Ignoring {} in project config {}: it can only be set in the user or system config",
+            key,
+            path.display()
+        );
+    }
+}
+
+/// Remove secret references that read a file or run a command, warning about each one
+fn reject_command_secrets(table: &mut Table, source: &ConfigSource) {
+    let mut keys: Vec<String> = SECRET_KEYS.iter().map(|key| key.to_string()).collect();
+    if let Some(Value::Table(profiles)) = table.get("profiles") {
+        for name in profiles.keys() {
+            keys.extend(
+                SECRET_KEYS
+                    .iter()
+                    .map(|key| format!("profiles.{}.{}", name, key)),
+            );
+        }
+    }
+
+    for key in keys {
+        let runs_command = lookup_value(table, &key)
+            .and_then(Value::as_str)
+            .and_then(|value| value.parse::<SecretRef>().ok())
+            .is_some_and(|secret| matches!(secret, SecretRef::File(_) | SecretRef::Command(_)));
+        if runs_command {
+            remove_value(table, &key);
+            log::warn!(
+                "Ignoring {} from {}: file: and cmd: references are only read from the user or system config",
+                key,
+                source
+            );
+        }
+    }
+}
+
+/// Value of a dotted key in a table
+fn lookup_value<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    match key.split_once('.') {
+        Some((first, rest)) => lookup_value(table.get(first)?.as_table()?, rest),
+        None => table.get(key),
+    }
+}
+
+/// Remove a dotted key from a table, returning its value
+fn remove_value(table: &mut Table, key: &str) -> Option<Value> {
+    match key.split_once('.') {
+        Some((first, rest)) => match table.get_mut(first)? {
+            Value::Table(table) => remove_value(table, rest),
+            _ => None,
+        },
+        None => table.remove(key),
+    }
+}
+
+/// Record the source of every value in a table
+fn record_leaves(
+    table: &Table,
+    prefix: &str,
+    source: &ConfigSource,
+    origins: &mut BTreeMap<String, ConfigSource>,
+) {
+    for (key, value) in table {
+        let path = join_key(prefix, key);
+        match value {
+            Value::Table(table) => record_leaves(table, &path, source, origins),
+            _ => {
+                origins.insert(path, source.clone());
+            }
+        }
+    }
+}
+
+/// Flatten a table into dotted keys and values
+pub(crate) fn collect_leaves(table: &Table, prefix: &str, leaves: &mut Vec<(String, Value)>) {
+    for (key, value) in table {
+        let path = join_key(prefix, key);
+        match value {
+            Value::Table(table) => collect_leaves(table, &path, leaves),
+            value => leaves.push((path, value.clone())),
+        }
+    }
+}
+
+/// Set a dotted key in a TOML value, creating tables as needed
+fn set_value(root: &mut Value, key: &str, value: Value) {
+    let mut current = root;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let table = match current {
+            Value::Table(table) => table,
+            _ => return,
+        };
+        if parts.peek().is_none() {
+            table.insert(part.to_string(), value);
+            return;
+        }
+        current = table
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Dotted config key named by an `RCP_*` environment variable
+fn env_var_key(var: &str) -> Option<String> {
+    let name = var.strip_prefix(ENV_PREFIX)?.to_lowercase();
+
+    if ENV_TOP_LEVEL_KEYS.contains(&name.as_str()) {
+        return Some(name);
+    }
+    ENV_TABLES.iter().find_map(|table| {
+        let field = name
+            .strip_prefix(&table.replace('.', "_"))?
+            .strip_prefix('_')?;
+        (!field.is_empty()).then(|| format!("{}.{}", table, field))
+    })
+}
+
+/// Parse an environment variable as the type of the value it replaces
+pub(crate) fn parse_env_value(current: Option<&Value>, raw: &str) -> Result<Value> {
+    Ok(match current {
+        Some(Value::Integer(_)) => Value::Integer(raw.trim().parse()?),
+        Some(Value::Float(_)) => Value::Float(raw.trim().parse()?),
+        Some(Value::Boolean(_)) => Value::Boolean(match raw.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            other => anyhow::bail!("expected true or false, got {}", other),
+        }),
+        Some(Value::Array(_)) => Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        ),
+        _ => Value::String(raw.to_string()),
+    })
+}
@@ -0,0 +1,216 @@
+//! `rcp://` connection links
+//!
+//! A link describes how to reach a server, so a connection setup can be
+//! shared in chat or a ticket and opened from the command line or the GUI:
+//!
+//! ```text
+//! rcp://alice@rcp.example.com:8717/?auth=psk&tls=1&profile=office
+//! ```
+//!
+//! | Part | Setting |
+//! |------|---------|
+//! | user | `auth.username` |
+//! | host, port | `server.address`, `server.port` |
+//! | `auth` | `auth.method` |
+//! | `tls` | `server.use_tls` (`1` or `0`) |
+//! | `profile` | Profile applied before the other settings |
+//!
+//! Links never carry secrets: generated links leave them out, and links with
+//! a password or secret parameter are refused. Likewise, a link cannot turn
+//! off server identity pinning, which is only set in the config.
+
+use crate::config::{AuthOverrides, ClientConfig, ServerOverrides};
+use anyhow::{bail, Context, Result};
+use percent_encoding::percent_decode_str;
+use std::fmt;
+use std::str::FromStr;
+use toml::Value;
+use url::{Host, Url};
+
+/// URI scheme of connection links
+pub const LINK_SCHEME: &str = "rcp";
+
+/// Query parameters that would carry a secret
+const SECRET_PARAMS: &[&str] = &["password", "psk", "token", "secret", "key"];
+
+/// Connection settings from an `rcp://` link
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLink {
+    /// Profile to apply before the other settings
+    pub profile: Option<String>,
+
+    /// Address, port and TLS settings
+    pub server: ServerOverrides,
+
+    /// Username and authentication method
+    pub auth: AuthOverrides,
+}
+
+impl ConnectionLink {
+    /// Link to the server and login of a configuration, without any secrets
+    pub fn from_config(config: &ClientConfig) -> Self {
+        let mut link = Self {
+            profile: config.active_profile.clone(),
+            ..Self::default()
+        };
+        link.server.address = Some(config.server.address.clone());
+        link.server.port = Some(config.server.port);
+        link.server.use_tls = Some(config.server.use_tls);
+        link.auth.username = config.auth.username.clone();
+        if config.auth.method != crate::auth::negotiate::AUTO_METHOD {
+            link.auth.method = Some(config.auth.method.clone());
+        }
+        link
+    }
+
+    /// Configuration with the link's profile and settings applied
+    pub fn apply(&self, config: &ClientConfig) -> Result<ClientConfig> {
+        let mut config = match &self.profile {
+            Some(name) => config.with_profile(name)?,
+            None => config.clone(),
+        };
+        self.server.apply(&mut config.server);
+        self.auth.apply(&mut config.auth);
+        Ok(config)
+    }
+
+    /// The link's settings as dotted keys, for the command-line layer
+    pub fn settings(&self) -> Vec<(&'static str, Value)> {
+        let mut settings = Vec::new();
+        if let Some(address) = &self.server.address {
+            settings.push(("server.address", Value::from(address.as_str())));
+        }
+        if let Some(port) = self.server.port {
+            settings.push(("server.port", Value::from(i64::from(port))));
+        }
+        if let Some(use_tls) = self.server.use_tls {
+            settings.push(("server.use_tls", Value::from(use_tls)));
+        }
+        if let Some(username) = &self.auth.username {
+            settings.push(("auth.username", Value::from(username.as_str())));
+        }
+        if let Some(method) = &self.auth.method {
+            settings.push(("auth.method", Value::from(method.as_str())));
+        }
+        settings
+    }
+}
+
+impl FromStr for ConnectionLink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let url =
+            Url::parse(s.trim()).with_context(|| format!("Invalid connection link {:?}", s))?;
+        if url.scheme() != LINK_SCHEME {
+            bail!(
+                "Connection links start with {}://, got {:?}",
+                LINK_SCHEME,
+                s
+            );
+        }
+        if url.password().is_some() {
+            bail!("Connection links cannot carry a password; remove it from the link");
+        }
+
+        let mut link = Self::default();
+        link.server.address = match url.host() {
+            Some(Host::Ipv6(address)) => Some(address.to_string()),
+            Some(host) => Some(decode(&host.to_string())?),
+            None => None,
+        };
+        link.server.port = url.port();
+        if !url.username().is_empty() {
+            link.auth.username = Some(decode(url.username())?);
+        }
+
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "auth" => link.auth.method = Some(value.into_owned()),
+                "tls" => link.server.use_tls = Some(parse_flag(&name, &value)?),
+                "verify" => bail!(
+                    "Connection links cannot change server identity pinning; set verify_server in the config instead"
+                ),
+                "profile" => link.profile = Some(value.into_owned()),
+                name if SECRET_PARAMS.contains(&name) => {
+                    bail!(
+                        "Connection links cannot carry secrets; remove the {} parameter",
+                        name
+                    )
+                }
+                name => bail!(
+                    "Unknown connection link parameter {:?}; expected auth, tls or profile",
+                    name
+                ),
+            }
+        }
+
+        if link.server.address.is_none() && link.profile.is_none() {
+            bail!(
+                "Connection link {:?} names neither a server nor a profile",
+                s
+            );
+        }
+        Ok(link)
+    }
+}
+
+impl fmt::Display for ConnectionLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = match self.server.address.as_deref() {
+            Some(address) if address.parse::<std::net::Ipv6Addr>().is_ok() => {
+                format!("[{}]", address)
+            }
+            Some(address) => address.to_string(),
+            None => String::new(),
+        };
+        let mut url = match Url::parse(&format!("{}://{}/", LINK_SCHEME, host)) {
+            Ok(url) => url,
+            Err(_) => return Err(fmt::Error),
+        };
+        // A link to a profile alone has no host to attach the user and port to
+        if !host.is_empty() {
+            if let Some(username) = &self.auth.username {
+                url.set_username(username).map_err(|_| fmt::Error)?;
+            }
+            url.set_port(self.server.port).map_err(|_| fmt::Error)?;
+        }
+
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(method) = &self.auth.method {
+                query.append_pair("auth", method);
+            }
+            if let Some(use_tls) = self.server.use_tls {
+                query.append_pair("tls", if use_tls { "1" } else { "0" });
+            }
+            if let Some(profile) = &self.profile {
+                query.append_pair("profile", profile);
+            }
+        }
+        if url.query() == Some("") {
+            url.set_query(None);
+        }
+        write!(f, "{}", url)
+    }
+}
+
+fn parse_flag(name: &str, value: &str) -> Result<bool> {
+    match value {
+        "1" | "true" | "yes" => Ok(true),
+        "0" | "false" | "no" => Ok(false),
+        _ => bail!(
+            "Connection link parameter {} must be 1 or 0, got {:?}",
+            name,
+            value
+        ),
+    }
+}
+
+/// Undo the percent-encoding of user and host names
+fn decode(value: &str) -> Result<String> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .context("Invalid percent-encoding in connection link")
+}
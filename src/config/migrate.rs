@@ -0,0 +1,170 @@
+//! Config schema versions and migrations
+//!
+//! Config files carry a top-level `version`; files without one are version 1.
+//! On load, older files are upgraded one version at a time by the migrations
+//! below. The user config is written back, keeping the original as
+//! `<file>.v<N>.bak`; other files, such as the system config or a project's
+//! `.rcp.toml`, are only upgraded in memory.
+//!
+//! | Version | Change |
+//! |---------|--------|
+//! | 1 | No `version` key |
+//! | 2 | `auth.use_native_auth` removed; `true` becomes `auth.method = "native"` if no method is set |
+
+use crate::config::storage::{recover_from_backup, write_copy, write_locked};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use toml::{Table, Value};
+
+/// Config schema version written by this client
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Upgrade from version `n` to `n + 1`, at index `n - 1`
+const MIGRATIONS: &[fn(&mut Table)] = &[v1_to_v2];
+
+/// Config file written by a newer client than this one
+#[derive(Debug, thiserror::Error)]
+#[error("config version {found} is newer than this client supports (up to version {supported}); upgrade the client to use this config")]
+pub struct UnsupportedConfigVersion {
+    /// Version of the file
+    pub found: u32,
+
+    /// Latest version this client understands
+    pub supported: u32,
+}
+
+/// Schema version of a parsed config file
+pub fn config_version(table: &Table) -> Result<u32> {
+    match table.get("version") {
+        None => Ok(1),
+        Some(Value::Integer(version)) if *version >= 1 => {
+            Ok(u32::try_from(*version).unwrap_or(u32::MAX))
+        }
+        Some(other) => anyhow::bail!("version must be a positive integer, got {}", other),
+    }
+}
+
+/// Upgrade a parsed config file to the current version
+///
+/// Returns the version it had if it was upgraded, or `None` if it was current.
+pub fn migrate(table: &mut Table) -> Result<Option<u32>> {
+    let found = config_version(table)?;
+    if found > CONFIG_VERSION {
+        return Err(UnsupportedConfigVersion {
+            found,
+            supported: CONFIG_VERSION,
+        }
+        .into());
+    }
+    if found == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &MIGRATIONS[(found - 1) as usize..] {
+        migration(table);
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION.into()));
+    Ok(Some(found))
+}
+
+/// Read a config file, upgrading it if it is from an older version
+///
+/// With `persist`, the upgraded file is also written back; if that fails, the
+/// upgrade is only applied in memory. Without it the file is never changed.
+/// With `recover`, a file that cannot be parsed is read from the copy of what
+/// the client last wrote to it.
+pub async fn read_config_table(path: &Path, recover: bool, persist: bool) -> Result<Table> {
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read config file: {:?}", path))?;
+    let parsed = String::from_utf8(bytes)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok((toml::from_str::<Table>(&content)?, content)));
+    let (mut table, content) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let recovered = match recover {
+                true => recover_from_backup(path, toml::from_str::<Table>),
+                false => None,
+            };
+            match recovered {
+                Some((content, table)) => (table, content),
+                None => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to parse config file: {:?}", path))
+                }
+            }
+        }
+    };
+
+    let upgraded_from =
+        migrate(&mut table).with_context(|| format!("Failed to load config file: {:?}", path))?;
+    match upgraded_from {
+        Some(from) if persist => match write_upgraded(path, &content, from, &table).await {
+            Ok(()) => log::info!(
+                "Upgraded config file {:?} from version {} to {}, original kept in {:?}",
+                path,
+                from,
+                CONFIG_VERSION,
+                backup_path(path, from)
+            ),
+            Err(e) => log::warn!(
+                "Config file {:?} is version {}; using the upgrade without saving it: {:#}",
+                path,
+                from,
+                e
+            ),
+        },
+        Some(from) => log::debug!(
+            "Upgraded config file {:?} from version {} to {} in memory",
+            path,
+            from,
+            CONFIG_VERSION
+        ),
+        None => {}
+    }
+    Ok(table)
+}
+
+/// Path of the backup kept when a file is upgraded from `version`
+pub fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    PathBuf::from(backup)
+}
+
+/// Write an upgraded config file, keeping the original content as a backup
+///
+/// An existing backup from the same version is not overwritten.
+pub async fn write_upgraded(path: &Path, original: &str, from: u32, table: &Table) -> Result<()> {
+    let backup = backup_path(path, from);
+    if !backup.exists() {
+        // The backup may hold secrets, so it is as private as the file
+        let (target, content) = (path.to_path_buf(), original.to_string());
+        let written = backup.clone();
+        tokio::task::spawn_blocking(move || write_copy(&written, content.as_bytes(), &target))
+            .await
+            .context("File writer stopped")?
+            .with_context(|| format!("Failed to write config backup: {:?}", backup))?;
+    }
+
+    let content = toml::to_string_pretty(table).context("Failed to serialize config")?;
+    write_locked(path.to_path_buf(), content.into_bytes())
+        .await
+        .with_context(|| format!("Failed to write config file: {:?}", path))
+}
+
+/// Drop `auth.use_native_auth`, which `auth.method` and `auth.methods` replaced
+///
+/// With `method = "auto"`, native authentication is already negotiated, so
+/// only a file without a method is switched to `"native"`.
+fn v1_to_v2(table: &mut Table) {
+    let Some(Value::Table(auth)) = table.get_mut("auth") else {
+        return;
+    };
+    let use_native_auth = auth.remove("use_native_auth");
+    if use_native_auth == Some(Value::Boolean(true)) && !auth.contains_key("method") {
+        auth.insert("method".to_string(), Value::String("native".to_string()));
+    }
+}
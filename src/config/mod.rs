@@ -1,13 +1,39 @@
+use crate::auth::SecretRef;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
-use tokio::fs;
 
 mod defaults;
+mod edit;
+mod layers;
+mod link;
+mod migrate;
+pub mod paths;
+mod profile;
+mod storage;
+mod validate;
+mod watch;
+
+pub use edit::{changed_settings, update_config_file, ConfigEditor};
+pub use layers::{
+    find_project_config, system_config_path, user_config_path, ConfigLayers, ConfigSource,
+    PROJECT_CONFIG_FILE, PROJECT_RESTRICTED_KEYS,
+};
+pub use link::{ConnectionLink, LINK_SCHEME};
+pub use migrate::{backup_path, config_version, migrate, UnsupportedConfigVersion, CONFIG_VERSION};
+pub use profile::{AuthOverrides, Profile, ServerOverrides};
+pub use storage::{bak_path, lock_file, recover_from_backup, write_atomic, write_locked, FileLock};
+pub use validate::{ConfigIssue, Severity, ValidationReport};
+pub use watch::{changed_sections, ConfigUpdate, ConfigWatcher};
 
 /// Client configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// Config schema version, see [`CONFIG_VERSION`]
+    #[serde(default = "default_config_version")]
+    pub version: u32,
+
     /// Server configuration
     #[serde(default)]
     pub server: ServerConfig,
@@ -19,6 +45,80 @@ pub struct ClientConfig {
     /// UI configuration
     #[serde(default)]
     pub ui: UiConfig,
+
+    /// Audit log configuration
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Profile used when none is selected with `--profile`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+
+    /// Named server profiles, each overriding parts of `server` and `auth`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Profile applied to `server` and `auth`, set by [`ClientConfig::select_profile`]
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+
+    /// `server` and `auth` as they were before a profile was applied
+    #[serde(skip)]
+    unprofiled: Option<Box<(ServerConfig, AuthConfig)>>,
+}
+
+impl ClientConfig {
+    /// Names of the configured profiles, in alphabetical order
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    /// Configuration with the named profile applied
+    ///
+    /// If a profile is already applied, it is replaced rather than combined
+    /// with the new one.
+    pub fn with_profile(&self, name: &str) -> Result<ClientConfig> {
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let known = self.profile_names();
+            if known.is_empty() {
+                anyhow::anyhow!("Unknown profile {}: no profiles are configured", name)
+            } else {
+                anyhow::anyhow!("Unknown profile {} (known: {})", name, known.join(", "))
+            }
+        })?;
+
+        let mut config = self.without_profile();
+        config.unprofiled = Some(Box::new((config.server.clone(), config.auth.clone())));
+        profile.apply(&mut config.server, &mut config.auth);
+        config.active_profile = Some(name.to_string());
+        Ok(config)
+    }
+
+    /// Keep the applied profile of `other` when this config is rebuilt from it
+    pub(crate) fn carry_profile_from(&mut self, other: &ClientConfig) {
+        self.active_profile = other.active_profile.clone();
+        self.unprofiled = other.unprofiled.clone();
+    }
+
+    /// Configuration with any applied profile removed
+    pub fn without_profile(&self) -> ClientConfig {
+        let mut config = self.clone();
+        if let Some(unprofiled) = config.unprofiled.take() {
+            (config.server, config.auth) = *unprofiled;
+        }
+        config.active_profile = None;
+        config
+    }
+
+    /// Apply the named profile, or `default_profile` if no name is given
+    ///
+    /// Without either, the configuration is returned unchanged.
+    pub fn select_profile(&self, name: Option<&str>) -> Result<ClientConfig> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self.with_profile(name),
+            None => Ok(self.clone()),
+        }
+    }
 }
 
 /// Server connection configuration
@@ -39,27 +139,120 @@ pub struct ServerConfig {
     /// Path to client key for mutual TLS
     pub client_key_path: Option<String>,
 
-    /// Whether to verify server certificate
+    /// Whether to pin the server identity in the known servers file
+    ///
+    /// Certificates are never checked against a CA, so with pinning off any
+    /// certificate is accepted.
     pub verify_server: bool,
+
+    /// Path to the known servers file holding pinned server identities
+    #[serde(default)]
+    pub known_servers_path: Option<String>,
+
+    /// Accept a changed server identity and update the pin (set by `--accept-server-key`)
+    #[serde(skip)]
+    pub accept_server_key: bool,
+
+    /// Unix socket of a local server, used instead of `address` and `port`
+    #[serde(default)]
+    pub socket_path: Option<String>,
 }
 
 /// Authentication configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
-    /// Authentication method (password, psk, key)
+    /// Authentication method (auto, password, psk, native, oauth2)
     pub method: String,
 
+    /// Methods to try in order of preference when `method` is `auto`
+    #[serde(default = "default_auth_methods")]
+    pub methods: Vec<String>,
+
     /// Username for authentication
     pub username: Option<String>,
 
-    /// Pre-shared key for authentication
-    pub psk: Option<String>,
+    /// Pre-shared key, or a reference such as `env:RCP_PSK` resolved when it is used
+    pub psk: Option<SecretRef>,
 
     /// Whether to save credentials
     pub save_credentials: bool,
 
-    /// Whether to use native OS authentication
-    pub use_native_auth: bool,
+    /// Whether to generate TOTP codes from a secret in the credential store
+    #[serde(default)]
+    pub totp_from_keyring: bool,
+
+    /// Command to get, store and erase secrets, like a git credential helper
+    #[serde(default)]
+    pub credential_helper: Option<String>,
+
+    /// Whether to keep server-issued session tokens in the credential store
+    #[serde(default)]
+    pub persist_session: bool,
+
+    /// Socket of the local daemon that signs native auth assertions for remote servers
+    #[serde(default)]
+    pub native_helper_socket: Option<String>,
+
+    /// Where stored secrets are kept
+    #[serde(default)]
+    pub store: CredentialStoreConfig,
+
+    /// Identity provider for the OAuth2 device flow
+    #[serde(default)]
+    pub oauth: Option<OAuthConfig>,
+}
+
+impl AuthConfig {
+    /// Username to authenticate as: the configured one, or the current OS user
+    pub fn username_or_current(&self) -> String {
+        self.username.clone().unwrap_or_else(|| {
+            std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "user".to_string())
+        })
+    }
+}
+
+/// OAuth2 device authorization configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthConfig {
+    /// Issuer URL of the identity provider, used to discover its endpoints
+    pub issuer_url: String,
+
+    /// Client ID registered with the identity provider
+    pub client_id: String,
+
+    /// Scopes to request, separated by spaces
+    #[serde(default)]
+    pub scope: Option<String>,
+
+    /// Device authorization endpoint, if not discovered from the issuer
+    #[serde(default)]
+    pub device_authorization_endpoint: Option<String>,
+
+    /// Token endpoint, if not discovered from the issuer
+    #[serde(default)]
+    pub token_endpoint: Option<String>,
+}
+
+/// Credential store configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialStoreConfig {
+    /// Backend holding stored secrets (keyring, file, env, memory)
+    #[serde(default = "default_store_backend")]
+    pub backend: String,
+
+    /// Path of the encrypted credentials file (file backend)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Environment variable holding the file passphrase (file backend)
+    #[serde(default = "default_store_passphrase_env")]
+    pub passphrase_env: String,
+
+    /// Prefix of the environment variables holding secrets (env backend)
+    #[serde(default = "default_store_env_prefix")]
+    pub env_prefix: String,
 }
 
 /// UI configuration
@@ -86,6 +279,54 @@ pub struct UiConfig {
     pub auto_reconnect: bool,
 }
 
+/// Audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Whether to record connections and authentication attempts
+    #[serde(default = "default_audit_enabled")]
+    pub enabled: bool,
+
+    /// Path of the audit log (defaults to `audit.log` in the state directory)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Size in kilobytes after which the log is rotated
+    #[serde(default = "default_audit_max_size_kb")]
+    pub max_size_kb: u64,
+
+    /// Number of rotated logs to keep
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+/// Default value for version, of files written before it was added
+fn default_config_version() -> u32 {
+    1
+}
+
+/// Default value for methods
+fn default_auth_methods() -> Vec<String> {
+    ["publickey", "native", "psk", "password"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect()
+}
+
+/// Default value for store.backend
+fn default_store_backend() -> String {
+    "keyring".to_string()
+}
+
+/// Default value for store.passphrase_env
+fn default_store_passphrase_env() -> String {
+    "RCP_STORE_PASSPHRASE".to_string()
+}
+
+/// Default value for store.env_prefix
+fn default_store_env_prefix() -> String {
+    "RCP_SECRET_".to_string()
+}
+
 /// Default value for auto_connect
 fn default_auto_connect() -> bool {
     false
@@ -96,6 +337,21 @@ fn default_auto_reconnect() -> bool {
     false
 }
 
+/// Default value for audit.enabled
+fn default_audit_enabled() -> bool {
+    true
+}
+
+/// Default value for audit.max_size_kb
+fn default_audit_max_size_kb() -> u64 {
+    1024
+}
+
+/// Default value for audit.max_files
+fn default_audit_max_files() -> usize {
+    5
+}
+
 /// Load configuration from a file
 pub async fn load_config<P: AsRef<Path>>(path: P) -> Result<ClientConfig> {
     // If the file doesn't exist, create it with default values
@@ -105,27 +361,21 @@ pub async fn load_config<P: AsRef<Path>>(path: P) -> Result<ClientConfig> {
         return Ok(default_config);
     }
 
-    // Read and parse the config file
-    let content = fs::read_to_string(&path)
-        .await
-        .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
-
-    toml::from_str(&content)
+    // Read, upgrade and parse the config file, or its backup if it is damaged
+    let table = migrate::read_config_table(path.as_ref(), true, true).await?;
+    toml::Value::Table(table)
+        .try_into()
         .with_context(|| format!("Failed to parse config file: {:?}", path.as_ref()))
 }
 
 /// Save configuration to a file
 pub async fn save_config<P: AsRef<Path>>(path: P, config: &ClientConfig) -> Result<()> {
-    // Create parent directories if they don't exist
-    if let Some(parent) = path.as_ref().parent() {
-        fs::create_dir_all(parent)
-            .await
-            .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
-    }
-
-    let content = toml::to_string_pretty(config).with_context(|| "Failed to serialize config")?;
+    // Save the top-level sections, not the ones a profile was applied to
+    let content = toml::to_string_pretty(&config.without_profile())
+        .with_context(|| "Failed to serialize config")?;
 
-    fs::write(&path, content)
+    // Replace the file in one step, so other instances never read half of it
+    storage::write_locked(path.as_ref().to_path_buf(), content.into_bytes())
         .await
         .with_context(|| format!("Failed to write config file: {:?}", path.as_ref()))
 }
@@ -133,9 +383,15 @@ pub async fn save_config<P: AsRef<Path>>(path: P, config: &ClientConfig) -> Resu
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             server: ServerConfig::default(),
             auth: AuthConfig::default(),
             ui: UiConfig::default(),
+            audit: AuditConfig::default(),
+            default_profile: None,
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            unprofiled: None,
         }
     }
 }
@@ -143,12 +399,15 @@ impl Default for ClientConfig {
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            address: "127.0.0.1".to_string(),
-            port: 8717,
+            address: defaults::DEFAULT_SERVER_ADDRESS.to_string(),
+            port: defaults::DEFAULT_SERVER_PORT,
             use_tls: false,
             client_cert_path: None,
             client_key_path: None,
             verify_server: true,
+            known_servers_path: None,
+            accept_server_key: false,
+            socket_path: None,
         }
     }
 }
@@ -156,11 +415,39 @@ impl Default for ServerConfig {
 impl Default for AuthConfig {
     fn default() -> Self {
         Self {
-            method: "password".to_string(),
+            method: defaults::DEFAULT_AUTH_METHOD.to_string(),
+            methods: default_auth_methods(),
             username: None,
             psk: None,
             save_credentials: false,
-            use_native_auth: false,
+            totp_from_keyring: false,
+            credential_helper: None,
+            persist_session: false,
+            native_helper_socket: None,
+            store: CredentialStoreConfig::default(),
+            oauth: None,
+        }
+    }
+}
+
+impl Default for CredentialStoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_store_backend(),
+            path: None,
+            passphrase_env: default_store_passphrase_env(),
+            env_prefix: default_store_env_prefix(),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_audit_enabled(),
+            path: None,
+            max_size_kb: default_audit_max_size_kb(),
+            max_files: default_audit_max_files(),
         }
     }
 }
@@ -170,7 +457,7 @@ impl Default for UiConfig {
         Self {
             dark_mode: true,
             start_minimized: false,
-            scale_factor: 1.0,
+            scale_factor: defaults::DEFAULT_UI_SCALE,
             theme: None,
             auto_connect: false, // Changed to false to disable auto-connect by default
             auto_reconnect: false,
@@ -0,0 +1,147 @@
+//! Where the client keeps its files
+//!
+//! Files are split by kind, following the XDG base directory specification on
+//! Linux and the platform conventions elsewhere:
+//!
+//! | Kind | Files | Default (Linux) | Portable |
+//! |------|-------|-----------------|----------|
+//! | config | `config.toml` | `$XDG_CONFIG_HOME/rcp_client` | `<dir>` |
+//! | data | `known_servers`, `credentials.age`, `resources` | `$XDG_DATA_HOME/rcp_client` | `<dir>/data` |
+//! | state | `connection_history.json`, `audit.log`, `shell_history` | `$XDG_STATE_HOME/rcp_client` | `<dir>/state` |
+//! | cache | files that can be rebuilt | `$XDG_CACHE_HOME/rcp_client` | `<dir>/cache` |
+//!
+//! A portable install keeps everything in one directory, given with
+//! `--data-dir` or `RCP_HOME`. Without a home directory, as in minimal
+//! containers, the files are kept in `rcp_client` in the working directory.
+//!
+//! Earlier versions kept every file in the config directory; such files are
+//! moved to their new place the first time they are used.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Once, OnceLock};
+
+/// Environment variable naming the directory of a portable install
+pub const HOME_ENV: &str = "RCP_HOME";
+
+/// Name of the client's directory in each platform directory
+const APP_DIR: &str = "rcp_client";
+
+/// Directory given with `--data-dir`
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep every file in `dir`, as `--data-dir` does
+///
+/// Takes precedence over `RCP_HOME`. Only the first call has an effect.
+pub fn set_data_dir(dir: PathBuf) {
+    if DATA_DIR.set(dir).is_err() {
+        log::warn!("Data directory is already set");
+    }
+}
+
+/// Directory of a portable install, if one is set
+pub fn portable_dir() -> Option<PathBuf> {
+    DATA_DIR.get().cloned().or_else(|| {
+        std::env::var_os(HOME_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Directory of the user config file
+pub fn config_dir() -> PathBuf {
+    match portable_dir() {
+        Some(dir) => dir,
+        None => platform_dir(dirs::config_dir()),
+    }
+}
+
+/// Directory of files the user would back up, such as pinned server keys
+pub fn data_dir() -> PathBuf {
+    match portable_dir() {
+        Some(dir) => dir.join("data"),
+        None => platform_dir(dirs::data_dir()),
+    }
+}
+
+/// Directory of files the client keeps between runs, such as history and logs
+///
+/// Platforms without a state directory use the data directory.
+pub fn state_dir() -> PathBuf {
+    match portable_dir() {
+        Some(dir) => dir.join("state"),
+        None => platform_dir(dirs::state_dir().or_else(dirs::data_dir)),
+    }
+}
+
+/// Directory of files that can be deleted and rebuilt
+pub fn cache_dir() -> PathBuf {
+    match portable_dir() {
+        Some(dir) => dir.join("cache"),
+        None => platform_dir(dirs::cache_dir()),
+    }
+}
+
+/// Path of a file in `dir`, moving it there from the config directory if an
+/// earlier version left it there
+///
+/// Files written next to it, such as `<name>.bak` or rotated logs, are moved
+/// too. If the file cannot be moved, it is used where it is.
+pub fn moved_file(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    let legacy_dir = config_dir();
+    let legacy = legacy_dir.join(name);
+    if portable_dir().is_some() || path.exists() || legacy == path || !legacy.exists() {
+        return path;
+    }
+
+    match fs::create_dir_all(dir).and_then(|_| fs::rename(&legacy, &path)) {
+        Ok(()) => {
+            log::info!("Moved {:?} to {:?}", legacy, path);
+            move_siblings(&legacy_dir, dir, name);
+            path
+        }
+        // Another instance may have moved it first
+        Err(_) if path.exists() => path,
+        Err(e) => {
+            log::warn!("Failed to move {:?} to {:?}: {}", legacy, path, e);
+            legacy
+        }
+    }
+}
+
+/// Move `<name>.*` files other than lock files
+fn move_siblings(from: &Path, to: &Path, name: &str) {
+    let prefix = format!("{}.", name);
+    let Ok(entries) = fs::read_dir(from) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with(&prefix) && !file_name.ends_with(".lock") {
+            if let Err(e) = fs::rename(entry.path(), to.join(file_name.as_ref())) {
+                log::warn!("Failed to move {:?} to {:?}: {}", entry.path(), to, e);
+            }
+        }
+    }
+}
+
+/// The client's directory in a platform directory, or in the working
+/// directory if there is no home directory
+fn platform_dir(base: Option<PathBuf>) -> PathBuf {
+    match base {
+        Some(base) => base.join(APP_DIR),
+        None => {
+            static WARNING: Once = Once::new();
+            WARNING.call_once(|| {
+                log::warn!(
+                    "No home directory found; keeping files in ./{} (set {} or --data-dir to choose a directory)",
+                    APP_DIR,
+                    HOME_ENV
+                );
+            });
+            PathBuf::from(APP_DIR)
+        }
+    }
+}
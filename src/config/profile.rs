@@ -0,0 +1,194 @@
+use crate::auth::SecretRef;
+use crate::config::{AuthConfig, CredentialStoreConfig, OAuthConfig, ServerConfig};
+use serde::{Deserialize, Serialize};
+
+/// Named set of overrides for the server and auth sections
+///
+/// Each field that is set replaces the value from the top-level `[server]` or
+/// `[auth]` section; unset fields keep it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Description shown in the profile picker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Server and TLS overrides
+    #[serde(default)]
+    pub server: ServerOverrides,
+
+    /// Authentication overrides
+    #[serde(default)]
+    pub auth: AuthOverrides,
+}
+
+impl Profile {
+    /// Apply the overrides to the given sections
+    pub fn apply(&self, server: &mut ServerConfig, auth: &mut AuthConfig) {
+        self.server.apply(server);
+        self.auth.apply(auth);
+    }
+}
+
+/// Overrides for the `[server]` section, including TLS settings
+///
+/// Setting `address` or `port` clears a `socket_path` inherited from
+/// `[server]`, unless the profile sets `socket_path` too.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerOverrides {
+    /// Server address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// Server port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// Unix socket of a local server, used instead of `address` and `port`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_path: Option<String>,
+
+    /// Whether to use TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_tls: Option<bool>,
+
+    /// Whether to pin the server identity in the known servers file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_server: Option<bool>,
+
+    /// Path to client certificate for mutual TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert_path: Option<String>,
+
+    /// Path to client key for mutual TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key_path: Option<String>,
+
+    /// Path to the known servers file holding pinned server identities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub known_servers_path: Option<String>,
+}
+
+impl ServerOverrides {
+    /// Replace the fields of `server` that are set here
+    pub fn apply(&self, server: &mut ServerConfig) {
+        if let Some(address) = &self.address {
+            server.address = address.clone();
+        }
+        if let Some(port) = self.port {
+            server.port = port;
+        }
+        // An inherited socket would be used instead of the address set here
+        if self.address.is_some() || self.port.is_some() {
+            server.socket_path = None;
+        }
+        if let Some(socket_path) = &self.socket_path {
+            server.socket_path = Some(socket_path.clone());
+        }
+        if let Some(use_tls) = self.use_tls {
+            server.use_tls = use_tls;
+        }
+        if let Some(verify_server) = self.verify_server {
+            server.verify_server = verify_server;
+        }
+        if let Some(path) = &self.client_cert_path {
+            server.client_cert_path = Some(path.clone());
+        }
+        if let Some(path) = &self.client_key_path {
+            server.client_key_path = Some(path.clone());
+        }
+        if let Some(path) = &self.known_servers_path {
+            server.known_servers_path = Some(path.clone());
+        }
+    }
+}
+
+/// Overrides for the `[auth]` section
+///
+/// `store` and `oauth` replace the whole table when set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthOverrides {
+    /// Authentication method (auto, password, psk, native, oauth2)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+
+    /// Methods to try in order of preference when `method` is `auto`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+
+    /// Username for authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Pre-shared key, or a reference such as `env:RCP_PSK`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psk: Option<SecretRef>,
+
+    /// Whether to save credentials
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_credentials: Option<bool>,
+
+    /// Whether to generate TOTP codes from a secret in the credential store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_from_keyring: Option<bool>,
+
+    /// Command to get, store and erase secrets, like a git credential helper
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_helper: Option<String>,
+
+    /// Whether to keep server-issued session tokens in the credential store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persist_session: Option<bool>,
+
+    /// Socket of the local daemon that signs native auth assertions for remote servers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub native_helper_socket: Option<String>,
+
+    /// Where stored secrets are kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<CredentialStoreConfig>,
+
+    /// Identity provider for the OAuth2 device flow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthConfig>,
+}
+
+impl AuthOverrides {
+    /// Replace the fields of `auth` that are set here
+    pub fn apply(&self, auth: &mut AuthConfig) {
+        if let Some(method) = &self.method {
+            auth.method = method.clone();
+        }
+        if let Some(methods) = &self.methods {
+            auth.methods = methods.clone();
+        }
+        if let Some(username) = &self.username {
+            auth.username = Some(username.clone());
+        }
+        if let Some(psk) = &self.psk {
+            auth.psk = Some(psk.clone());
+        }
+        if let Some(save_credentials) = self.save_credentials {
+            auth.save_credentials = save_credentials;
+        }
+        if let Some(totp_from_keyring) = self.totp_from_keyring {
+            auth.totp_from_keyring = totp_from_keyring;
+        }
+        if let Some(helper) = &self.credential_helper {
+            auth.credential_helper = Some(helper.clone());
+        }
+        if let Some(persist_session) = self.persist_session {
+            auth.persist_session = persist_session;
+        }
+        if let Some(socket) = &self.native_helper_socket {
+            auth.native_helper_socket = Some(socket.clone());
+        }
+        if let Some(store) = &self.store {
+            auth.store = store.clone();
+        }
+        if let Some(oauth) = &self.oauth {
+            auth.oauth = Some(oauth.clone());
+        }
+    }
+}
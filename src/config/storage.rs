@@ -0,0 +1,142 @@
+//! Crash-safe writes of config and state files
+//!
+//! Files are written to a temporary file next to the target and renamed over
+//! it, so a reader sees either the old or the new content, never a partial
+//! file. Writers hold an advisory lock on `<file>.lock` so that several client
+//! instances do not interleave their read-modify-write cycles. Each write also
+//! leaves a copy of the new content in `<file>.bak`, which is read instead
+//! when the file itself turns out to be damaged.
+
+use anyhow::{Context, Result};
+use fs4::fs_std::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Exclusive advisory lock on a file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+/// Lock `<path>.lock`, waiting for other instances to release it
+///
+/// The lock is advisory: it only keeps out writers that also take it.
+pub fn lock_file(path: &Path) -> Result<FileLock> {
+    let lock_path = sibling_path(path, "lock");
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file: {:?}", lock_path))?;
+    file.lock_exclusive()
+        .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+    Ok(FileLock { _file: file })
+}
+
+/// Path of the copy of the last content written to `path`
+pub fn bak_path(path: &Path) -> PathBuf {
+    sibling_path(path, "bak")
+}
+
+/// Replace the content of a file without ever leaving it partly written
+///
+/// The caller should hold the [`lock_file`] lock when the new content depends
+/// on what was read before. The content is also copied to [`bak_path`].
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+
+    replace(path, content, path).with_context(|| format!("Failed to write {:?}", path))?;
+
+    // The backup is only a fallback, so failing to update it is not an error.
+    // It holds the same content, so it gets the same permissions.
+    let backup = bak_path(path);
+    if let Err(e) = write_copy(&backup, content, path) {
+        log::warn!("Failed to update backup {:?}: {}", backup, e);
+    }
+    Ok(())
+}
+
+/// Lock a file and replace its content, without blocking the async runtime
+pub async fn write_locked(path: PathBuf, content: Vec<u8>) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let _lock = lock_file(&path)?;
+        write_atomic(&path, &content)
+    })
+    .await
+    .context("File writer stopped")?
+}
+
+/// Parse the backup of a file that could not be parsed itself
+///
+/// Returns `None` if there is no backup or it cannot be parsed either.
+pub fn recover_from_backup<T, E: std::fmt::Display>(
+    path: &Path,
+    parse: impl Fn(&str) -> std::result::Result<T, E>,
+) -> Option<(String, T)> {
+    let backup = bak_path(path);
+    let content = fs::read_to_string(&backup).ok()?;
+    match parse(&content) {
+        Ok(value) => {
+            log::warn!(
+                "{:?} is damaged; using the copy last written to {:?}",
+                path,
+                backup
+            );
+            Some((content, value))
+        }
+        Err(e) => {
+            log::error!("Backup {:?} cannot be used either: {}", backup, e);
+            None
+        }
+    }
+}
+
+/// Write a copy of `original`'s content to `path`, with the permissions of `original`
+///
+/// Used for backups, which must not be easier to read than the file itself.
+pub(crate) fn write_copy(path: &Path, content: &[u8], original: &Path) -> std::io::Result<()> {
+    replace(path, content, original)
+}
+
+/// Write to a temporary file next to `path`, flush it to disk and rename it over `path`
+///
+/// The new file gets the permissions of `permissions_of`, if it exists.
+fn replace(path: &Path, content: &[u8], permissions_of: &Path) -> std::io::Result<()> {
+    let tmp_path = sibling_path(path, &format!("{}.tmp", std::process::id()));
+    let result = File::create(&tmp_path)
+        .and_then(|mut file| {
+            copy_permissions(permissions_of, &file)?;
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Give `file` the permissions of the file at `path`, such as a private config
+fn copy_permissions(path: &Path, file: &File) -> std::io::Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) => file.set_permissions(metadata.permissions()),
+        Err(_) => Ok(()),
+    }
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(".");
+    sibling.push(extension);
+    PathBuf::from(sibling)
+}
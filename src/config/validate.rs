@@ -0,0 +1,445 @@
+//! Configuration validation
+//!
+//! [`ClientConfig::validate`] checks the settings that deserialize fine but
+//! would only fail, or be silently ignored, when connecting. Every problem is
+//! reported with the dotted key it concerns and, where possible, how to fix it.
+
+use crate::auth::negotiate::AUTO_METHOD;
+use crate::auth::store::StoreBackend;
+use crate::auth::{AuthMethod, SecretRef};
+use crate::config::defaults::DEFAULT_SERVER_PORT;
+use crate::config::{AuthOverrides, ClientConfig, ServerOverrides};
+use std::fmt;
+use std::path::Path;
+
+/// Authentication method names accepted in `auth.method` and `auth.methods`
+const METHOD_NAMES: &[&str] = &["password", "psk", "native", "publickey", "oauth2"];
+
+/// Credential store backend names accepted in `auth.store.backend`
+const BACKEND_NAMES: &[&str] = &["keyring", "file", "env", "memory"];
+
+/// How serious a configuration problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting cannot work and must be fixed
+    Error,
+
+    /// The setting works but is probably not what was intended
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// One problem found in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// How serious the problem is
+    pub severity: Severity,
+
+    /// Dotted key of the setting, such as `server.port`
+    pub key: String,
+
+    /// What is wrong
+    pub message: String,
+
+    /// How to fix it
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.key, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// All problems found in a configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Problems in the order they were found
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the configuration has no errors; warnings are allowed
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Problems that must be fixed
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    /// Problems that are probably mistakes
+    pub fn warnings(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// Problem reported for the given key, if any
+    pub fn issue(&self, key: &str) -> Option<&ConfigIssue> {
+        self.issues.iter().find(|issue| issue.key == key)
+    }
+
+    fn error(&mut self, key: impl Into<String>, message: impl Into<String>) -> &mut ConfigIssue {
+        self.push(Severity::Error, key.into(), message.into())
+    }
+
+    fn warning(&mut self, key: impl Into<String>, message: impl Into<String>) -> &mut ConfigIssue {
+        self.push(Severity::Warning, key.into(), message.into())
+    }
+
+    fn push(&mut self, severity: Severity, key: String, message: String) -> &mut ConfigIssue {
+        self.issues.push(ConfigIssue {
+            severity,
+            key,
+            message,
+            suggestion: None,
+        });
+        self.issues.last_mut().expect("issue was just pushed")
+    }
+}
+
+impl ConfigIssue {
+    fn suggest(&mut self, suggestion: impl Into<String>) {
+        self.suggestion = Some(suggestion.into());
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl ClientConfig {
+    /// Check the configuration for settings that cannot work
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        self.validate_server(&mut report);
+        self.validate_auth(&mut report);
+        self.validate_ui(&mut report);
+        self.validate_audit(&mut report);
+        self.validate_profiles(&mut report);
+        report
+    }
+
+    fn validate_server(&self, report: &mut ValidationReport) {
+        let server = &self.server;
+
+        if server.socket_path.is_none() {
+            check_address(report, "server.address", &server.address);
+        }
+        check_port(report, "server.port", server.port);
+
+        if let Some(path) = &server.socket_path {
+            if cfg!(not(unix)) {
+                report
+                    .error(
+                        "server.socket_path",
+                        "Unix sockets are not supported on this platform",
+                    )
+                    .suggest("remove server.socket_path and set server.address instead");
+            } else if path.is_empty() {
+                report
+                    .error("server.socket_path", "socket path is empty")
+                    .suggest("remove server.socket_path to connect over TCP");
+            }
+        }
+
+        check_file(
+            report,
+            "server.client_cert_path",
+            server.client_cert_path.as_deref(),
+        );
+        check_file(
+            report,
+            "server.client_key_path",
+            server.client_key_path.as_deref(),
+        );
+        match (&server.client_cert_path, &server.client_key_path) {
+            (Some(_), None) => {
+                report
+                    .error("server.client_key_path", "client certificate has no key")
+                    .suggest("set server.client_key_path to the certificate's private key");
+            }
+            (None, Some(_)) => {
+                report
+                    .error("server.client_cert_path", "client key has no certificate")
+                    .suggest("set server.client_cert_path to the client certificate");
+            }
+            _ => {}
+        }
+
+        if !server.use_tls {
+            for (key, value) in [
+                ("server.client_cert_path", &server.client_cert_path),
+                ("server.client_key_path", &server.client_key_path),
+            ] {
+                if value.is_some() {
+                    report
+                        .warning(key, "ignored because TLS is disabled")
+                        .suggest("set server.use_tls = true to use the client certificate");
+                }
+            }
+        }
+    }
+
+    fn validate_auth(&self, report: &mut ValidationReport) {
+        let auth = &self.auth;
+
+        if auth.method != AUTO_METHOD {
+            check_method(report, "auth.method", &auth.method, true);
+        }
+        for (index, method) in auth.methods.iter().enumerate() {
+            check_method(report, &format!("auth.methods[{}]", index), method, false);
+        }
+        if auth.method == AUTO_METHOD && auth.methods.is_empty() {
+            report
+                .error("auth.methods", "no methods to negotiate")
+                .suggest(format!("list methods to try, such as {:?}", METHOD_NAMES));
+        }
+
+        if AuthMethod::from_str(&auth.method) == Some(AuthMethod::OAuth2) && auth.oauth.is_none() {
+            report
+                .error("auth.oauth", "the oauth2 method needs an identity provider")
+                .suggest("add an [auth.oauth] table with issuer_url and client_id");
+        }
+        if let Some(oauth) = &auth.oauth {
+            if oauth.issuer_url.is_empty() {
+                report.error("auth.oauth.issuer_url", "issuer URL is empty");
+            }
+            if oauth.client_id.is_empty() {
+                report.error("auth.oauth.client_id", "client ID is empty");
+            }
+        }
+
+        if let Some(psk) = &auth.psk {
+            check_secret(report, "auth.psk", psk);
+        }
+
+        if let Some(username) = &auth.username {
+            if username.trim().is_empty() {
+                report
+                    .warning("auth.username", "username is empty")
+                    .suggest("remove auth.username to use the current OS user");
+            }
+        }
+
+        if StoreBackend::from_name(&auth.store.backend).is_none() {
+            let issue = report.error(
+                "auth.store.backend",
+                format!("unknown credential store {:?}", auth.store.backend),
+            );
+            issue.suggest(suggest_name(&auth.store.backend, BACKEND_NAMES));
+        }
+        if auth.store.backend.eq_ignore_ascii_case("memory") && auth.save_credentials {
+            report
+                .warning(
+                    "auth.store.backend",
+                    "saved credentials are lost when the client exits",
+                )
+                .suggest("use the keyring or file backend to keep saved credentials");
+        }
+    }
+
+    fn validate_ui(&self, report: &mut ValidationReport) {
+        let scale = self.ui.scale_factor;
+        if scale.is_nan() || scale <= 0.0 || scale > 4.0 {
+            report
+                .error(
+                    "ui.scale_factor",
+                    format!("scale factor {} is out of range", scale),
+                )
+                .suggest("use a value above 0 and at most 4.0, such as 1.0");
+        }
+    }
+
+    fn validate_audit(&self, report: &mut ValidationReport) {
+        if self.audit.enabled && self.audit.max_size_kb == 0 {
+            report
+                .error("audit.max_size_kb", "the audit log cannot hold any events")
+                .suggest("set a size such as 1024, or audit.enabled = false");
+        }
+    }
+
+    fn validate_profiles(&self, report: &mut ValidationReport) {
+        if let Some(name) = &self.default_profile {
+            if !self.profiles.contains_key(name) {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                let issue = report.error("default_profile", format!("unknown profile {:?}", name));
+                if names.is_empty() {
+                    issue.suggest("add a [profiles.<name>] table or remove default_profile");
+                } else {
+                    issue.suggest(suggest_name(name, &names));
+                }
+            }
+        }
+
+        for (name, profile) in &self.profiles {
+            let prefix = format!("profiles.{}", name);
+            check_server_overrides(report, &format!("{}.server", prefix), &profile.server);
+            check_auth_overrides(report, &format!("{}.auth", prefix), &profile.auth);
+        }
+    }
+}
+
+fn check_server_overrides(report: &mut ValidationReport, prefix: &str, server: &ServerOverrides) {
+    if let Some(address) = &server.address {
+        check_address(report, &format!("{}.address", prefix), address);
+    }
+    if let Some(port) = server.port {
+        check_port(report, &format!("{}.port", prefix), port);
+    }
+    check_file(
+        report,
+        &format!("{}.client_cert_path", prefix),
+        server.client_cert_path.as_deref(),
+    );
+    check_file(
+        report,
+        &format!("{}.client_key_path", prefix),
+        server.client_key_path.as_deref(),
+    );
+}
+
+fn check_auth_overrides(report: &mut ValidationReport, prefix: &str, auth: &AuthOverrides) {
+    if let Some(method) = &auth.method {
+        if method != AUTO_METHOD {
+            check_method(report, &format!("{}.method", prefix), method, true);
+        }
+    }
+    for (index, method) in auth.methods.iter().flatten().enumerate() {
+        check_method(
+            report,
+            &format!("{}.methods[{}]", prefix, index),
+            method,
+            false,
+        );
+    }
+    if let Some(psk) = &auth.psk {
+        check_secret(report, &format!("{}.psk", prefix), psk);
+    }
+}
+
+fn check_address(report: &mut ValidationReport, key: &str, address: &str) {
+    if address.trim().is_empty() {
+        report
+            .error(key, "server address is empty")
+            .suggest("set a hostname or IP address, such as 127.0.0.1");
+    } else if address.contains(char::is_whitespace) {
+        report
+            .error(key, format!("server address {:?} contains spaces", address))
+            .suggest("remove the spaces");
+    } else if address.contains("://") {
+        report
+            .error(
+                key,
+                format!("server address {:?} includes a scheme", address),
+            )
+            .suggest("give only the hostname, and set the port and use_tls separately");
+    }
+}
+
+fn check_port(report: &mut ValidationReport, key: &str, port: u16) {
+    if port == 0 {
+        report.error(key, "port 0 is not valid").suggest(format!(
+            "use a port between 1 and 65535; the default is {}",
+            DEFAULT_SERVER_PORT
+        ));
+    }
+}
+
+fn check_method(report: &mut ValidationReport, key: &str, method: &str, allow_auto: bool) {
+    if AuthMethod::from_str(method).is_some() {
+        return;
+    }
+
+    let mut names = METHOD_NAMES.to_vec();
+    if allow_auto {
+        names.insert(0, AUTO_METHOD);
+    }
+    let issue = report.error(key, format!("unknown authentication method {:?}", method));
+    issue.suggest(suggest_name(method, &names));
+}
+
+fn check_file(report: &mut ValidationReport, key: &str, path: Option<&str>) {
+    if let Some(path) = path {
+        if !Path::new(path).is_file() {
+            report
+                .error(key, format!("file {} does not exist", path))
+                .suggest("check the path, or remove the setting");
+        }
+    }
+}
+
+fn check_secret(report: &mut ValidationReport, key: &str, secret: &SecretRef) {
+    match secret {
+        SecretRef::Value(_) => {
+            report
+                .warning(key, "secret is stored in the config file")
+                .suggest(
+                    "refer to it instead, such as \"env:RCP_PSK\" or \"keyring:rcp-client/psk\"",
+                );
+        }
+        SecretRef::Env(var) if std::env::var_os(var).is_none() => {
+            report
+                .warning(key, format!("environment variable {} is not set", var))
+                .suggest(format!("set {} before connecting", var));
+        }
+        SecretRef::File(path) if !path.is_file() => {
+            report
+                .error(key, format!("file {} does not exist", path.display()))
+                .suggest("check the path, or refer to the secret another way");
+        }
+        _ => {}
+    }
+}
+
+/// Suggest the closest known name, or list all of them
+pub(crate) fn suggest_name(value: &str, names: &[&str]) -> String {
+    let value = value.to_lowercase();
+    let closest = names
+        .iter()
+        .map(|name| (edit_distance(&value, name), name))
+        .min()
+        .filter(|(distance, _)| *distance <= 2);
+
+    match closest {
+        Some((_, name)) => format!("did you mean {:?}?", name),
+        None => format!("expected one of: {}", names.join(", ")),
+    }
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,180 @@
+//! Config file hot reload
+//!
+//! [`ConfigWatcher`] watches the files read by [`ConfigLayers::load`] and
+//! resolves the configuration again whenever one of them changes. A changed
+//! configuration that does not parse or fails validation is rejected, and the
+//! last good one stays in use.
+
+use crate::config::{ClientConfig, ConfigLayers};
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use toml::Value;
+
+/// How long to wait for more changes before reloading, as editors often write in steps
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Result of reloading the configuration after a file changed
+#[derive(Debug, Clone)]
+pub enum ConfigUpdate {
+    /// The new configuration is valid and replaces the old one
+    Applied {
+        /// The new configuration
+        config: Box<ClientConfig>,
+
+        /// Top-level sections that changed, such as `server` or `ui`
+        changed: Vec<String>,
+    },
+
+    /// The new configuration is invalid; the last good one stays in use
+    Rejected(String),
+}
+
+impl ConfigUpdate {
+    /// Whether the connection settings changed, so they apply on the next connect
+    pub fn changes_connection(&self) -> bool {
+        match self {
+            ConfigUpdate::Applied { changed, .. } => changed
+                .iter()
+                .any(|section| matches!(section.as_str(), "server" | "auth" | "profiles")),
+            ConfigUpdate::Rejected(_) => false,
+        }
+    }
+}
+
+/// Watches the config files and reports each reload
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    /// Watch the files the layers were loaded from
+    ///
+    /// Must be called from within a Tokio runtime. Updates are sent on the
+    /// returned channel until the watcher is dropped.
+    pub fn spawn(layers: ConfigLayers) -> Result<(Self, mpsc::Receiver<ConfigUpdate>)> {
+        let files: BTreeSet<PathBuf> = layers.files().iter().map(|f| absolute(f)).collect();
+        let (changed_tx, mut changed_rx) = mpsc::unbounded_channel();
+
+        let watched = files.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Config watcher error: {}", e);
+                    return;
+                }
+            };
+            let relevant = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) && event.paths.iter().any(|path| watched.contains(path));
+            if relevant {
+                let _ = changed_tx.send(());
+            }
+        })
+        .context("Failed to create config watcher")?;
+
+        // Watch the directories, as files are often replaced rather than edited
+        let dirs: BTreeSet<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+        for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Failed to watch {}", dir.display()))?;
+            log::debug!("Watching {} for config changes", dir.display());
+        }
+
+        let (update_tx, update_rx) = mpsc::channel(8);
+        let task = tokio::spawn(async move {
+            let mut current = layers.resolve().ok();
+
+            while changed_rx.recv().await.is_some() {
+                tokio::time::sleep(DEBOUNCE).await;
+                while changed_rx.try_recv().is_ok() {}
+
+                let update = reload(&layers, current.as_ref()).await;
+                match &update {
+                    ConfigUpdate::Applied { config, changed } => {
+                        if changed.is_empty() {
+                            continue;
+                        }
+                        log::info!("Configuration reloaded, changed: {}", changed.join(", "));
+                        current = Some((**config).clone());
+                    }
+                    ConfigUpdate::Rejected(reason) => {
+                        log::warn!("Rejected configuration change: {}", reason);
+                    }
+                }
+                if update_tx.send(update).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok((
+            Self {
+                _watcher: watcher,
+                task,
+            },
+            update_rx,
+        ))
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Resolve the configuration again and compare it with the current one
+pub async fn reload(layers: &ConfigLayers, current: Option<&ClientConfig>) -> ConfigUpdate {
+    let config = match layers.reload().await.and_then(|layers| layers.resolve()) {
+        Ok(config) => config,
+        Err(e) => return ConfigUpdate::Rejected(format!("{:#}", e)),
+    };
+
+    let report = config.validate();
+    if !report.is_ok() {
+        return ConfigUpdate::Rejected(report.to_string().trim_end().to_string());
+    }
+
+    let changed = match current {
+        Some(current) => changed_sections(current, &config),
+        None => vec!["server".to_string(), "auth".to_string(), "ui".to_string()],
+    };
+    ConfigUpdate::Applied {
+        config: Box::new(config),
+        changed,
+    }
+}
+
+/// Top-level sections whose values differ between two configurations
+pub fn changed_sections(old: &ClientConfig, new: &ClientConfig) -> Vec<String> {
+    let (Ok(Value::Table(old)), Ok(Value::Table(new))) =
+        (Value::try_from(old), Value::try_from(new))
+    else {
+        return Vec::new();
+    };
+
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
@@ -4,7 +4,9 @@
 //! It provides functionality for authenticating with an RCP server,
 //! communicating using the RCP protocol, and displaying a UI.
 
+pub mod audit;
 pub mod auth;
+pub mod cli;
 pub mod config;
 pub mod protocol;
 pub mod resources;
@@ -12,6 +14,7 @@ pub mod ui;
 
 use anyhow::Result;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Initialize the RCP client with the given configuration file path
 /// If the path doesn't exist, a default configuration will be created
@@ -20,40 +23,54 @@ pub async fn init_with_config<P: AsRef<Path>>(config_path: P) -> Result<config::
 }
 
 /// Connect to an RCP server with the given configuration
+///
+/// For TLS connections, the server identity is pinned on first use; a changed
+/// identity is an error unless `server.accept_server_key` is set. The attempt
+/// is recorded in the audit log.
 pub async fn connect(config: &config::ClientConfig) -> Result<protocol::Client> {
-    protocol::Client::connect(&config.server.address, config.server.port).await
+    let result = protocol::Client::connect_with_config(&config.server, None).await;
+    audit::record_connection(config, audit::AuditEvent::Connect, &result);
+    result
+}
+
+/// Close the connection to the RCP server and record it in the audit log
+pub async fn disconnect(client: protocol::Client, config: &config::ClientConfig) -> Result<()> {
+    let result = client.close().await;
+    audit::record_connection(config, audit::AuditEvent::Disconnect, &result);
+    result
 }
 
 /// Authenticate with the RCP server
+///
+/// Missing secrets and second factor codes are read from the terminal
+/// (or generated from the credential store when `auth.totp_from_keyring` is set).
+/// Session tokens are only kept for resumption when `auth.persist_session` is
+/// set; use [`authenticate_with_options`] with a shared [`auth::SessionStore`]
+/// to resume sessions in memory.
 pub async fn authenticate(
     client: &protocol::Client,
     config: &config::ClientConfig,
+) -> Result<bool> {
+    let sessions = auth::SessionStore::from_config(&config.auth);
+    let options = auth::AuthOptions::from_config(config, Arc::new(auth::TerminalPrompter))
+        .with_sessions(Arc::new(sessions));
+    authenticate_with_options(client, config, options).await
+}
+
+/// Authenticate with the RCP server using the given provider options
+///
+/// A stored session token is presented first; the provider flow only runs if
+/// there is none or the server rejects it. With `auth.method = "auto"` the
+/// methods the server accepts are tried in the order of `auth.methods`.
+pub async fn authenticate_with_options(
+    client: &protocol::Client,
+    config: &config::ClientConfig,
+    options: auth::AuthOptions,
 ) -> Result<bool> {
     // Get username for authentication
-    let username = config.auth.username.clone().unwrap_or_else(|| {
-        // Try to get the current OS username
-        std::env::var("USER")
-            .or_else(|_| std::env::var("USERNAME"))
-            .unwrap_or_else(|_| "user".to_string())
-    });
-
-    // Determine authentication method
-    let auth_method = match auth::AuthMethod::from_str(&config.auth.method) {
-        Some(method) => method,
-        None => {
-            log::warn!(
-                "Unknown authentication method: {}, falling back to password",
-                config.auth.method
-            );
-            auth::AuthMethod::Password
-        }
-    };
-
-    // Authenticate
-    log::info!("Authenticating with method: {}", auth_method);
-    let auth_provider = auth::create_provider(auth_method, &username);
-
-    client.authenticate_with_provider(&*auth_provider).await
+    let username = config.auth.username_or_current();
+
+    auth::negotiate::authenticate(client, &config.auth, &username, &options).await
 }
 
 /// Start the RCP client UI
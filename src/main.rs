@@ -1,9 +1,11 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
 use log::{info, LevelFilter};
 use std::path::PathBuf;
 
+mod audit;
 mod auth;
+mod cli;
 mod config;
 mod protocol;
 mod resources;
@@ -13,14 +15,23 @@ mod ui;
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
 struct Args {
-    /// Path to the configuration file
+    /// Path to the user configuration file
     #[clap(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
 
+    /// Keep the configuration, history and other files in this directory
+    /// instead of the user's directories, for portable installs (or set RCP_HOME)
+    #[clap(long, value_name = "DIR")]
+    data_dir: Option<PathBuf>,
+
     /// Server address to connect to
     #[clap(short, long)]
     server: Option<String>,
 
+    /// Named profile from the configuration file to use
+    #[clap(short, long, value_name = "NAME")]
+    profile: Option<String>,
+
     /// Verbose mode (repeat for more verbosity)
     #[clap(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -29,27 +40,108 @@ struct Args {
     #[clap(short, long)]
     username: Option<String>,
 
-    /// Authentication method (password, psk, native)
-    #[clap(long, value_parser = ["password", "psk", "native"])]
+    /// Authentication method (auto, password, psk, native, oauth2)
+    #[clap(long, value_parser = ["auto", "password", "psk", "native", "oauth2"])]
     auth_method: Option<String>,
 
     /// Connect in background (don't force connection on startup)
     #[clap(long, action)]
     background_connect: bool,
 
-    /// Use event-based UI implementation
+    /// Never start the GUI; a subcommand such as exec or shell is then required
     #[clap(long, action)]
-    event_based: bool,
+    no_gui: bool,
 
-    /// Use simple text-based interface instead of GUI
+    /// Accept a changed server identity and update the pinned fingerprint
     #[clap(long, action)]
-    no_gui: bool,
+    accept_server_key: bool,
+
+    /// Output format of exec and shell results: raw, json, jsonl, yaml or table
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = cli::OutputFormat::Raw, global = true)]
+    output: cli::OutputFormat,
+
+    /// Print the effective configuration and where each value came from, then exit
+    #[clap(long, action)]
+    show_config: bool,
+
+    /// Connection link to open, such as rcp://alice@rcp.example.com:8717/?auth=psk
+    #[clap(value_name = "LINK")]
+    link: Option<config::ConnectionLink>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the configuration
+    Config {
+        #[clap(subcommand)]
+        action: ConfigCommand,
+    },
+
+    /// Run one command on the server, print the response and exit
+    Exec {
+        /// Command to run
+        command: String,
+
+        /// Command parameters as a JSON object, or - to read them from stdin
+        #[clap(long, value_name = "JSON")]
+        params: Option<String>,
+
+        /// Seconds to wait for the response
+        #[clap(long, value_name = "SECS", default_value_t = cli::DEFAULT_TIMEOUT_SECS)]
+        timeout: u64,
+    },
+
+    /// Run commands interactively, with line editing and history
+    Shell {
+        /// Seconds to wait for the response to each command
+        #[clap(long, value_name = "SECS", default_value_t = cli::DEFAULT_TIMEOUT_SECS)]
+        timeout: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Check the effective configuration and report every problem found
+    Check,
+
+    /// Print the effective value of a setting, such as server.port
+    Get { key: String },
+
+    /// Change a setting in the user configuration file
+    Set { key: String, value: String },
+
+    /// Remove a setting from the user configuration file
+    Unset { key: String },
+
+    /// List the settings in the user configuration file
+    List {
+        /// List every effective setting and where it came from instead
+        #[clap(long, action)]
+        effective: bool,
+    },
+
+    /// Print the path of the user configuration file
+    Path,
+
+    /// Open the user configuration file in $VISUAL or $EDITOR, then check it
+    Edit,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments
     let args = Args::parse();
+    if args.no_gui && args.command.is_none() && !args.show_config {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "--no-gui needs a subcommand to run, such as exec or shell",
+            )
+            .exit();
+    }
 
     // Configure logging based on verbosity level
     let log_level = match args.verbose {
@@ -65,38 +157,110 @@ async fn main() -> Result<()> {
 
     info!("Starting RCP client v{}", env!("CARGO_PKG_VERSION"));
 
-    // Load configuration
-    let config_path = args.config.unwrap_or_else(|| {
-        dirs::config_dir()
-            .expect("Could not find config directory")
-            .join("rcp_client")
-            .join("config.toml")
-    });
+    if let Some(dir) = &args.data_dir {
+        config::paths::set_data_dir(dir.clone());
+    }
+
+    // Load the system, user and project configs and RCP_* environment variables
+    let user_config = args.config.clone().unwrap_or_else(config::user_config_path);
+    let working_dir = std::env::current_dir().ok();
+    // A layer that cannot be read is an error rather than replaced by the
+    // defaults, which would silently drop every other setting too
+    let mut layers = config::ConfigLayers::load(args.config.as_deref(), working_dir.as_deref())
+        .await
+        .context("Failed to load configuration");
 
-    // Try to load configuration, but use defaults if it fails
-    let mut config = match config::load_config(&config_path).await {
-        Ok(config) => {
-            info!("Configuration loaded from {:?}", config_path);
-            config
+    // Command-line arguments override the profile and every config file,
+    // and the options given on their own override a connection link
+    if let Ok(layers) = &mut layers {
+        let link_profile = args.link.as_ref().and_then(|link| link.profile.as_deref());
+        layers.select_profile(args.profile.as_deref().or(link_profile));
+        if let Some(link) = &args.link {
+            for (key, value) in link.settings() {
+                layers.set_command_line(key, value);
+            }
         }
-        Err(e) => {
-            log::warn!("Failed to load configuration: {}", e);
-            log::info!("Using default configuration");
-            config::ClientConfig::default()
+        if let Some(server) = args.server {
+            layers.set_command_line("server.address", server);
+        }
+        if let Some(username) = args.username {
+            layers.set_command_line("auth.username", username);
+        }
+        if let Some(auth_method) = args.auth_method {
+            layers.set_command_line("auth.method", auth_method);
         }
+    }
+
+    if args.show_config {
+        let layers = layers.unwrap_or_else(|e| fail(args.output, cli::ExecError::Config(e)));
+        print_effective_config(&layers)?;
+        return Ok(());
+    }
+
+    let command = match args.command {
+        Some(Command::Config { action }) => {
+            let code = run_config_command(action, layers, &user_config).await?;
+            std::process::exit(code);
+        }
+        command => command,
     };
 
-    // Override configuration with command-line arguments
-    if let Some(server) = args.server {
-        config.server.address = server;
+    // Startup errors are printed like the errors of the command, so that
+    // scripts reading structured output see them too
+    let layers = layers.unwrap_or_else(|e| fail(args.output, cli::ExecError::Config(e)));
+    let mut config = layers
+        .resolve()
+        .unwrap_or_else(|e| fail(args.output, cli::ExecError::Config(e)));
+    if let Some(profile) = &config.active_profile {
+        info!("Using profile {}", profile);
     }
 
-    if let Some(username) = args.username {
-        config.auth.username = Some(username);
+    let report = config.validate();
+    for issue in report.warnings() {
+        log::warn!("{}", issue);
+    }
+    if !report.is_ok() {
+        fail(
+            args.output,
+            cli::ExecError::Config(anyhow::anyhow!("Invalid configuration:\n{}", report)),
+        );
     }
 
-    if let Some(auth_method) = args.auth_method {
-        config.auth.method = auth_method;
+    if args.accept_server_key {
+        config.server.accept_server_key = true;
+    }
+
+    // Run commands without starting the GUI
+    match command {
+        Some(Command::Exec {
+            command,
+            params,
+            timeout,
+        }) => {
+            let result = match cli::parse_params(params.as_deref()) {
+                Ok(params) => cli::exec(&config, &command, params, timeout).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(data) => {
+                    args.output
+                        .print(&cli::OutputRecord::Response { command, data });
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    args.output.print(&cli::OutputRecord::error(&e));
+                    std::process::exit(e.exit_code());
+                }
+            }
+        }
+        Some(Command::Shell { timeout }) => {
+            return ui::Shell::new(config)
+                .with_timeout(timeout)
+                .with_output(args.output)
+                .run()
+                .await;
+        }
+        _ => {}
     }
 
     // Disable auto-connect on startup
@@ -127,6 +291,15 @@ async fn main() -> Result<()> {
 
     let app_config = config.clone(); // Clone config for the app
 
+    // Apply edits to the config files while the client runs
+    let (_config_watcher, config_updates) = match config::ConfigWatcher::spawn(layers) {
+        Ok((watcher, updates)) => (Some(watcher), Some(updates)),
+        Err(e) => {
+            log::warn!("Configuration changes will not be reloaded: {:#}", e);
+            (None, None)
+        }
+    };
+
     // Spawn a task to gracefully shutdown the runtime when the GUI exits
     let _rt_handle_shutdown = rt.handle().clone();
     tokio::spawn(async move {
@@ -146,6 +319,8 @@ async fn main() -> Result<()> {
             Box::new(crate::ui::gui::RcpClientApp::new(
                 cc,
                 app_config,
+                user_config,
+                config_updates,
                 rt_handle,
                 shutdown_tx,
             ))
@@ -155,3 +330,157 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Run a `config` subcommand, returning the exit code
+///
+/// Commands that only change the user config file also work when the
+/// configuration cannot be loaded, so that it can be repaired.
+async fn run_config_command(
+    action: ConfigCommand,
+    layers: Result<config::ConfigLayers>,
+    user_config: &std::path::Path,
+) -> Result<i32> {
+    match action {
+        ConfigCommand::Check => {
+            let report = layers?.resolve()?.validate();
+            if report.issues.is_empty() {
+                println!("Configuration is valid");
+            } else {
+                print!("{}", report);
+            }
+            Ok(if report.is_ok() { 0 } else { 1 })
+        }
+        ConfigCommand::Get { key } => {
+            let effective = toml::Value::try_from(layers?.resolve()?)?;
+            let value = key
+                .split('.')
+                .try_fold(&effective, |value, part| value.get(part));
+            match value.map(|value| redacted(&key, value)) {
+                Some(toml::Value::String(value)) => println!("{}", value),
+                Some(value) => println!("{}", value),
+                None => {
+                    eprintln!("{} is not set", key);
+                    return Ok(1);
+                }
+            }
+            Ok(0)
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut editor = config::ConfigEditor::open(user_config).await?;
+            editor.set(&key, &value)?;
+            save_checked(&editor).await
+        }
+        ConfigCommand::Unset { key } => {
+            let mut editor = config::ConfigEditor::open(user_config).await?;
+            if !editor.unset(&key) {
+                eprintln!("{} is not set in {}", key, user_config.display());
+                return Ok(1);
+            }
+            save_checked(&editor).await
+        }
+        ConfigCommand::List { effective: true } => {
+            print_effective_config(&layers?)?;
+            Ok(0)
+        }
+        ConfigCommand::List { effective: false } => {
+            let editor = config::ConfigEditor::open(user_config).await?;
+            for (key, value) in editor.entries() {
+                println!("{} = {}", key, redacted(&key, &value));
+            }
+            Ok(0)
+        }
+        ConfigCommand::Path => {
+            println!("{}", user_config.display());
+            Ok(0)
+        }
+        ConfigCommand::Edit => edit_config(user_config).await,
+    }
+}
+
+/// Print a startup error in the chosen output format and exit with its status
+fn fail(output: cli::OutputFormat, error: cli::ExecError) -> ! {
+    output.print(&cli::OutputRecord::error(&error));
+    std::process::exit(error.exit_code());
+}
+
+/// Print every effective setting and the layer it came from
+fn print_effective_config(layers: &config::ConfigLayers) -> Result<()> {
+    for (key, value, source) in layers.explain()? {
+        println!("{} = {}  # {}", key, redacted(&key, &value), source);
+    }
+    Ok(())
+}
+
+/// Hide pre-shared keys, but show references such as env:RCP_PSK
+fn redacted(key: &str, value: &toml::Value) -> toml::Value {
+    let is_secret = key == "auth.psk" || key.ends_with(".auth.psk");
+    let is_reference = value
+        .as_str()
+        .and_then(|s| s.parse::<auth::SecretRef>().ok())
+        .is_some_and(|psk| psk.is_reference());
+    if is_secret && !is_reference {
+        toml::Value::String("********".to_string())
+    } else {
+        value.clone()
+    }
+}
+
+/// Save a changed config file unless the change added validation errors
+async fn save_checked(editor: &config::ConfigEditor) -> Result<i32> {
+    let issues = editor.new_issues()?;
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+    if issues
+        .iter()
+        .any(|issue| issue.severity == config::Severity::Error)
+    {
+        eprintln!("{} was not changed", editor.path().display());
+        return Ok(1);
+    }
+    editor.save().await?;
+    Ok(0)
+}
+
+/// Open the user config file in an editor until it is valid or the user gives up
+async fn edit_config(user_config: &std::path::Path) -> Result<i32> {
+    if !user_config.exists() {
+        config::ConfigEditor::open(user_config)
+            .await?
+            .save()
+            .await?;
+    }
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+
+    loop {
+        let command_line = format!("{} \"{}\"", editor, user_config.display());
+        let status = auth::store::shell(&command_line).status()?;
+        if !status.success() {
+            anyhow::bail!("{} exited with {}", editor, status);
+        }
+
+        let report = config::ConfigEditor::open(user_config)
+            .await
+            .and_then(|file| file.config())
+            .map(|config| config.validate());
+        match report {
+            Ok(report) if report.is_ok() => {
+                print!("{}", report);
+                println!("Configuration is valid");
+                return Ok(0);
+            }
+            Ok(report) => print!("{}", report),
+            Err(e) => println!("{:#}", e),
+        }
+
+        print!("Edit again? [Y/n] ");
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("n") {
+            return Ok(1);
+        }
+    }
+}
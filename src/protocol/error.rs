@@ -15,9 +15,9 @@ pub enum ProtocolError {
     #[error("Authentication failed: {0}")]
     AuthenticationFailed(String),
 
-    /// Server error
-    #[error("Server error: {0}")]
-    ServerError(String),
+    /// The server reported an error, with its error code if it sent one
+    #[error("Server error: {message}{}", .code.map(|code| format!(" (code {})", code)).unwrap_or_default())]
+    ServerError { code: Option<u32>, message: String },
 
     /// Channel closed
     #[error("Channel closed")]
@@ -27,7 +27,39 @@ pub enum ProtocolError {
     #[error("Operation timed out")]
     Timeout,
 
+    /// The server presented a different identity than the pinned one
+    #[error(
+        "Server identity for {server} has changed (pinned {expected}, presented {actual}); \
+         if the server certificate was replaced on purpose, reconnect with --accept-server-key"
+    )]
+    ServerIdentityChanged {
+        server: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// The user declined to trust a server seen for the first time
+    #[error("Server {0} was not trusted")]
+    ServerNotTrusted(String),
+
     /// Other error
     #[error("Protocol error: {0}")]
     Other(String),
 }
+
+impl ProtocolError {
+    /// Name of the error variant, as used in machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProtocolError::MalformedPayload(_) => "malformed_payload",
+            ProtocolError::Transport(_) => "transport",
+            ProtocolError::AuthenticationFailed(_) => "authentication_failed",
+            ProtocolError::ServerError { .. } => "server_error",
+            ProtocolError::ChannelClosed => "channel_closed",
+            ProtocolError::Timeout => "timeout",
+            ProtocolError::ServerIdentityChanged { .. } => "server_identity_changed",
+            ProtocolError::ServerNotTrusted(_) => "server_not_trusted",
+            ProtocolError::Other(_) => "other",
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Pinned server identities
+//!
+//! The first time the client connects to a server over TLS, the fingerprint
+//! of the server certificate is recorded (trust on first use). Later
+//! connections fail if the server presents a different certificate, unless
+//! the change is accepted explicitly.
+//!
+//! The file has one entry per line, `host:port sha256:<hex>`; blank lines and
+//! lines starting with `#` are ignored.
+
+use crate::auth::Prompter;
+use crate::config::{lock_file, paths, write_atomic};
+use crate::protocol::ProtocolError;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Result of looking up a server in the known servers file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostStatus {
+    /// The fingerprint matches the pinned one
+    Trusted,
+
+    /// The server has not been seen before
+    Unknown,
+
+    /// The server presented a different fingerprint than the pinned one
+    Changed {
+        /// Pinned fingerprint
+        expected: String,
+    },
+}
+
+/// Known servers file
+pub struct KnownServers {
+    path: PathBuf,
+    entries: BTreeMap<String, String>,
+    trusted: BTreeSet<String>,
+}
+
+impl KnownServers {
+    /// Default location of the known servers file
+    pub fn default_path() -> PathBuf {
+        paths::moved_file(&paths::data_dir(), "known_servers")
+    }
+
+    /// Load the known servers file; a missing file is treated as empty
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = BTreeMap::new();
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read known servers file: {:?}", path))?;
+
+            for line in content.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                match line.split_once(char::is_whitespace) {
+                    Some((server, fingerprint)) => {
+                        entries.insert(server.to_string(), fingerprint.trim().to_string());
+                    }
+                    None => log::warn!("Ignoring malformed line in {:?}: {}", path, line),
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            entries,
+            trusted: BTreeSet::new(),
+        })
+    }
+
+    fn key(address: &str, port: u16) -> String {
+        format!("{}:{}", address, port)
+    }
+
+    /// Compare a server's fingerprint with the pinned one
+    pub fn check(&self, address: &str, port: u16, fingerprint: &str) -> HostStatus {
+        match self.entries.get(&Self::key(address, port)) {
+            Some(pinned) if pinned == fingerprint => HostStatus::Trusted,
+            Some(pinned) => HostStatus::Changed {
+                expected: pinned.clone(),
+            },
+            None => HostStatus::Unknown,
+        }
+    }
+
+    /// Pin a fingerprint for a server, replacing any existing entry
+    pub fn trust(&mut self, address: &str, port: u16, fingerprint: &str) {
+        let server = Self::key(address, port);
+        self.entries.insert(server.clone(), fingerprint.to_string());
+        self.trusted.insert(server);
+    }
+
+    /// Write the servers trusted since loading back to disk
+    ///
+    /// The file is locked and read again first, so entries pinned meanwhile
+    /// by other instances are kept, and it is replaced in one step.
+    pub fn save(&self) -> Result<()> {
+        let _lock = lock_file(&self.path)?;
+        let mut entries = Self::load(&self.path)?.entries;
+        for server in &self.trusted {
+            if let Some(fingerprint) = self.entries.get(server) {
+                entries.insert(server.clone(), fingerprint.clone());
+            }
+        }
+
+        let mut content = String::from("# RCP client known servers\n");
+        for (server, fingerprint) in &entries {
+            content.push_str(&format!("{} {}\n", server, fingerprint));
+        }
+
+        write_atomic(&self.path, content.as_bytes())
+            .with_context(|| format!("Failed to write known servers file: {:?}", self.path))
+    }
+}
+
+/// Check a server's fingerprint against the known servers file
+///
+/// Unknown servers are pinned on first use; with a prompter the user is asked
+/// first. A changed fingerprint is an error unless `accept_changed` is set or
+/// the user accepts the new fingerprint at the prompt.
+pub async fn verify(
+    known_servers: &Path,
+    address: &str,
+    port: u16,
+    fingerprint: &str,
+    accept_changed: bool,
+    prompter: Option<&dyn Prompter>,
+) -> Result<()> {
+    let mut known = KnownServers::load(known_servers)?;
+    let server = KnownServers::key(address, port);
+
+    match known.check(address, port, fingerprint) {
+        HostStatus::Trusted => return Ok(()),
+        HostStatus::Unknown => {
+            if let Some(prompter) = prompter {
+                let question = format!(
+                    "The identity of {} has not been seen before.\n\
+                     Fingerprint: {}\n\
+                     Trust this server?",
+                    server, fingerprint
+                );
+                if !prompter.confirm(&question).await? {
+                    return Err(ProtocolError::ServerNotTrusted(server).into());
+                }
+            }
+            log::info!("Pinning identity of {}: {}", server, fingerprint);
+        }
+        HostStatus::Changed { expected } => {
+            let accepted = accept_changed
+                || match prompter {
+                    Some(prompter) => {
+                        let question = format!(
+                            "WARNING: the identity of {} has changed!\n\
+                             Someone could be intercepting the connection.\n\
+                             Pinned: {}\n\
+                             Presented: {}\n\
+                             Replace the pinned fingerprint?",
+                            server, expected, fingerprint
+                        );
+                        prompter.confirm(&question).await?
+                    }
+                    None => false,
+                };
+
+            if !accepted {
+                return Err(ProtocolError::ServerIdentityChanged {
+                    server,
+                    expected,
+                    actual: fingerprint.to_string(),
+                }
+                .into());
+            }
+            log::warn!(
+                "Replacing pinned identity of {}: {} -> {}",
+                server,
+                expected,
+                fingerprint
+            );
+        }
+    }
+
+    known.trust(address, port, fingerprint);
+    known.save()
+}
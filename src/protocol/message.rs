@@ -44,7 +44,9 @@ impl fmt::Display for MessageType {
 }
 
 /// A message in the RCP protocol
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// `Debug` output omits the payload of auth messages, which carries credentials.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Message {
     /// Unique ID for this message
     pub id: Uuid,
@@ -60,6 +62,24 @@ pub struct Message {
     pub payload: Value,
 }
 
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Message");
+        debug
+            .field("id", &self.id)
+            .field("message_type", &self.message_type)
+            .field("timestamp", &self.timestamp);
+
+        if self.message_type == MessageType::Auth {
+            debug.field("payload", &"<redacted>");
+        } else {
+            debug.field("payload", &self.payload);
+        }
+
+        debug.finish()
+    }
+}
+
 impl Message {
     /// Create a new message with the given type and payload
     pub fn new(message_type: MessageType, payload: Value) -> Self {
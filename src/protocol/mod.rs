@@ -1,4 +1,7 @@
+use crate::auth::Prompter;
+use crate::config::ServerConfig;
 use anyhow::Result;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
@@ -6,24 +9,36 @@ use tokio::sync::Mutex;
 use tokio::time::{timeout, Duration};
 
 mod error;
+pub mod known_servers;
 mod message;
 mod response_handler;
+pub mod tls;
 mod transport;
 
 pub use error::ProtocolError;
+pub use known_servers::KnownServers;
 pub use message::{Message, MessageType};
 pub use transport::Transport;
 
+/// Command that asks the server which commands it accepts
+pub const CAPABILITIES_COMMAND: &str = "capabilities";
+
 /// Client connection to the RCP server
 pub struct Client {
     /// The underlying transport
     transport: Arc<Mutex<Transport>>,
 
     /// Channel for receiving messages from the server
-    receiver: mpsc::Receiver<Message>,
+    receiver: Mutex<mpsc::Receiver<Message>>,
 
     /// Channel for sending messages to the server
     sender: mpsc::Sender<Message>,
+
+    /// Fingerprint of the server certificate, for TLS connections
+    server_fingerprint: Option<String>,
+
+    /// Path of the Unix socket, for local connections
+    unix_socket: Option<PathBuf>,
 }
 
 impl Client {
@@ -37,23 +52,128 @@ impl Client {
 
         Ok(Self {
             transport,
-            receiver,
+            receiver: Mutex::new(receiver),
             sender,
+            server_fingerprint: None,
+            unix_socket: None,
+        })
+    }
+
+    /// Connect to a local RCP server over a Unix socket
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+            ProtocolError::Transport(format!("Failed to connect to {}: {}", path.display(), e))
+        })?;
+
+        // Create the transport
+        let (transport, receiver, sender) = Transport::new(stream).await?;
+
+        Ok(Self {
+            transport,
+            receiver: Mutex::new(receiver),
+            sender,
+            server_fingerprint: None,
+            unix_socket: Some(path.to_path_buf()),
         })
     }
 
     /// Connect with TLS
+    ///
+    /// The server certificate is not checked here; compare
+    /// [`Client::server_fingerprint`] with the known servers file, as
+    /// [`Client::connect_with_config`] does.
     pub async fn connect_tls(
         address: &str,
         port: u16,
         client_cert: Option<&str>,
         client_key: Option<&str>,
-        verify_server: bool,
     ) -> Result<Self> {
-        // This would use rustls or native-tls to establish a secure connection
-        // For now, just use the regular connect and note that TLS would be implemented here
-        log::warn!("TLS support not yet implemented, using insecure connection");
-        Self::connect(address, port).await
+        let stream = TcpStream::connect(format!("{}:{}", address, port)).await?;
+        let (stream, fingerprint) = tls::connect(stream, address, client_cert, client_key).await?;
+
+        // Create the transport
+        let (transport, receiver, sender) = Transport::new(stream).await?;
+
+        Ok(Self {
+            transport,
+            receiver: Mutex::new(receiver),
+            sender,
+            server_fingerprint: Some(fingerprint),
+            unix_socket: None,
+        })
+    }
+
+    /// Connect as described by the server configuration
+    ///
+    /// With TLS enabled and `verify_server` set, the server identity is checked
+    /// against the known servers file; `prompter` is asked before trusting a
+    /// new or changed identity.
+    pub async fn connect_with_config(
+        config: &ServerConfig,
+        prompter: Option<&dyn Prompter>,
+    ) -> Result<Self> {
+        if let Some(socket_path) = &config.socket_path {
+            #[cfg(unix)]
+            return Self::connect_unix(socket_path).await;
+
+            #[cfg(not(unix))]
+            anyhow::bail!(
+                "Unix sockets are not supported on this platform: {}",
+                socket_path
+            );
+        }
+
+        if !config.use_tls {
+            return Self::connect(&config.address, config.port).await;
+        }
+
+        let client = Self::connect_tls(
+            &config.address,
+            config.port,
+            config.client_cert_path.as_deref(),
+            config.client_key_path.as_deref(),
+        )
+        .await?;
+
+        let fingerprint = client.server_fingerprint().unwrap_or_default();
+        if config.verify_server {
+            let known_servers = config
+                .known_servers_path
+                .as_ref()
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(KnownServers::default_path);
+
+            known_servers::verify(
+                &known_servers,
+                &config.address,
+                config.port,
+                fingerprint,
+                config.accept_server_key,
+                prompter,
+            )
+            .await?;
+        } else {
+            log::warn!(
+                "Server identity pinning is off, accepting any certificate from {}:{} ({})",
+                config.address,
+                config.port,
+                fingerprint
+            );
+        }
+
+        Ok(client)
+    }
+
+    /// Fingerprint of the server certificate, for TLS connections
+    pub fn server_fingerprint(&self) -> Option<&str> {
+        self.server_fingerprint.as_deref()
+    }
+
+    /// Path of the Unix socket, for local connections
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.unix_socket.as_deref()
     }
 
     /// Send a message to the server
@@ -65,18 +185,125 @@ impl Client {
     }
 
     /// Receive a message from the server
-    pub async fn receive(&mut self) -> Option<Message> {
-        self.receiver.recv().await
+    pub async fn receive(&self) -> Option<Message> {
+        self.receiver.lock().await.recv().await
     }
 
     /// Receive a message from the server with timeout
-    pub async fn receive_with_timeout(&mut self, timeout_secs: u64) -> Result<Option<Message>> {
-        match timeout(Duration::from_secs(timeout_secs), self.receiver.recv()).await {
+    pub async fn receive_with_timeout(&self, timeout_secs: u64) -> Result<Option<Message>> {
+        let mut receiver = self.receiver.lock().await;
+        match timeout(Duration::from_secs(timeout_secs), receiver.recv()).await {
             Ok(message) => Ok(message),
             Err(_) => Err(ProtocolError::Timeout.into()),
         }
     }
 
+    /// Wait for the next response or error message from the server
+    ///
+    /// Pings are answered and events are skipped while waiting, so this can be
+    /// used for request/reply exchanges such as authentication.
+    pub async fn wait_for_reply(&self, timeout_secs: u64) -> Result<Message> {
+        self.wait_for_reply_with_events(timeout_secs, |event| {
+            log::debug!(
+                "Ignoring {} message while waiting for reply",
+                event.message_type
+            )
+        })
+        .await
+    }
+
+    /// Like [`wait_for_reply`](Self::wait_for_reply), passing the events that
+    /// arrive meanwhile to `on_event`
+    pub async fn wait_for_reply_with_events(
+        &self,
+        timeout_secs: u64,
+        mut on_event: impl FnMut(&Message),
+    ) -> Result<Message> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_secs);
+        let mut receiver = self.receiver.lock().await;
+
+        loop {
+            let message = match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Err(ProtocolError::ChannelClosed.into()),
+                Err(_) => return Err(ProtocolError::Timeout.into()),
+            };
+
+            match message.message_type {
+                MessageType::Response | MessageType::Error => return Ok(message),
+                MessageType::Ping => self.send(Message::pong(message.id)).await?,
+                _ => on_event(&message),
+            }
+        }
+    }
+
+    /// Send a command and wait for its response
+    ///
+    /// Returns the `data` of a successful response. A failed response or an
+    /// error message from the server is a [`ProtocolError::ServerError`].
+    pub async fn execute(
+        &self,
+        command: &str,
+        params: serde_json::Value,
+        timeout_secs: u64,
+    ) -> Result<serde_json::Value> {
+        self.execute_with_events(command, params, timeout_secs, |event| {
+            log::debug!(
+                "Ignoring {} message while waiting for reply",
+                event.message_type
+            )
+        })
+        .await
+    }
+
+    /// Like [`execute`](Self::execute), passing the events that arrive
+    /// meanwhile to `on_event`
+    pub async fn execute_with_events(
+        &self,
+        command: &str,
+        params: serde_json::Value,
+        timeout_secs: u64,
+        on_event: impl FnMut(&Message),
+    ) -> Result<serde_json::Value> {
+        let request = Message::command(command, params);
+        let request_id = request.id;
+        self.send(request).await?;
+
+        let reply = self
+            .wait_for_reply_with_events(timeout_secs, on_event)
+            .await?;
+        response_handler::handle_response(&reply, &request_id).await
+    }
+
+    /// Ask the server which commands it accepts
+    ///
+    /// Sends the [`CAPABILITIES_COMMAND`], which the server answers with
+    /// `{"commands": ["status", ...]}`. Returns `None` if the server does not
+    /// advertise its commands.
+    pub async fn query_commands(&self, timeout_secs: u64) -> Result<Option<Vec<String>>> {
+        let data = match self
+            .execute(CAPABILITIES_COMMAND, serde_json::json!({}), timeout_secs)
+            .await
+        {
+            Ok(data) => data,
+            Err(e) if matches!(e.downcast_ref(), Some(ProtocolError::ServerError { .. })) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(data
+            .get("commands")
+            .and_then(|v| v.as_array())
+            .map(|commands| {
+                commands
+                    .iter()
+                    .filter_map(|c| c.as_str())
+                    .map(|c| c.to_string())
+                    .collect()
+            }))
+    }
+
     /// Authenticate with the server
     pub async fn authenticate(
         &self,
@@ -5,6 +5,19 @@ use uuid::Uuid;
 
 /// Handle an RCP response message
 pub async fn handle_response(response: &Message, request_id: &Uuid) -> Result<Value> {
+    if response.message_type == MessageType::Error {
+        let message = response
+            .payload
+            .get("message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown error");
+        return Err(ProtocolError::ServerError {
+            code: error_code(&response.payload),
+            message: message.to_string(),
+        }
+        .into());
+    }
+
     if response.message_type != MessageType::Response {
         return Err(ProtocolError::Other(format!(
             "Expected response message, got {}",
@@ -37,7 +50,11 @@ pub async fn handle_response(response: &Message, request_id: &Uuid) -> Result<Va
                 .get("message")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Unknown error");
-            return Err(ProtocolError::ServerError(message.to_string()).into());
+            return Err(ProtocolError::ServerError {
+                code: error_code(payload),
+                message: message.to_string(),
+            }
+            .into());
         }
     }
 
@@ -49,3 +66,11 @@ pub async fn handle_response(response: &Message, request_id: &Uuid) -> Result<Va
     // If there's no data, return an empty object
     Ok(json!({}))
 }
+
+/// Error code the server sent along with an error, if any
+fn error_code(payload: &Value) -> Option<u32> {
+    payload
+        .get("code")
+        .and_then(|v| v.as_u64())
+        .and_then(|code| u32::try_from(code).ok())
+}
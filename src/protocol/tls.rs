@@ -0,0 +1,138 @@
+use crate::protocol::ProtocolError;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, DigitallySignedStruct, SignatureScheme};
+use tokio_rustls::TlsConnector;
+
+/// Fingerprint of a DER-encoded certificate, as stored in the known servers file
+pub fn fingerprint(certificate: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(certificate)))
+}
+
+/// Certificate verifier for pinned servers
+///
+/// RCP servers commonly use self-signed certificates, so the chain is not
+/// checked against a CA here. The handshake signature is still verified, which
+/// proves the server holds the key for the certificate it presented; the
+/// certificate itself is then checked against the known servers file, unless
+/// `verify_server` is off, in which case any certificate is accepted.
+#[derive(Debug)]
+struct PinningVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Load a PEM certificate chain
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open client certificate: {}", path))?;
+    rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse client certificate: {}", path))
+}
+
+/// Load a PEM private key
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open client key: {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse client key: {}", path))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path))
+}
+
+/// Run the TLS handshake over `stream`
+///
+/// Returns the TLS stream and the fingerprint of the server certificate.
+pub async fn connect(
+    stream: TcpStream,
+    address: &str,
+    client_cert: Option<&str>,
+    client_key: Option<&str>,
+) -> Result<(TlsStream<TcpStream>, String)> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier { provider }));
+
+    let tls_config = match (client_cert, client_key) {
+        (Some(cert), Some(key)) => {
+            builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => anyhow::bail!("Mutual TLS needs both a client certificate and a client key"),
+    };
+
+    let server_name = ServerName::try_from(address.to_string())
+        .map_err(|e| ProtocolError::Transport(format!("Invalid server name {}: {}", address, e)))?;
+
+    let stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| ProtocolError::Transport(format!("TLS handshake failed: {}", e)))?;
+
+    let certificate = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| ProtocolError::Transport("Server sent no certificate".to_string()))?;
+    let fingerprint = fingerprint(certificate);
+
+    Ok((stream, fingerprint))
+}
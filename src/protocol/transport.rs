@@ -1,23 +1,31 @@
 use crate::protocol::{Message, ProtocolError};
 use anyhow::Result;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// A byte stream the transport can run over (TCP, TLS or a Unix socket)
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
 
 /// Transport layer for RCP protocol
+///
+/// The stream is split so that reading and writing run in separate tasks;
+/// both stop when the transport is dropped.
 pub struct Transport {
-    /// The underlying TCP stream
-    stream: TcpStream,
+    /// Task reading messages from the stream
+    reader_task: JoinHandle<()>,
 
-    /// Buffer for reading
-    read_buffer: Vec<u8>,
+    /// Task writing messages to the stream
+    writer_task: JoinHandle<()>,
 }
 
 impl Transport {
     /// Create a new transport using the given stream
-    pub async fn new(
-        stream: TcpStream,
+    pub async fn new<S: Stream + 'static>(
+        stream: S,
     ) -> Result<(
         Arc<Mutex<Self>>,
         mpsc::Receiver<Message>,
@@ -25,22 +33,18 @@ impl Transport {
     )> {
         // Create channels for sending and receiving messages
         let (incoming_tx, incoming_rx) = mpsc::channel(100);
-        let (outgoing_tx, mut outgoing_rx) = mpsc::channel(100);
+        let (outgoing_tx, mut outgoing_rx) = mpsc::channel::<Message>(100);
 
-        // Create the transport
-        let transport = Arc::new(Mutex::new(Self {
-            stream,
-            read_buffer: Vec::with_capacity(4096),
-        }));
+        let stream: Box<dyn Stream> = Box::new(stream);
+        let (mut reader, mut writer) = tokio::io::split(stream);
 
         // Spawn a task to receive messages from the stream
-        let transport_clone = transport.clone();
-        tokio::spawn(async move {
-            let mut transport = transport_clone.lock().await;
+        let reader_task = tokio::spawn(async move {
+            let mut read_buffer = Vec::with_capacity(4096);
 
             loop {
                 // Read a message from the stream
-                match transport.read_message().await {
+                match read_message(&mut reader, &mut read_buffer).await {
                     Ok(message) => {
                         // Send the message to the incoming channel
                         if incoming_tx.send(message).await.is_err() {
@@ -57,58 +61,69 @@ impl Transport {
         });
 
         // Spawn a task to send messages to the stream
-        let transport_clone = transport.clone();
-        tokio::spawn(async move {
+        let writer_task = tokio::spawn(async move {
             while let Some(message) = outgoing_rx.recv().await {
-                let mut transport = transport_clone.lock().await;
-
-                if let Err(e) = transport.write_message(&message).await {
+                if let Err(e) = write_message(&mut writer, &message).await {
                     log::error!("Error writing message: {}", e);
                     break;
                 }
             }
         });
 
+        let transport = Arc::new(Mutex::new(Self {
+            reader_task,
+            writer_task,
+        }));
+
         Ok((transport, incoming_rx, outgoing_tx))
     }
+}
 
-    /// Read a message from the stream
-    async fn read_message(&mut self) -> Result<Message> {
-        // Read message size (4 bytes)
-        let mut size_buf = [0u8; 4];
-        self.stream.read_exact(&mut size_buf).await?;
-        let size = u32::from_be_bytes(size_buf) as usize;
+impl Drop for Transport {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+        self.writer_task.abort();
+    }
+}
 
-        // Ensure the buffer is large enough
-        if self.read_buffer.len() < size {
-            self.read_buffer.resize(size, 0);
-        }
+/// Read a message from the stream
+async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    read_buffer: &mut Vec<u8>,
+) -> Result<Message> {
+    // Read message size (4 bytes)
+    let mut size_buf = [0u8; 4];
+    reader.read_exact(&mut size_buf).await?;
+    let size = u32::from_be_bytes(size_buf) as usize;
+
+    // Ensure the buffer is large enough
+    if read_buffer.len() < size {
+        read_buffer.resize(size, 0);
+    }
 
-        // Read the message data
-        self.stream
-            .read_exact(&mut self.read_buffer[..size])
-            .await?;
+    // Read the message data
+    reader.read_exact(&mut read_buffer[..size]).await?;
 
-        // Parse the message
-        let message = serde_json::from_slice(&self.read_buffer[..size])
-            .map_err(|e| ProtocolError::MalformedPayload(e.to_string()))?;
+    // Parse the message
+    let message = serde_json::from_slice(&read_buffer[..size])
+        .map_err(|e| ProtocolError::MalformedPayload(e.to_string()))?;
 
-        Ok(message)
-    }
+    Ok(message)
+}
 
-    /// Write a message to the stream
-    async fn write_message(&mut self, message: &Message) -> Result<()> {
-        // Serialize the message
-        let data = serde_json::to_vec(message)
-            .map_err(|e| ProtocolError::MalformedPayload(e.to_string()))?;
+/// Write a message to the stream
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Message) -> Result<()> {
+    // Serialize the message
+    let data =
+        serde_json::to_vec(message).map_err(|e| ProtocolError::MalformedPayload(e.to_string()))?;
 
-        // Write the message size
-        let size = data.len() as u32;
-        self.stream.write_all(&size.to_be_bytes()).await?;
+    // Write the message size
+    let size = data.len() as u32;
+    writer.write_all(&size.to_be_bytes()).await?;
 
-        // Write the message data
-        self.stream.write_all(&data).await?;
+    // Write the message data
+    writer.write_all(&data).await?;
+    writer.flush().await?;
 
-        Ok(())
-    }
+    Ok(())
 }
@@ -1,3 +1,4 @@
+use crate::config::paths;
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -49,10 +50,9 @@ impl ResourceManager {
         let candidates = vec![
             // Current directory
             PathBuf::from("resources"),
-            // User configuration directory
-            dirs::config_dir()
-                .map(|p| p.join("rcp_client").join("resources"))
-                .unwrap_or_else(|| PathBuf::from("resources")),
+            // User data directory, or the config directory of earlier versions
+            paths::data_dir().join("resources"),
+            paths::config_dir().join("resources"),
             // System-wide configuration directory
             #[cfg(target_os = "linux")]
             PathBuf::from("/etc/rcp_client/resources"),
@@ -132,6 +132,8 @@ impl RcpClientApp {
             // Draw server configuration panel
             super::widgets::server_panel::draw_server_panel(
                 ui,
+                &[],
+                &mut None,
                 &mut self.server_address,
                 &mut self.server_port,
                 &mut self.use_tls,
@@ -1,8 +1,9 @@
+use crate::audit::{self, AuditEvent};
 use crate::auth;
 use crate::config::ClientConfig;
 use crate::protocol;
 use anyhow::Result;
-use log::{error, info, warn};
+use log::{error, info};
 use std::fmt;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot, Mutex};
@@ -55,18 +56,22 @@ pub struct EventBasedApp {
     client: Arc<Mutex<Option<protocol::Client>>>,
     /// Auto-connect flag
     auto_connect: bool,
+    /// Session tokens kept across reconnects
+    sessions: Arc<auth::SessionStore>,
 }
 
 impl EventBasedApp {
     /// Create a new application with the given configuration
     pub fn new(config: ClientConfig, auto_connect: bool) -> Self {
         let (event_tx, event_rx) = mpsc::channel(32);
+        let sessions = auth::SessionStore::from_config(&config.auth);
         Self {
             config,
             event_tx,
             event_rx,
             client: Arc::new(Mutex::new(None)),
             auto_connect,
+            sessions: Arc::new(sessions),
         }
     }
 
@@ -164,6 +169,10 @@ impl EventBasedApp {
             }
             AppEvent::Quit => {
                 info!("Quitting application");
+                if let Some(client) = self.client.lock().await.take() {
+                    let result = client.close().await;
+                    audit::record_connection(&self.config, AuditEvent::Disconnect, &result);
+                }
                 Ok(true)
             }
         }
@@ -173,6 +182,12 @@ impl EventBasedApp {
     async fn connect_to_server(&self) -> Result<()> {
         let config = self.config.clone();
         let event_tx = self.event_tx.clone();
+        // A connect after an earlier connection is audited as a reconnect
+        let audit_event = if self.client.lock().await.is_some() {
+            AuditEvent::Reconnect
+        } else {
+            AuditEvent::Connect
+        };
 
         // Spawn a task to connect to the server
         tokio::spawn(async move {
@@ -181,7 +196,9 @@ impl EventBasedApp {
                 config.server.address, config.server.port
             );
 
-            match protocol::Client::connect(&config.server.address, config.server.port).await {
+            let result = protocol::Client::connect_with_config(&config.server, None).await;
+            audit::record_connection(&config, audit_event, &result);
+            match result {
                 Ok(client) => {
                     info!("Connected to server");
                     event_tx.send(AppEvent::Connected(client)).await.unwrap();
@@ -204,6 +221,7 @@ impl EventBasedApp {
         let config = self.config.clone();
         let event_tx = self.event_tx.clone();
         let client_lock = self.client.clone();
+        let sessions = self.sessions.clone();
 
         // Spawn a task to authenticate
         tokio::spawn(async move {
@@ -211,29 +229,14 @@ impl EventBasedApp {
             let client_opt = client_lock.lock().await;
             if let Some(client) = &*client_opt {
                 // Get the username
-                let username = config.auth.username.clone().unwrap_or_else(|| {
-                    // Try to get the current OS username
-                    std::env::var("USER")
-                        .or_else(|_| std::env::var("USERNAME"))
-                        .unwrap_or_else(|_| "user".to_string())
-                });
-
-                // Determine authentication method
-                let auth_method = match auth::AuthMethod::from_str(&config.auth.method) {
-                    Some(method) => method,
-                    None => {
-                        warn!(
-                            "Unknown authentication method: {}, falling back to password",
-                            config.auth.method
-                        );
-                        auth::AuthMethod::Password
-                    }
-                };
+                let username = config.auth.username_or_current();
 
-                info!("Authenticating with method: {}", auth_method);
-                let auth_provider = auth::create_provider(auth_method, &username);
+                let options =
+                    auth::AuthOptions::from_config(&config, Arc::new(auth::TerminalPrompter))
+                        .with_sessions(sessions);
 
-                match client.authenticate_with_provider(&*auth_provider).await {
+                match auth::negotiate::authenticate(client, &config.auth, &username, &options).await
+                {
                     Ok(true) => {
                         info!("Authentication successful");
                         event_tx
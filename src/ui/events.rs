@@ -1,8 +1,12 @@
+use crate::auth::Secret;
+use crate::config::{ClientConfig, ConnectionLink};
+use std::path::PathBuf;
+
 /// GUI Application events
 #[derive(Debug, Clone)]
 pub enum AppEvent {
-    /// Connect to server
-    Connect,
+    /// Connect with the settings and password entered in the panels
+    Connect(Box<ClientConfig>, Secret),
     /// Disconnect from server
     Disconnect,
     /// Connection succeeded
@@ -13,8 +17,8 @@ pub enum AppEvent {
     AuthenticationSucceeded,
     /// Authentication failed
     AuthenticationFailed(String),
-    /// Config saved
-    ConfigSaved,
+    /// Config saved to the given file
+    ConfigSaved(PathBuf),
     /// Config save failed
     ConfigSaveFailed(String),
     /// Disconnection Confirmed
@@ -33,8 +37,20 @@ pub enum AppEvent {
     ClearCredentials,
     /// Validate input
     ValidateInput(String),
-    /// Save configuration
-    SaveConfig,
+    /// Set (or, for `None`, remove) these dotted keys in the user config file
+    SaveConfig(Vec<(String, Option<String>)>),
     /// Update status with a message
     StatusUpdate(String),
+    /// Submit the secret entered in the prompt dialog
+    SubmitPrompt(Secret),
+    /// Answer the yes/no question in the prompt dialog
+    ConfirmPrompt(bool),
+    /// Cancel the prompt dialog
+    CancelPrompt,
+    /// Switch to the named profile, or to no profile
+    SelectProfile(Option<String>),
+    /// Use a configuration reloaded from the config files on the next connect
+    ConfigReloaded(Box<ClientConfig>),
+    /// Use the settings from a connection link on the next connect
+    OpenLink(ConnectionLink),
 }
@@ -1,12 +1,19 @@
 // filepath: /Volumes/EXT/repos/open-rcp/rust-rcp-client/src/ui/gui.rs
-use crate::config::ClientConfig;
+use crate::audit::{self, AuditEvent, AuditLog, AuditRecord};
+use crate::auth::{self, Secret};
+use crate::config::{
+    self, ClientConfig, ConfigIssue, ConfigUpdate, ConnectionLink, Severity, UiConfig,
+};
 use crate::protocol;
 use crate::ui::events::AppEvent;
 use crate::ui::history::{
     add_to_connection_history, load_connection_history, save_connection_history,
 }; // Added save_connection_history
 use crate::ui::models::{AppState, ConnectionEntry};
+use crate::ui::prompter::{GuiPrompter, PromptAnswer};
+use crate::ui::widgets::action_panel::PanelAction;
 use eframe::egui;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::runtime::Handle;
 use tokio::sync::{mpsc, oneshot, Mutex};
@@ -17,10 +24,10 @@ pub struct RcpClientApp {
     server_port: String,
     auth_method: String,
     username: String,
-    password: String, // For UI binding if needed, auth_panel uses AppState.password for its logic
+    password: Secret, // For UI binding if needed, auth_panel uses AppState.password for its logic
     token: String,    // For UI binding if needed
     psk_identity: String, // For UI binding if needed
-    psk_key: String,  // For UI binding if needed
+    psk_key: Secret,  // For UI binding if needed
     use_tls: bool,
     remember_credentials: bool,
     auto_connect: bool,
@@ -28,19 +35,30 @@ pub struct RcpClientApp {
 
     status: Arc<Mutex<String>>,
     app_state: Arc<Mutex<AppState>>,
-    client: Arc<Mutex<Option<protocol::Client>>>,
+    client: Arc<Mutex<Option<Arc<protocol::Client>>>>,
     event_tx: mpsc::Sender<AppEvent>,
     event_rx: Option<mpsc::Receiver<AppEvent>>,
     status_message: String,
     rt_handle: Handle,
     shutdown_tx: Option<oneshot::Sender<()>>,
     connection_history: Vec<ConnectionEntry>, // Changed to Vec<ConnectionEntry>
+    audit_log: Option<AuditLog>,
+    audit_records: Vec<AuditRecord>,
+    show_audit_log: bool,
+    config: ClientConfig,
+    selected_profile: Option<String>,
+    config_issues: Vec<ConfigIssue>,
+    config_updates: Option<mpsc::Receiver<ConfigUpdate>>,
+    link_input: String,
+    show_open_link: bool,
 }
 
 impl RcpClientApp {
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         config: ClientConfig,
+        config_path: PathBuf,
+        config_updates: Option<mpsc::Receiver<ConfigUpdate>>,
         rt_handle: Handle,
         shutdown_tx: oneshot::Sender<()>,
     ) -> Self {
@@ -48,7 +66,7 @@ impl RcpClientApp {
         let ctx = &cc.egui_ctx;
 
         // Increase font size throughout the application
-        ctx.set_pixels_per_point(1.3); // Increase UI scale by 30%
+        apply_ui_settings(ctx, &config.ui);
 
         let (event_tx_async_to_gui, event_rx_gui) = mpsc::channel(100);
         let (event_tx_gui_to_async, event_rx_async) = mpsc::channel(100);
@@ -57,14 +75,17 @@ impl RcpClientApp {
             is_connected: false,
             connecting: false,
             connection_status: "Disconnected".to_string(),
-            password: String::new(),
+            password: Secret::default(),
             show_password: false,
             last_validated_address: None,
             connection_time: None,
+            pending_prompt: None,
+            prompt_input: Secret::default(),
+            device_code: None,
         }));
 
         let status = Arc::new(Mutex::new("Ready".to_string()));
-        let client_arc = Arc::new(Mutex::new(None::<protocol::Client>));
+        let client_arc = Arc::new(Mutex::new(None::<Arc<protocol::Client>>));
 
         let rt_handle_clone = rt_handle.clone();
         let status_clone = status.clone();
@@ -79,6 +100,7 @@ impl RcpClientApp {
         rt_handle.spawn(async move {
             run_gui_inner(
                 config_clone_for_async,
+                config_path,
                 auto_connect_for_async,
                 event_tx_for_async_logic,
                 event_rx_async,
@@ -97,10 +119,10 @@ impl RcpClientApp {
             server_port: config.server.port.to_string(),
             auth_method: config.auth.method.clone(),
             username: config.auth.username.clone().unwrap_or_default(),
-            password: String::new(),
+            password: Secret::default(),
             token: String::new(),
             psk_identity: String::new(),
-            psk_key: String::new(),
+            psk_key: Secret::default(),
             use_tls: config.server.use_tls,
             remember_credentials: config.auth.save_credentials,
             auto_connect: config.ui.auto_connect, // Use original config
@@ -114,6 +136,15 @@ impl RcpClientApp {
             rt_handle,
             shutdown_tx: Some(shutdown_tx),
             connection_history: loaded_history, // Assign Vec<ConnectionEntry>
+            audit_log: AuditLog::from_config(&config.audit),
+            audit_records: Vec::new(),
+            show_audit_log: false,
+            selected_profile: config.active_profile.clone(),
+            config,
+            config_issues: Vec::new(),
+            config_updates,
+            link_input: String::new(),
+            show_open_link: false,
         }
     }
 
@@ -131,9 +162,21 @@ impl RcpClientApp {
                 ui.heading("RCP Client");
                 ui.separator();
                 if ui.button("Save Config").clicked() {
-                    if let Err(e) = self.event_tx.try_send(AppEvent::SaveConfig) {
-                        eprintln!("Failed to send SaveConfig event: {}", e);
-                    }
+                    self.save_config();
+                }
+                if ui
+                    .button("Copy Link")
+                    .on_hover_text("Copy an rcp:// link to this server, without any secrets")
+                    .clicked()
+                {
+                    self.copy_link(ctx);
+                }
+                if ui.button("Open Link").clicked() {
+                    self.show_open_link = true;
+                }
+                if self.audit_log.is_some() && ui.button("Audit Log").clicked() {
+                    self.show_audit_log = true;
+                    self.reload_audit_log();
                 }
             });
         });
@@ -143,9 +186,13 @@ impl RcpClientApp {
             ui.add_space(10.0);
 
             if !is_connected && !is_connecting {
-                // Server Panel (8 arguments)
+                // Server Panel
+                let profiles = self.config.profile_names();
+                let previous_profile = self.selected_profile.clone();
                 crate::ui::widgets::server_panel::draw_server_panel(
                     ui,
+                    &profiles,
+                    &mut self.selected_profile,
                     &mut self.server_address,
                     &mut self.server_port,
                     &mut self.use_tls,
@@ -154,6 +201,9 @@ impl RcpClientApp {
                     &self.connection_history,
                     &self.app_state,
                 );
+                if self.selected_profile != previous_profile {
+                    self.select_profile(self.selected_profile.clone());
+                }
 
                 // Auth Panel (7 arguments)
                 crate::ui::widgets::auth_panel::draw_auth_panel(
@@ -188,22 +238,21 @@ impl RcpClientApp {
                     &self.rt_handle,
                     &self.app_state,
                 );
-            } else {
-                crate::ui::widgets::connection_panel::draw_connection_panel_controls(
-                    ui,
-                    &self.server_address, // Pass current server_address
-                    &self.server_port,    // Pass current server_port
-                    &mut self.auto_connect,
-                    &mut self.auto_reconnect,
-                    is_connecting,
-                    &self.status_message,
-                    &self.event_tx,
-                );
+            } else if crate::ui::widgets::connection_panel::draw_connection_panel_controls(
+                ui,
+                &self.server_address, // Pass current server_address
+                &self.server_port,    // Pass current server_port
+                &mut self.auto_connect,
+                &mut self.auto_reconnect,
+                is_connecting,
+                &self.status_message,
+            ) {
+                self.connect();
             }
 
             // Action Panel (10 arguments, only if connected)
             if is_connected {
-                crate::ui::widgets::action_panel::draw_action_panel(
+                let action = crate::ui::widgets::action_panel::draw_action_panel(
                     ui,
                     &self.server_address,
                     &self.server_port,
@@ -215,6 +264,11 @@ impl RcpClientApp {
                     &self.status_message,
                     self.event_tx.clone(),
                 );
+                match action {
+                    Some(PanelAction::Connect) => self.connect(),
+                    Some(PanelAction::SaveConfig) => self.save_config(),
+                    None => {}
+                }
             }
 
             ui.add_space(10.0);
@@ -223,12 +277,257 @@ impl RcpClientApp {
             if is_connecting {
                 ui.spinner();
             }
+            for issue in &self.config_issues {
+                let color = match issue.severity {
+                    Severity::Error => egui::Color32::RED,
+                    Severity::Warning => egui::Color32::YELLOW,
+                };
+                ui.colored_label(color, issue.to_string());
+            }
         });
+
+        crate::ui::widgets::auth_panel::draw_prompt_dialog(
+            ctx,
+            &self.event_tx,
+            &self.rt_handle,
+            &self.app_state,
+        );
+        crate::ui::widgets::auth_panel::draw_device_code_panel(ctx, &self.app_state);
+
+        if crate::ui::widgets::server_panel::draw_open_link_window(
+            ctx,
+            &mut self.show_open_link,
+            &mut self.link_input,
+        ) {
+            self.open_link();
+        }
+
+        if crate::ui::widgets::audit_panel::draw_audit_log_window(
+            ctx,
+            &mut self.show_audit_log,
+            &self.audit_records,
+        ) {
+            self.reload_audit_log();
+        }
+    }
+
+    /// Read the audit log again for the audit log window
+    fn reload_audit_log(&mut self) {
+        if let Some(audit_log) = &self.audit_log {
+            match audit_log.read() {
+                Ok(records) => self.audit_records = records,
+                Err(e) => self.status_message = format!("Failed to read audit log: {}", e),
+            }
+        }
+    }
+
+    /// Use a configuration reloaded after the config files changed
+    ///
+    /// UI settings apply immediately; server and auth settings are passed to
+    /// the async task and used from the next connect.
+    fn apply_config_update(&mut self, ctx: &egui::Context, update: ConfigUpdate) {
+        let connection_changed = update.changes_connection();
+        let (config, changed) = match update {
+            ConfigUpdate::Applied { config, changed } => (*config, changed),
+            ConfigUpdate::Rejected(reason) => {
+                log::warn!("Rejected configuration change: {}", reason);
+                self.status_message =
+                    "Configuration change rejected, keeping the previous settings".to_string();
+                if let Ok(mut status) = self.status.try_lock() {
+                    *status = self.status_message.clone();
+                }
+                return;
+            }
+        };
+
+        // Keep the profile picked in the GUI and the settings only made at startup
+        let mut config = match &self.selected_profile {
+            Some(name) => config.with_profile(name).unwrap_or(config),
+            None => config.without_profile(),
+        };
+        self.selected_profile = config.active_profile.clone();
+        config.server.accept_server_key = self.config.server.accept_server_key;
+        config.ui.auto_connect = false;
+
+        if changed.iter().any(|section| section == "ui") {
+            apply_ui_settings(ctx, &config.ui);
+            self.auto_reconnect = config.ui.auto_reconnect;
+        }
+        if changed.iter().any(|section| section == "audit") {
+            self.audit_log = AuditLog::from_config(&config.audit);
+        }
+
+        let (is_connected, is_connecting) = match self.app_state.try_lock() {
+            Ok(state) => (state.is_connected, state.connecting),
+            Err(_) => (true, true),
+        };
+        let mut message = "Configuration reloaded".to_string();
+        if connection_changed {
+            if is_connected || is_connecting {
+                message.push_str("; server and login changes apply on the next connect");
+            } else {
+                self.fill_panels(&config);
+            }
+            if let Err(e) = self
+                .event_tx
+                .try_send(AppEvent::ConfigReloaded(Box::new(config.clone())))
+            {
+                eprintln!("Failed to send ConfigReloaded event: {}", e);
+            }
+        }
+
+        self.config = config;
+        self.config_issues.clear();
+        self.status_message = message;
+        if let Ok(mut status) = self.status.try_lock() {
+            *status = self.status_message.clone();
+        }
+    }
+
+    /// The configuration with the settings entered in the panels
+    fn panel_config(&self) -> Result<ClientConfig, String> {
+        let mut config = self.config.clone();
+        config.server.address = self.server_address.trim().to_string();
+        config.server.use_tls = self.use_tls;
+        config.auth.method = self.auth_method.clone();
+        config.auth.username = Some(self.username.trim().to_string()).filter(|u| !u.is_empty());
+        config.auth.save_credentials = self.remember_credentials;
+        config.server.port = self
+            .server_port
+            .trim()
+            .parse()
+            .map_err(|_| "port must be a number between 1-65535".to_string())?;
+        Ok(config)
+    }
+
+    /// Connect with the settings and password entered in the panels
+    fn connect(&mut self) {
+        let config = match self.panel_config() {
+            Ok(config) => config,
+            Err(e) => {
+                self.status_message = format!("Not connecting: {}", e);
+                return;
+            }
+        };
+        let password = match self.app_state.try_lock() {
+            Ok(state) => state.password.clone(),
+            Err(_) => Secret::default(),
+        };
+
+        // Show the connecting state until the async task reports back
+        let event = AppEvent::Connect(Box::new(config), password);
+        self.handle_event(event.clone());
+        if let Err(e) = self.event_tx.try_send(event) {
+            eprintln!("Failed to send Connect event: {}", e);
+        }
+    }
+
+    /// Fill in the panels from a configuration
+    fn fill_panels(&mut self, config: &ClientConfig) {
+        self.server_address = config.server.address.clone();
+        self.server_port = config.server.port.to_string();
+        self.use_tls = config.server.use_tls;
+        self.auth_method = config.auth.method.clone();
+        self.username = config.auth.username.clone().unwrap_or_default();
+        self.remember_credentials = config.auth.save_credentials;
+    }
+
+    /// Validate the settings entered in the panels and save the changed ones
+    /// to the user config file if they are valid
+    fn save_config(&mut self) {
+        let edited = match self.panel_config() {
+            Ok(edited) => edited,
+            Err(e) => {
+                self.status_message = format!("Configuration not saved: {}", e);
+                return;
+            }
+        };
+
+        let report = edited.validate();
+        self.config_issues = report.issues.clone();
+        if !report.is_ok() {
+            self.status_message = format!(
+                "Configuration not saved: {} problem(s) found",
+                report.errors().count()
+            );
+            return;
+        }
+
+        let changes = config::changed_settings(&self.config, &edited);
+        if changes.is_empty() {
+            self.status_message = "No changes to save".to_string();
+            return;
+        }
+
+        self.status_message = "Saving configuration...".to_string();
+        if let Err(e) = self.event_tx.try_send(AppEvent::SaveConfig(changes)) {
+            eprintln!("Failed to send SaveConfig event: {}", e);
+        }
+    }
+
+    /// Switch to the named profile, or to no profile, and fill in its settings
+    fn select_profile(&mut self, name: Option<String>) {
+        let config = match &name {
+            Some(name) => match self.config.with_profile(name) {
+                Ok(config) => config,
+                Err(e) => {
+                    self.status_message = e.to_string();
+                    return;
+                }
+            },
+            None => self.config.without_profile(),
+        };
+
+        self.fill_panels(&config);
+        self.selected_profile = name.clone();
+        self.config = config;
+
+        if let Err(e) = self.event_tx.try_send(AppEvent::SelectProfile(name)) {
+            eprintln!("Failed to send SelectProfile event: {}", e);
+        }
+    }
+
+    /// Copy a link to the server and login entered in the panels, without any secrets
+    fn copy_link(&mut self, ctx: &egui::Context) {
+        match self.panel_config() {
+            Ok(config) => {
+                let link = ConnectionLink::from_config(&config).to_string();
+                ctx.output_mut(|output| output.copied_text = link.clone());
+                self.status_message = format!("Copied {}", link);
+            }
+            Err(e) => self.status_message = format!("No link copied: {}", e),
+        }
+    }
+
+    /// Fill in the panels from the pasted connection link and use it for the next connect
+    fn open_link(&mut self) {
+        let opened = self
+            .link_input
+            .parse::<ConnectionLink>()
+            .and_then(|link| Ok((link.apply(&self.config)?, link)));
+        let (config, link) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.status_message = format!("{:#}", e);
+                return;
+            }
+        };
+
+        self.fill_panels(&config);
+        self.selected_profile = config.active_profile.clone();
+        self.config = config;
+        self.show_open_link = false;
+        self.link_input.clear();
+        self.status_message = "Opened connection link".to_string();
+
+        if let Err(e) = self.event_tx.try_send(AppEvent::OpenLink(link)) {
+            eprintln!("Failed to send OpenLink event: {}", e);
+        }
     }
 
     fn handle_event(&mut self, event: AppEvent) {
         match event {
-            AppEvent::Connect => {
+            AppEvent::Connect(..) => {
                 println!(
                     "GUI: Connect event received, should be handled by async task via channel"
                 );
@@ -312,7 +611,7 @@ impl RcpClientApp {
                     *status_mg = "Disconnected".to_string();
                 }
             }
-            AppEvent::SaveConfig => {
+            AppEvent::SaveConfig(_) => {
                 println!("GUI: SaveConfig event received by GUI event handler.");
                 self.status_message = "Configuration save requested.".to_string();
                 if let Ok(mut status_mg) = self.status.try_lock() {
@@ -342,14 +641,17 @@ impl RcpClientApp {
                     reason
                 );
             }
-            AppEvent::ConfigSaved => {
-                println!("GUI: ConfigSaved event - not fully handled yet.");
+            AppEvent::ConfigSaved(path) => {
+                self.status_message = format!("Configuration saved to {}", path.display());
+                if let Ok(mut status_mg) = self.status.try_lock() {
+                    *status_mg = self.status_message.clone();
+                }
             }
             AppEvent::ConfigSaveFailed(reason) => {
-                println!(
-                    "GUI: ConfigSaveFailed event: {} - not fully handled yet.",
-                    reason
-                );
+                self.status_message = format!("Configuration not saved: {}", reason);
+                if let Ok(mut status_mg) = self.status.try_lock() {
+                    *status_mg = self.status_message.clone();
+                }
             }
             AppEvent::UpdateConnectionState(is_connected) => {
                 println!(
@@ -366,11 +668,8 @@ impl RcpClientApp {
             AppEvent::UpdateConnectionHistory(..) => {
                 println!("GUI: UpdateConnectionHistory event - not fully handled yet.");
             }
-            AppEvent::SaveCredentials => {
-                println!("GUI: SaveCredentials event - not fully handled yet.");
-            }
-            AppEvent::ClearCredentials => {
-                println!("GUI: ClearCredentials event - not fully handled yet.");
+            AppEvent::SaveCredentials | AppEvent::ClearCredentials => {
+                // Handled by the async task, which reports back with StatusUpdate
             }
             _ => {
                 // log::debug!("Unhandled AppEvent in GUI: {:?}", event);
@@ -396,6 +695,16 @@ impl eframe::App for RcpClientApp {
             self.handle_event(event); // This takes &mut self
         }
 
+        let mut config_updates = Vec::new();
+        if let Some(rx) = &mut self.config_updates {
+            while let Ok(update) = rx.try_recv() {
+                config_updates.push(update);
+            }
+        }
+        for update in config_updates {
+            self.apply_config_update(ctx, update);
+        }
+
         // Sync status from shared Arc<Mutex<String>>
         // This part should be fine as it's sequential to handle_event
         if let Ok(status_guard) = self.status.try_lock() {
@@ -420,17 +729,33 @@ impl eframe::App for RcpClientApp {
     }
 }
 
+/// Apply the dark mode and scale settings, on top of a 30% larger base scale
+fn apply_ui_settings(ctx: &egui::Context, ui: &UiConfig) {
+    ctx.set_visuals(if ui.dark_mode {
+        egui::Visuals::dark()
+    } else {
+        egui::Visuals::light()
+    });
+    ctx.set_pixels_per_point(1.3 * ui.scale_factor);
+}
+
 async fn run_gui_inner(
-    _config: ClientConfig,
+    mut config: ClientConfig,
+    config_path: PathBuf,
     auto_connect_initial: bool,
     event_tx_to_gui: mpsc::Sender<AppEvent>,
     mut event_rx_from_gui: mpsc::Receiver<AppEvent>,
     _rt_handle: Handle,
     status_arc: Arc<Mutex<String>>,
     app_state_arc: Arc<Mutex<AppState>>,
-    _client_arc: Arc<Mutex<Option<protocol::Client>>>,
+    client_arc: Arc<Mutex<Option<Arc<protocol::Client>>>>,
 ) {
     let (_shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+    let prompter = Arc::new(GuiPrompter::new(app_state_arc.clone()));
+    let sessions = Arc::new(auth::SessionStore::from_config(&config.auth));
+    let mut session_refresh = tokio::time::interval(tokio::time::Duration::from_secs(30));
+    // Later connections in this session are audited as reconnects
+    let mut has_connected = false;
 
     // Auto-connect is explicitly disabled, the if-condition will never be true
     // but we keep the code structure for future reference
@@ -440,7 +765,8 @@ async fn run_gui_inner(
             let mut app_state = app_state_arc.lock().await;
             app_state.connecting = true;
         }
-        if let Err(e) = event_tx_to_gui.send(AppEvent::Connect).await {
+        let event = AppEvent::Connect(Box::new(config.clone()), Secret::default());
+        if let Err(e) = event_tx_to_gui.send(event).await {
             eprintln!("run_gui_inner: Failed to send initial Connect event: {}", e);
         }
     }
@@ -451,43 +777,90 @@ async fn run_gui_inner(
                 println!("Async task received event: {:?}", event);
 
                 match event {
-                    AppEvent::Connect => {
+                    AppEvent::Connect(panel_config, password) => {
                         println!("Async task: Handling Connect event");
                         app_state_arc.lock().await.connecting = true;
                         status_arc.lock().await.clear();
                         status_arc.lock().await.push_str("Connecting...");
 
-                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await; // Shorter delay for testing
-
-                        let connected = true;
-                        if connected {
-                            let mut app_state_locked = app_state_arc.lock().await;
-                            app_state_locked.is_connected = true;
-                            app_state_locked.connecting = false;
-                            app_state_locked.set_connected(true); // Use method to set time and status string
-                            drop(app_state_locked); // Release lock before sending event
+                        // Later disconnects, session refreshes and saved
+                        // credentials are for the server entered in the panels
+                        config = *panel_config;
 
-                            status_arc.lock().await.clear();
-                            status_arc.lock().await.push_str("Connected successfully!");
-                            if let Err(e) = event_tx_to_gui.send(AppEvent::ConnectionSucceeded).await {
-                                eprintln!("Failed to send ConnectionSucceeded: {}", e);
-                            }
+                        // Connect in a separate task so that prompt answers
+                        // from the GUI can still be received by this loop
+                        let config = config.clone();
+                        let prompter = prompter.clone();
+                        let sessions = sessions.clone();
+                        let event_tx_to_gui = event_tx_to_gui.clone();
+                        let status_arc = status_arc.clone();
+                        let app_state_arc = app_state_arc.clone();
+                        let client_arc = client_arc.clone();
+                        let audit_event = if has_connected {
+                            AuditEvent::Reconnect
                         } else {
-                            let mut app_state_locked = app_state_arc.lock().await;
-                            app_state_locked.is_connected = false;
-                            app_state_locked.connecting = false;
-                            app_state_locked.connection_status = "Failed to connect".to_string();
-                            drop(app_state_locked); // Release lock
-
-                            status_arc.lock().await.clear();
-                            status_arc.lock().await.push_str("Connection failed.");
-                            if let Err(e) = event_tx_to_gui.send(AppEvent::ConnectionFailed("Simulated failure".to_string())).await {
-                                eprintln!("Failed to send ConnectionFailed: {}", e);
+                            AuditEvent::Connect
+                        };
+                        has_connected = true;
+
+                        tokio::spawn(async move {
+                            match connect_and_authenticate(&config, &password, prompter, sessions, audit_event).await {
+                                Ok(client) => {
+                                    *client_arc.lock().await = Some(Arc::new(client));
+
+                                    let mut app_state_locked = app_state_arc.lock().await;
+                                    app_state_locked.is_connected = true;
+                                    app_state_locked.connecting = false;
+                                    app_state_locked.set_connected(true); // Use method to set time and status string
+                                    drop(app_state_locked); // Release lock before sending event
+
+                                    status_arc.lock().await.clear();
+                                    status_arc.lock().await.push_str("Connected successfully!");
+                                    if let Err(e) = event_tx_to_gui.send(AppEvent::ConnectionSucceeded).await {
+                                        eprintln!("Failed to send ConnectionSucceeded: {}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    let mut app_state_locked = app_state_arc.lock().await;
+                                    app_state_locked.is_connected = false;
+                                    app_state_locked.connecting = false;
+                                    app_state_locked.connection_status = "Failed to connect".to_string();
+                                    drop(app_state_locked); // Release lock
+
+                                    status_arc.lock().await.clear();
+                                    status_arc.lock().await.push_str("Connection failed.");
+                                    if let Err(e) = event_tx_to_gui.send(AppEvent::ConnectionFailed(e.to_string())).await {
+                                        eprintln!("Failed to send ConnectionFailed: {}", e);
+                                    }
+                                }
                             }
-                        }
+                        });
+                    }
+                    AppEvent::SubmitPrompt(secret) => {
+                        prompter.answer(PromptAnswer::Secret(secret)).await;
+                    }
+                    AppEvent::ConfirmPrompt(answer) => {
+                        prompter.answer(PromptAnswer::Confirm(answer)).await;
+                    }
+                    AppEvent::CancelPrompt => {
+                        prompter.answer(PromptAnswer::Cancelled).await;
                     }
                     AppEvent::Disconnect => {
                         println!("Async task: Handling Disconnect event");
+                        let client = client_arc.lock().await.take();
+                        if let Some(client) = client {
+                            // A session refresh in flight keeps its own
+                            // reference, the connection closes when it ends
+                            let result = match Arc::try_unwrap(client) {
+                                Ok(client) => client.close().await,
+                                Err(_) => Ok(()),
+                            };
+                            audit::record_connection(&config, AuditEvent::Disconnect, &result);
+                            if let Err(e) = result {
+                                eprintln!("Failed to close connection: {}", e);
+                            }
+                        }
+
                         let mut app_state_locked = app_state_arc.lock().await;
                         app_state_locked.is_connected = false;
                         app_state_locked.connecting = false;
@@ -500,17 +873,88 @@ async fn run_gui_inner(
                              eprintln!("Failed to send DisconnectedConfirmed event: {}", e);
                         }
                     }
-                    AppEvent::SaveConfig => {
-                        println!("Async task: SaveConfig event received.");
-                        status_arc.lock().await.clear();
-                        status_arc.lock().await.push_str("Configuration saved (simulated).");
-                         if let Err(e) = event_tx_to_gui.send(AppEvent::StatusUpdate("Config saved.".to_string())).await {
-                             eprintln!("Failed to send StatusUpdate event: {}", e);
+                    AppEvent::SelectProfile(name) => {
+                        let selected = match &name {
+                            Some(name) => config.with_profile(name),
+                            None => Ok(config.without_profile()),
+                        };
+                        let message = match selected {
+                            Ok(selected) => {
+                                config = selected;
+                                match name {
+                                    Some(name) => format!("Using profile {}", name),
+                                    None => "Not using a profile".to_string(),
+                                }
+                            }
+                            Err(e) => e.to_string(),
+                        };
+                        if let Err(e) = event_tx_to_gui.send(AppEvent::StatusUpdate(message)).await {
+                            eprintln!("Failed to send StatusUpdate event: {}", e);
+                        }
+                    }
+                    AppEvent::ConfigReloaded(reloaded) => {
+                        config = *reloaded;
+                    }
+                    AppEvent::OpenLink(link) => match link.apply(&config) {
+                        Ok(linked) => config = linked,
+                        Err(e) => {
+                            if let Err(e) = event_tx_to_gui.send(AppEvent::StatusUpdate(e.to_string())).await {
+                                eprintln!("Failed to send StatusUpdate event: {}", e);
+                            }
+                        }
+                    },
+                    AppEvent::SaveConfig(changes) => {
+                        // Only the changed keys are written, and the file is
+                        // replaced in one step under its lock
+                        let event = match config::update_config_file(&config_path, &changes).await {
+                            Ok(()) => AppEvent::ConfigSaved(config_path.clone()),
+                            Err(e) => AppEvent::ConfigSaveFailed(format!("{:#}", e)),
+                        };
+                        if let Err(e) = event_tx_to_gui.send(event).await {
+                            eprintln!("Failed to send config save result: {}", e);
+                        }
+                    }
+                    AppEvent::SaveCredentials => {
+                        let password = app_state_arc.lock().await.password.clone();
+                        let message = if password.is_empty() {
+                            "No password entered to save.".to_string()
+                        } else {
+                            match save_credentials(&config, prompter.clone(), &password).await {
+                                Ok(()) => "Credentials saved.".to_string(),
+                                Err(e) => format!("Failed to save credentials: {}", e),
+                            }
+                        };
+                        if let Err(e) = event_tx_to_gui.send(AppEvent::StatusUpdate(message)).await {
+                            eprintln!("Failed to send StatusUpdate event: {}", e);
+                        }
+                    }
+                    AppEvent::ClearCredentials => {
+                        let message = match clear_credentials(&config, prompter.clone(), &sessions).await {
+                            Ok(()) => "Saved credentials cleared.".to_string(),
+                            Err(e) => format!("Failed to clear credentials: {}", e),
+                        };
+                        if let Err(e) = event_tx_to_gui.send(AppEvent::StatusUpdate(message)).await {
+                            eprintln!("Failed to send StatusUpdate event: {}", e);
                         }
                     }
                     _ => {}
                 }
             }
+            _ = session_refresh.tick() => {
+                // Refresh in a separate task, without holding the client lock,
+                // so that other events are not held up by the exchange
+                let client = client_arc.lock().await.clone();
+                if let Some(client) = client {
+                    let username = config.auth.username_or_current();
+                    let options = auth::AuthOptions::from_config(&config, prompter.clone())
+                        .with_sessions(sessions.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = auth::session::refresh_if_needed(&client, &username, &options).await {
+                            eprintln!("Failed to refresh session token: {}", e);
+                        }
+                    });
+                }
+            }
             _ = &mut shutdown_rx => {
                 println!("Async task shutting down");
                 break;
@@ -518,3 +962,78 @@ async fn run_gui_inner(
         }
     }
 }
+
+/// Save the password entered in the auth panel for the configured user and server
+async fn save_credentials(
+    config: &ClientConfig,
+    prompter: Arc<GuiPrompter>,
+    password: &Secret,
+) -> anyhow::Result<()> {
+    let options = auth::AuthOptions::from_config(config, prompter);
+    auth::PasswordAuthProvider::new(&config.auth.username_or_current())
+        .with_options(options)
+        .save_password_to_store(password)
+        .await?;
+    Ok(())
+}
+
+/// Remove the saved password, PSK and session token for the configured user and server
+async fn clear_credentials(
+    config: &ClientConfig,
+    prompter: Arc<GuiPrompter>,
+    sessions: &auth::SessionStore,
+) -> anyhow::Result<()> {
+    let username = config.auth.username_or_current();
+    let options = auth::AuthOptions::from_config(config, prompter);
+
+    auth::PasswordAuthProvider::new(&username)
+        .with_options(options.clone())
+        .forget_password()
+        .await?;
+    auth::PskAuthProvider::new()
+        .with_username(&username)
+        .with_options(options.clone())
+        .forget_key()
+        .await?;
+    auth::OAuthAuthProvider::new(&username)
+        .with_options(options)
+        .forget_refresh_token()
+        .await?;
+    sessions.remove(
+        &username,
+        &auth::AuthTarget::new(&config.server.address, config.server.port),
+    );
+    Ok(())
+}
+
+/// Connect to the configured server and run the authentication exchange
+///
+/// A session token from an earlier connection is presented first, so that a
+/// reconnect does not re-run the provider flow. A `password` entered in the
+/// auth panel is used instead of the stored one. The connection is recorded
+/// in the audit log as `event`.
+async fn connect_and_authenticate(
+    config: &ClientConfig,
+    password: &Secret,
+    prompter: Arc<GuiPrompter>,
+    sessions: Arc<auth::SessionStore>,
+    event: AuditEvent,
+) -> anyhow::Result<protocol::Client> {
+    let client = protocol::Client::connect_with_config(&config.server, Some(&*prompter)).await;
+    audit::record_connection(config, event, &client);
+    let client = client?;
+
+    let username = config.auth.username_or_current();
+    let options = auth::AuthOptions::from_config(config, prompter).with_sessions(sessions);
+    let options = if password.is_empty() {
+        options
+    } else {
+        options.with_password(password.clone())
+    };
+
+    if !auth::negotiate::authenticate(&client, &config.auth, &username, &options).await? {
+        anyhow::bail!("Authentication rejected");
+    }
+
+    Ok(client)
+}
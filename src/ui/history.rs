@@ -1,54 +1,94 @@
+use crate::config::{lock_file, paths, recover_from_backup, write_atomic};
 use crate::ui::models::ConnectionEntry;
 use log::error;
 use std::fs;
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Most entries kept in the connection history
+const MAX_HISTORY: usize = 10;
 
 /// Load connection history from disk
+///
+/// A damaged history file is read from the copy last written next to it.
 pub fn load_connection_history() -> Vec<ConnectionEntry> {
-    // Get history file path
+    read_history(&get_history_file_path())
+}
+
+/// Save connection history, merging entries saved meanwhile by other instances
+pub fn save_connection_history(history: &[ConnectionEntry]) {
     let history_path = get_history_file_path();
+    let _lock = match lock_file(&history_path) {
+        Ok(lock) => lock,
+        Err(e) => {
+            error!("Failed to lock connection history: {:#}", e);
+            return;
+        }
+    };
 
-    // If file doesn't exist, return empty vector
-    if !history_path.exists() {
-        return Vec::new();
+    let merged = merge_connection_history(history, read_history(&history_path));
+    write_history(&history_path, &merged);
+}
+
+/// Remove every entry from the connection history, including other instances' ones
+pub fn clear_connection_history() {
+    let history_path = get_history_file_path();
+    match lock_file(&history_path) {
+        Ok(_lock) => write_history(&history_path, &[]),
+        Err(e) => error!("Failed to lock connection history: {:#}", e),
     }
+}
 
-    // Attempt to read and deserialize history file
-    match fs::read_to_string(&history_path) {
-        Ok(content) => match serde_json::from_str::<Vec<ConnectionEntry>>(&content) {
-            Ok(history) => history,
-            Err(e) => {
-                error!("Failed to parse connection history: {}", e);
-                Vec::new()
-            }
-        },
-        Err(e) => {
-            error!("Failed to read connection history: {}", e);
-            Vec::new()
+/// Combine two histories, keeping the most recent entry for each server
+pub fn merge_connection_history(
+    ours: &[ConnectionEntry],
+    theirs: Vec<ConnectionEntry>,
+) -> Vec<ConnectionEntry> {
+    let mut merged = theirs;
+    for entry in ours {
+        match merged
+            .iter_mut()
+            .find(|other| other.address == entry.address && other.port == entry.port)
+        {
+            Some(other) if other.last_connected < entry.last_connected => *other = entry.clone(),
+            Some(_) => {}
+            None => merged.push(entry.clone()),
         }
     }
+
+    merged.sort_by_key(|entry| std::cmp::Reverse(entry.last_connected));
+    merged.truncate(MAX_HISTORY);
+    merged
 }
 
-/// Save connection history to config
-pub fn save_connection_history(history: &[ConnectionEntry]) {
-    // Get history file path
-    let history_path = get_history_file_path();
+fn read_history(history_path: &Path) -> Vec<ConnectionEntry> {
+    let content = match fs::read(history_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            error!("Failed to read connection history: {}", e);
+            return Vec::new();
+        }
+    };
 
-    // Ensure parent directory exists
-    if let Some(parent) = history_path.parent() {
-        if !parent.exists() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                error!("Failed to create history directory: {}", e);
-                return;
-            }
+    match serde_json::from_slice::<Vec<ConnectionEntry>>(&content) {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Failed to parse connection history: {}", e);
+            recover_from_backup(history_path, |content| {
+                serde_json::from_str::<Vec<ConnectionEntry>>(content)
+            })
+            .map(|(_, history)| history)
+            .unwrap_or_default()
         }
     }
+}
 
-    // Serialize and save history
+fn write_history(history_path: &Path, history: &[ConnectionEntry]) {
     match serde_json::to_string_pretty(history) {
         Ok(content) => {
-            if let Err(e) = fs::write(&history_path, content) {
-                error!("Failed to write connection history: {}", e);
+            if let Err(e) = write_atomic(history_path, content.as_bytes()) {
+                error!("Failed to write connection history: {:#}", e);
             }
         }
         Err(e) => {
@@ -59,10 +99,7 @@ pub fn save_connection_history(history: &[ConnectionEntry]) {
 
 /// Get connection history file path
 pub fn get_history_file_path() -> PathBuf {
-    dirs::config_dir()
-        .expect("Could not find config directory")
-        .join("rcp_client")
-        .join("connection_history.json")
+    paths::moved_file(&paths::state_dir(), "connection_history.json")
 }
 
 /// Add or update connection in history
@@ -104,10 +141,8 @@ pub fn add_to_connection_history(
     // Sort by last connected time (most recent first)
     history.sort_by(|a, b| b.last_connected.cmp(&a.last_connected));
 
-    // Limit history to 10 entries
-    if history.len() > 10 {
-        history.truncate(10);
-    }
+    // Limit history to the most recent entries
+    history.truncate(MAX_HISTORY);
 
     // Save updated history
     save_connection_history(history);
@@ -4,8 +4,11 @@ mod events;
 pub mod gui; // Make the gui module public
 mod history;
 mod models;
+mod prompter;
+pub mod shell;
 mod widgets;
 
 pub use app::App;
 pub use event_app::EventBasedApp;
+pub use shell::Shell;
 // Make all public items in gui module available
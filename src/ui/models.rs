@@ -1,3 +1,4 @@
+use crate::auth::{DeviceCodePrompt, Secret};
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
@@ -7,10 +8,25 @@ pub struct AppState {
     pub is_connected: bool,
     pub connecting: bool,
     pub connection_status: String, // Ensure this field exists
-    pub password: String,
+    pub password: Secret,
     pub show_password: bool,
     pub last_validated_address: Option<String>,
     pub connection_time: Option<SystemTime>,
+    /// Question from the authentication flow waiting for the user
+    pub pending_prompt: Option<PendingPrompt>,
+    /// Input being entered for the pending prompt
+    pub prompt_input: Secret,
+    /// Where to sign in while the OAuth2 device flow waits for the user
+    pub device_code: Option<DeviceCodePrompt>,
+}
+
+/// A question from the authentication flow shown in a dialog
+#[derive(Debug, Clone)]
+pub enum PendingPrompt {
+    /// Enter a secret such as a password or one-time code
+    Secret(String),
+    /// Answer a yes/no question
+    Confirm(String),
 }
 
 impl AppState {
@@ -19,10 +35,13 @@ impl AppState {
             is_connected: false,
             connecting: false,
             connection_status: "Disconnected".to_string(), // Initialize
-            password: String::new(),
+            password: Secret::default(),
             show_password: false,
             last_validated_address: None,
             connection_time: None,
+            pending_prompt: None,
+            prompt_input: Secret::default(),
+            device_code: None,
         }
     }
 
@@ -0,0 +1,92 @@
+use crate::auth::{AuthError, DeviceCodePrompt, Prompter, Secret, SecretPrompt};
+use crate::ui::models::{AppState, PendingPrompt};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+
+/// Answer to a prompt shown in the GUI
+#[derive(Debug)]
+pub enum PromptAnswer {
+    /// Secret entered by the user
+    Secret(Secret),
+    /// Answer to a yes/no question
+    Confirm(bool),
+    /// Dialog was cancelled
+    Cancelled,
+}
+
+/// Prompter that asks the user in a modal dialog
+///
+/// While a prompt is pending, `AppState::pending_prompt` is set so that the
+/// auth panel shows the dialog. The dialog answers through
+/// `AppEvent::SubmitPrompt`, `AppEvent::ConfirmPrompt` and
+/// `AppEvent::CancelPrompt`, which the async task forwards to
+/// [`GuiPrompter::answer`]. Device codes are shown through
+/// `AppState::device_code` until sign-in finishes.
+pub struct GuiPrompter {
+    app_state: Arc<Mutex<AppState>>,
+    pending: Mutex<Option<oneshot::Sender<PromptAnswer>>>,
+}
+
+impl GuiPrompter {
+    /// Create a new GUI prompter
+    pub fn new(app_state: Arc<Mutex<AppState>>) -> Self {
+        Self {
+            app_state,
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// Answer the pending prompt
+    pub async fn answer(&self, answer: PromptAnswer) {
+        if let Some(tx) = self.pending.lock().await.take() {
+            let _ = tx.send(answer);
+        }
+    }
+
+    /// Show a prompt and wait for the user's answer
+    async fn ask(&self, prompt: PendingPrompt) -> PromptAnswer {
+        let (tx, rx) = oneshot::channel();
+        *self.pending.lock().await = Some(tx);
+
+        {
+            let mut state = self.app_state.lock().await;
+            state.pending_prompt = Some(prompt);
+            state.prompt_input = Secret::default();
+        }
+
+        let answer = rx.await.unwrap_or(PromptAnswer::Cancelled);
+
+        {
+            let mut state = self.app_state.lock().await;
+            state.pending_prompt = None;
+            state.prompt_input = Secret::default();
+        }
+
+        answer
+    }
+}
+
+#[async_trait]
+impl Prompter for GuiPrompter {
+    async fn prompt_secret(&self, prompt: &SecretPrompt) -> Result<Secret, AuthError> {
+        match self
+            .ask(PendingPrompt::Secret(prompt.message.clone()))
+            .await
+        {
+            PromptAnswer::Secret(secret) if !secret.is_empty() => Ok(secret),
+            _ => Err(AuthError::UserDeclined),
+        }
+    }
+
+    async fn confirm(&self, question: &str) -> Result<bool, AuthError> {
+        match self.ask(PendingPrompt::Confirm(question.to_string())).await {
+            PromptAnswer::Confirm(answer) => Ok(answer),
+            _ => Ok(false),
+        }
+    }
+
+    async fn show_device_code(&self, prompt: Option<&DeviceCodePrompt>) {
+        self.app_state.lock().await.device_code = prompt.cloned();
+    }
+}
@@ -0,0 +1,543 @@
+//! Interactive shell for RCP commands
+//!
+//! `rcp shell` reads commands of the form `name {"json": "params"}`, sends
+//! them with [`protocol::Client`] and pretty-prints the responses. Params may
+//! span several lines: input continues until its brackets are closed. Events
+//! from the server are printed as they arrive, and lines starting with `:` are
+//! meta-commands, see [`META_COMMANDS`]. Tab completes command names from the
+//! server's capabilities, and the input history is kept in the state
+//! directory. A server session token is refreshed before it expires, also
+//! while the shell sits idle. With `--output json`, `jsonl` or `yaml`, stdout
+//! holds only the records and the shell's own messages go to stderr.
+
+use crate::audit::{self, AuditEvent};
+use crate::auth::SessionStore;
+use crate::cli::{self, ExecError, OutputFormat, OutputRecord};
+use crate::config::{paths, ClientConfig};
+use crate::protocol::{self, Message, MessageType, ProtocolError};
+use anyhow::{Context, Result};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, ExternalPrinter, Helper};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Meta-commands and what they do
+pub const META_COMMANDS: &[(&str, &str)] = &[
+    (":help", "Show this help"),
+    (":status", "Show the server, user and connection state"),
+    (":reconnect", "Connect and authenticate again"),
+    (":quit", "Leave the shell (or press Ctrl-D)"),
+];
+
+/// One line, or several lines of params, read by the shell
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellInput {
+    /// Nothing to do, such as a blank line or a `#` comment
+    Empty,
+
+    /// `:help`
+    Help,
+
+    /// `:status`
+    Status,
+
+    /// `:reconnect`
+    Reconnect,
+
+    /// `:quit`
+    Quit,
+
+    /// A meta-command that does not exist, such as `:bogus`
+    Unknown(String),
+
+    /// A command for the server
+    Command { name: String, params: Value },
+}
+
+/// Parse the input of the shell
+///
+/// Commands without params get an empty object.
+pub fn parse_input(input: &str) -> Result<ShellInput> {
+    let input = input.trim();
+    if input.is_empty() || input.starts_with('#') {
+        return Ok(ShellInput::Empty);
+    }
+
+    if let Some(meta) = input.strip_prefix(':') {
+        return Ok(match meta.trim() {
+            "help" | "h" | "?" => ShellInput::Help,
+            "status" => ShellInput::Status,
+            "reconnect" => ShellInput::Reconnect,
+            "quit" | "q" | "exit" => ShellInput::Quit,
+            other => ShellInput::Unknown(other.to_string()),
+        });
+    }
+
+    let (name, params) = match input.split_once(char::is_whitespace) {
+        Some((name, params)) => (name, params.trim()),
+        None => (input, ""),
+    };
+    let params = if params.is_empty() {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_str(params).context("Params must be JSON")?
+    };
+    Ok(ShellInput::Command {
+        name: name.to_string(),
+        params,
+    })
+}
+
+/// Whether `input` is complete, or has JSON brackets or a string left open
+pub fn is_complete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    // Unbalanced closing brackets are complete, so the JSON error is shown
+    !in_string && depth <= 0
+}
+
+/// Meta-commands and server commands starting with `prefix`, sorted
+pub fn completions(prefix: &str, commands: &[String]) -> Vec<String> {
+    let mut matches: Vec<String> = META_COMMANDS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .chain(commands.iter().cloned())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+/// Line editor helper completing command names and continuing open params
+struct ShellHelper {
+    commands: Arc<Mutex<Vec<String>>>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        // Only the command name is completed, not its params
+        let word = line[..pos].trim_start();
+        if word.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        let commands = self.commands.lock().unwrap();
+        Ok((pos - word.len(), completions(word, &commands)))
+    }
+}
+
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(if is_complete(ctx.input()) {
+            ValidationResult::Valid(None)
+        } else {
+            ValidationResult::Incomplete
+        })
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Output that does not garble the line being typed
+type Printer = Box<dyn ExternalPrinter + Send>;
+
+/// What the line reader thread read
+enum ReadLine {
+    Input(String),
+    Eof,
+}
+
+/// Interactive shell connected to the configured server
+pub struct Shell {
+    config: ClientConfig,
+    sessions: Arc<SessionStore>,
+    client: Option<protocol::Client>,
+    commands: Arc<Mutex<Vec<String>>>,
+    timeout_secs: u64,
+    output: OutputFormat,
+}
+
+impl Shell {
+    /// Create a shell for the configured server
+    pub fn new(config: ClientConfig) -> Self {
+        let sessions = Arc::new(SessionStore::from_config(&config.auth));
+        Self {
+            config,
+            sessions,
+            client: None,
+            commands: Arc::new(Mutex::new(Vec::new())),
+            timeout_secs: cli::DEFAULT_TIMEOUT_SECS,
+            output: OutputFormat::default(),
+        }
+    }
+
+    /// How responses, errors and events are printed
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Seconds to wait for the response to each command
+    pub fn with_timeout(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// Path of the input history
+    pub fn history_path() -> PathBuf {
+        paths::state_dir().join("shell_history")
+    }
+
+    /// Connect and read commands until `:quit` or end of input
+    ///
+    /// A failed connection is reported, and the shell starts anyway so that
+    /// `:reconnect` can be used.
+    pub async fn run(mut self) -> Result<()> {
+        self.reconnect().await;
+        self.say(format_args!(
+            "Type a command such as status {{\"verbose\": true}}, or :help"
+        ));
+
+        let (line_tx, mut line_rx) = mpsc::unbounded_channel();
+        let (prompt_tx, prompt_rx) = std_mpsc::channel();
+        let mut printer = spawn_reader(
+            ShellHelper {
+                commands: self.commands.clone(),
+            },
+            line_tx,
+            prompt_rx,
+        )?;
+
+        let mut session_refresh = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            if prompt_tx.send(self.prompt()).is_err() {
+                break;
+            }
+
+            // Show events and keep the session alive while the user types
+            let line = loop {
+                let message = tokio::select! {
+                    line = line_rx.recv() => break line,
+                    message = next_message(self.client.as_ref()) => Some(message),
+                    _ = session_refresh.tick() => None,
+                };
+                match message {
+                    Some(message) => self.show_idle_message(message, &mut printer).await,
+                    None => self.refresh_session().await,
+                }
+            };
+
+            match line {
+                Some(ReadLine::Input(input)) => {
+                    if !self.handle(&input).await {
+                        break;
+                    }
+                }
+                Some(ReadLine::Eof) | None => break,
+            }
+        }
+
+        self.disconnect().await;
+        Ok(())
+    }
+
+    /// Run one input, returning false to leave the shell
+    async fn handle(&mut self, input: &str) -> bool {
+        match parse_input(input) {
+            Ok(ShellInput::Empty) => {}
+            Ok(ShellInput::Help) => {
+                self.say("Commands are sent as: name {\"json\": \"params\"}");
+                for (name, description) in META_COMMANDS {
+                    self.say(format_args!("  {:<12} {}", name, description));
+                }
+            }
+            Ok(ShellInput::Status) => self.print_status(),
+            Ok(ShellInput::Reconnect) => self.reconnect().await,
+            Ok(ShellInput::Quit) => return false,
+            Ok(ShellInput::Unknown(name)) => {
+                self.output
+                    .print(&OutputRecord::error(&ExecError::Command(anyhow::anyhow!(
+                        "Unknown command :{}, try :help",
+                        name
+                    ))))
+            }
+            Ok(ShellInput::Command { name, params }) => self.execute(name, params).await,
+            Err(e) => self
+                .output
+                .print(&OutputRecord::error(&ExecError::InvalidParams(format!(
+                    "{:#}",
+                    e
+                )))),
+        }
+        true
+    }
+
+    async fn execute(&mut self, name: String, params: Value) {
+        let output = self.output;
+        let Some(client) = &self.client else {
+            output.print(&OutputRecord::error(&ExecError::Command(anyhow::anyhow!(
+                "Not connected; use :reconnect"
+            ))));
+            return;
+        };
+        cli::refresh_session(client, &self.config, self.sessions.clone()).await;
+
+        let result = client
+            .execute_with_events(&name, params, self.timeout_secs, |event| {
+                output.print(&OutputRecord::event(event))
+            })
+            .await;
+        match result {
+            Ok(data) => output.print(&OutputRecord::Response {
+                command: name,
+                data,
+            }),
+            Err(e) => {
+                let closed = matches!(e.downcast_ref(), Some(ProtocolError::ChannelClosed));
+                output.print(&OutputRecord::error(&ExecError::Command(e)));
+                if closed {
+                    self.connection_lost();
+                }
+            }
+        }
+    }
+
+    /// Show a message that arrived while no command was running
+    async fn show_idle_message(&mut self, message: Option<Message>, printer: &mut Option<Printer>) {
+        let record = match message {
+            None => {
+                self.connection_lost();
+                OutputRecord::error(&ExecError::Command(
+                    anyhow::Error::new(ProtocolError::ChannelClosed)
+                        .context("Connection closed by the server; use :reconnect"),
+                ))
+            }
+            Some(message) if message.message_type == MessageType::Ping => {
+                if let Some(client) = &self.client {
+                    if let Err(e) = client.send(Message::pong(message.id)).await {
+                        log::warn!("Failed to answer ping: {:#}", e);
+                    }
+                }
+                return;
+            }
+            Some(message) => OutputRecord::event(&message),
+        };
+        let text = self.output.render(&record);
+
+        match printer {
+            Some(printer) => {
+                if let Err(e) = printer.print(format!("{}\n", text)) {
+                    log::warn!("Failed to print: {}", e);
+                }
+            }
+            None => println!("{}", text),
+        }
+    }
+
+    async fn reconnect(&mut self) {
+        self.disconnect().await;
+        self.say(format_args!("Connecting to {}...", self.server_name()));
+
+        match cli::connect(&self.config, self.sessions.clone()).await {
+            Ok(client) => {
+                match client.query_commands(self.timeout_secs).await {
+                    Ok(Some(commands)) => *self.commands.lock().unwrap() = commands,
+                    Ok(None) => log::info!("Server does not advertise its commands"),
+                    Err(e) => log::warn!("Failed to ask the server for its commands: {:#}", e),
+                }
+                self.say("Connected");
+                self.client = Some(client);
+            }
+            Err(e) => eprintln!("{}; use :reconnect to try again", e),
+        }
+    }
+
+    /// Refresh the session token before it expires, so a long session does
+    /// not have to authenticate again
+    async fn refresh_session(&self) {
+        if let Some(client) = &self.client {
+            cli::refresh_session(client, &self.config, self.sessions.clone()).await;
+        }
+    }
+
+    async fn disconnect(&mut self) {
+        if let Some(client) = self.client.take() {
+            let result = client.close().await;
+            audit::record_connection(&self.config, AuditEvent::Disconnect, &result);
+        }
+    }
+
+    fn connection_lost(&mut self) {
+        if self.client.take().is_some() {
+            audit::record_connection(
+                &self.config,
+                AuditEvent::Disconnect,
+                &Err::<(), _>(anyhow::anyhow!("connection lost")),
+            );
+        }
+    }
+
+    fn print_status(&self) {
+        self.say(format_args!("Server:   {}", self.server_name()));
+        self.say(format_args!(
+            "User:     {}",
+            self.config
+                .auth
+                .username
+                .as_deref()
+                .unwrap_or("(login name)")
+        ));
+        self.say(format_args!("Method:   {}", self.config.auth.method));
+        self.say(format_args!(
+            "State:    {}",
+            if self.client.is_some() {
+                "connected"
+            } else {
+                "disconnected"
+            }
+        ));
+        self.say(format_args!(
+            "Commands: {}",
+            self.commands.lock().unwrap().len()
+        ));
+    }
+
+    /// Print a message for the person at the terminal
+    ///
+    /// With structured output it goes to stderr, so that stdout only holds
+    /// records.
+    fn say(&self, text: impl std::fmt::Display) {
+        if self.output.is_structured() {
+            eprintln!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+    }
+
+    fn server_name(&self) -> String {
+        match &self.config.server.socket_path {
+            Some(path) => path.clone(),
+            None => format!("{}:{}", self.config.server.address, self.config.server.port),
+        }
+    }
+
+    fn prompt(&self) -> String {
+        if self.client.is_some() {
+            format!("rcp {}> ", self.server_name())
+        } else {
+            "rcp (disconnected)> ".to_string()
+        }
+    }
+}
+
+/// Next message from the server, or never without a connection
+async fn next_message(client: Option<&protocol::Client>) -> Option<Message> {
+    match client {
+        Some(client) => client.receive().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Read lines on a thread of their own, prompting each time a prompt is sent
+///
+/// Returns a printer for output that must not garble the line being typed,
+/// if the terminal supports one.
+fn spawn_reader(
+    helper: ShellHelper,
+    lines: mpsc::UnboundedSender<ReadLine>,
+    prompts: std_mpsc::Receiver<String>,
+) -> Result<Option<Printer>> {
+    let mut editor =
+        Editor::<ShellHelper, DefaultHistory>::new().context("Failed to set up the line editor")?;
+    editor.set_helper(Some(helper));
+
+    let history_path = Shell::history_path();
+    if history_path.exists() {
+        if let Err(e) = editor.load_history(&history_path) {
+            log::warn!("Failed to load shell history {:?}: {}", history_path, e);
+        }
+    }
+
+    let printer = match editor.create_external_printer() {
+        Ok(printer) => Some(Box::new(printer) as Printer),
+        Err(e) => {
+            log::debug!("Events will be printed without an external printer: {}", e);
+            None
+        }
+    };
+
+    std::thread::spawn(move || {
+        while let Ok(prompt) = prompts.recv() {
+            let line = loop {
+                match editor.readline(&prompt) {
+                    Ok(line) => break ReadLine::Input(line),
+                    // Ctrl-C discards the line being typed
+                    Err(ReadlineError::Interrupted) => continue,
+                    Err(ReadlineError::Eof) => break ReadLine::Eof,
+                    Err(e) => {
+                        log::error!("Failed to read input: {}", e);
+                        break ReadLine::Eof;
+                    }
+                }
+            };
+
+            if let ReadLine::Input(input) = &line {
+                if !input.trim().is_empty() {
+                    let _ = editor.add_history_entry(input.as_str());
+                    if let Some(parent) = history_path.parent() {
+                        let _ = std::fs::create_dir_all(parent);
+                    }
+                    if let Err(e) = editor.save_history(&history_path) {
+                        log::warn!("Failed to save shell history {:?}: {}", history_path, e);
+                    }
+                }
+            }
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(printer)
+}
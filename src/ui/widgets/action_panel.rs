@@ -3,6 +3,16 @@ use egui::Ui; // Ensure Ui is imported
 use log::error;
 use tokio::sync::mpsc;
 
+/// Request made with the action panel that needs the settings in the panels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelAction {
+    /// Connect with the settings in the panels
+    Connect,
+
+    /// Validate and save the settings in the panels
+    SaveConfig,
+}
+
 /// Draw the action panel with connect/disconnect buttons
 pub fn draw_action_panel(
     ui: &mut Ui,                // Ensure Ui is used here
@@ -15,7 +25,8 @@ pub fn draw_action_panel(
     is_connecting: bool,   // Added (was connecting)
     _status_message: &str, // Prefixed with _
     event_tx: mpsc::Sender<AppEvent>,
-) {
+) -> Option<PanelAction> {
+    let mut action = None;
     ui.horizontal(|ui| {
         // Input validation for connect button
         let inputs_valid = !server_address.is_empty()
@@ -76,46 +87,17 @@ pub fn draw_action_panel(
         ui.label("").on_hover_text(tooltip_text);
 
         // Handle click response separately
-        if connect_response.clicked() {
-            if !is_connected && !is_connecting {
-                // First save the config
-                let tx = event_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = tx.send(AppEvent::SaveConfig).await {
-                        error!("Failed to send save config event: {}", e);
-                    }
-                });
-
-                let tx = event_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = tx.send(AppEvent::Connect).await {
-                        error!("Failed to send connect event: {}", e);
-                    }
-                });
-            }
+        if connect_response.clicked() && !is_connected && !is_connecting {
+            action = Some(PanelAction::Connect);
         }
 
         // Handle Ctrl+Enter to connect if inputs are valid and not currently connecting
         if ui.input_mut(|i| i.key_pressed(egui::Key::Enter) && i.modifiers.ctrl)
             && inputs_valid
             && !is_connecting
+            && !is_connected
         {
-            // Changed from connecting
-            if !is_connected {
-                let tx = event_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = tx.send(AppEvent::SaveConfig).await {
-                        error!("Failed to send save config event: {}", e);
-                    }
-                });
-
-                let tx = event_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = tx.send(AppEvent::Connect).await {
-                        error!("Failed to send connect event: {}", e);
-                    }
-                });
-            }
+            action = Some(PanelAction::Connect);
         }
 
         // Disconnect button
@@ -192,12 +174,7 @@ pub fn draw_action_panel(
         );
 
         if save_button.clicked() {
-            let tx = event_tx.clone();
-            tokio::spawn(async move {
-                if let Err(e) = tx.send(AppEvent::SaveConfig).await {
-                    error!("Failed to send save config event: {}", e);
-                }
-            });
+            action = Some(PanelAction::SaveConfig);
         }
 
         ui.label("")
@@ -205,14 +182,10 @@ pub fn draw_action_panel(
 
         // Add keyboard shortcut for save
         if ui.input_mut(|i| i.key_pressed(egui::Key::S) && i.modifiers.ctrl) {
-            let tx = event_tx.clone();
-            tokio::spawn(async move {
-                if let Err(e) = tx.send(AppEvent::SaveConfig).await {
-                    error!("Failed to send save config event: {}", e);
-                }
-            });
+            action = Some(PanelAction::SaveConfig);
         }
     });
+    action
 }
 
 /// Draw progress indicator for connection attempts
@@ -0,0 +1,60 @@
+use crate::audit::{AuditRecord, AuditResult};
+use eframe::egui;
+
+/// Draw the audit log window, newest events first
+///
+/// Returns true if the user asked to reload the log.
+pub fn draw_audit_log_window(
+    ctx: &egui::Context,
+    open: &mut bool,
+    records: &[AuditRecord],
+) -> bool {
+    let mut refresh = false;
+
+    egui::Window::new("Audit Log")
+        .open(open)
+        .default_width(700.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} events", records.len()));
+                if ui.button("Refresh").clicked() {
+                    refresh = true;
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("audit_log_grid")
+                    .striped(true)
+                    .num_columns(6)
+                    .show(ui, |ui| {
+                        for heading in ["Time", "Event", "Server", "User", "Method", "Result"] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for record in records.iter().rev() {
+                            ui.label(&record.timestamp);
+                            ui.label(record.event.to_string());
+                            ui.label(&record.server);
+                            ui.label(record.username.as_deref().unwrap_or("-"));
+                            ui.label(record.method.as_deref().unwrap_or("-"));
+                            match record.result {
+                                AuditResult::Success => {
+                                    ui.colored_label(egui::Color32::GREEN, "success");
+                                }
+                                AuditResult::Failure => {
+                                    ui.colored_label(
+                                        egui::Color32::RED,
+                                        record.reason.as_deref().unwrap_or("failure"),
+                                    );
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    refresh
+}
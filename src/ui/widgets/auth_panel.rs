@@ -1,5 +1,6 @@
+use crate::auth::Secret;
 use crate::ui::events::AppEvent;
-use crate::ui::models::AppState;
+use crate::ui::models::{AppState, PendingPrompt};
 use eframe::egui;
 use std::sync::Arc;
 use tokio::runtime::Handle;
@@ -45,24 +46,30 @@ pub fn draw_auth_panel(
                 let _auth_dropdown = egui::ComboBox::from_label("")
                     .selected_text(auth_method.as_str())
                     .show_ui(ui, |ui| {
+                        ui.selectable_value(auth_method, "auto".to_string(), "Automatic");
                         ui.selectable_value(auth_method, "password".to_string(), "Password");
                         ui.selectable_value(auth_method, "psk".to_string(), "Pre-Shared Key");
                         ui.selectable_value(auth_method, "native".to_string(), "Native OS");
+                        ui.selectable_value(auth_method, "oauth2".to_string(), "Single Sign-On");
                     });
 
                 // Add info tooltip based on selected auth method
                 let info_text = match auth_method.as_str() {
+                    "auto" => "Try the methods the server accepts in order of preference",
                     "password" => "Standard password authentication",
                     "psk" => "Pre-shared key authentication",
                     "native" => "Use system-level authentication",
+                    "oauth2" => "Sign in with your identity provider in a browser",
                     _ => "Unknown authentication method",
                 };
 
                 // Show a colored icon based on how secure the method is
                 let (security_icon, security_color) = match auth_method.as_str() {
+                    "auto" => ("🔄", egui::Color32::LIGHT_BLUE), // Depends on negotiated method
                     "password" => ("🔑", egui::Color32::YELLOW), // Medium security
                     "psk" => ("🔒", egui::Color32::GREEN),       // High security
                     "native" => ("🛡", egui::Color32::LIGHT_GREEN), // Good security
+                    "oauth2" => ("🛡", egui::Color32::GREEN),     // Identity provider
                     _ => ("❓", egui::Color32::RED),             // Unknown
                 };
 
@@ -91,12 +98,12 @@ pub fn draw_auth_panel(
                             } else {
                                 // If we can't get the lock, use default values
                                 show_password = false;
-                                password_display = String::new();
+                                password_display = Secret::default();
                             }
                         }
 
                         let password_edit = ui.add(
-                            egui::TextEdit::singleline(&mut password_display)
+                            egui::TextEdit::singleline(password_display.expose_mut())
                                 .password(!show_password)
                                 .hint_text("Enter password"),
                         );
@@ -127,6 +134,9 @@ pub fn draw_auth_panel(
                 "native" => {
                     ui.label("Using native OS authentication mechanisms");
                 }
+                "oauth2" => {
+                    ui.label("NOTE: The identity provider is set in the client config file");
+                }
                 _ => {}
             }
 
@@ -158,3 +168,117 @@ pub fn draw_auth_panel(
             });
         });
 }
+
+/// Draw the prompt dialog while the authentication flow waits for the user
+pub fn draw_prompt_dialog(
+    ctx: &egui::Context,
+    event_tx: &mpsc::Sender<AppEvent>,
+    rt_handle: &Handle,
+    app_state: &Arc<Mutex<AppState>>,
+) {
+    // Only hold the lock for a short time - using non-blocking approach
+    let (prompt, mut input) = match app_state.try_lock() {
+        Ok(state) => match &state.pending_prompt {
+            Some(prompt) => (prompt.clone(), state.prompt_input.clone()),
+            None => return,
+        },
+        Err(_) => return,
+    };
+
+    let mut event = None;
+
+    egui::Window::new("Authentication")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| match &prompt {
+            PendingPrompt::Secret(message) => {
+                ui.label(message.as_str());
+                ui.add_space(5.0);
+
+                let input_edit = ui.add(
+                    egui::TextEdit::singleline(input.expose_mut())
+                        .password(true)
+                        .desired_width(200.0),
+                );
+                input_edit.request_focus();
+
+                if input_edit.changed() {
+                    if let Ok(mut state) = app_state.try_lock() {
+                        state.prompt_input = input.clone();
+                    }
+                }
+
+                let enter_pressed = ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if (ui.button("OK").clicked() || enter_pressed) && !input.is_empty() {
+                        event = Some(AppEvent::SubmitPrompt(input.clone()));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        event = Some(AppEvent::CancelPrompt);
+                    }
+                });
+            }
+            PendingPrompt::Confirm(question) => {
+                ui.label(question.as_str());
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        event = Some(AppEvent::ConfirmPrompt(true));
+                    }
+                    if ui.button("No").clicked() {
+                        event = Some(AppEvent::ConfirmPrompt(false));
+                    }
+                });
+            }
+        });
+
+    if let Some(event) = event {
+        let tx = event_tx.clone();
+        rt_handle.spawn(async move {
+            let _ = tx.send(event).await;
+        });
+    }
+}
+
+/// Draw where to sign in while the OAuth2 device flow waits for the user
+pub fn draw_device_code_panel(ctx: &egui::Context, app_state: &Arc<Mutex<AppState>>) {
+    // Only hold the lock for a short time - using non-blocking approach
+    let prompt = match app_state.try_lock() {
+        Ok(state) => match &state.device_code {
+            Some(prompt) => prompt.clone(),
+            None => return,
+        },
+        Err(_) => return,
+    };
+
+    egui::Window::new("Sign in")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.label("Open this page in a browser:");
+            let uri = prompt
+                .verification_uri_complete
+                .as_deref()
+                .unwrap_or(&prompt.verification_uri);
+            ui.hyperlink(uri);
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                ui.label("and enter the code");
+                ui.monospace(prompt.user_code.as_str());
+                if ui.button("📋").on_hover_text("Copy code").clicked() {
+                    ui.output_mut(|o| o.copied_text = prompt.user_code.clone());
+                }
+            });
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Waiting for sign-in...");
+            });
+        });
+}
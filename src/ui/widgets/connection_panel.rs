@@ -208,6 +208,8 @@ pub fn draw_connection_panel(
 }
 
 /// Draw the connection controls panel (shown when not connected)
+///
+/// Returns whether Connect was clicked.
 pub fn draw_connection_panel_controls(
     ui: &mut egui::Ui,
     server_address: &str,
@@ -216,8 +218,8 @@ pub fn draw_connection_panel_controls(
     auto_reconnect: &mut bool,
     is_connecting: bool,
     status_message: &str,
-    event_tx: &mpsc::Sender<AppEvent>,
-) {
+) -> bool {
+    let mut connect_requested = false;
     egui::Frame::group(ui.style())
         .inner_margin(egui::Margin::same(10.0))
         .show(ui, |ui| {
@@ -261,24 +263,9 @@ pub fn draw_connection_panel_controls(
                 .min_size(egui::Vec2::new(120.0, 32.0)),
             );
 
-            if connect_response.clicked() {
-                if !is_connecting {
-                    let tx = event_tx.clone();
-                    // It's generally better to spawn async tasks from the main runtime handle
-                    // provided to the app, rather than tokio::spawn directly in a UI function
-                    // if that handle is available. However, for sending a simple event,
-                    // this might be acceptable if the event_tx is Send + Sync.
-                    // For now, let's assume direct send or a small spawn is fine.
-                    // Consider if SaveConfig should be sent here too, like in action_panel.
-                    tokio::spawn(async move {
-                        if let Err(e) = tx.send(AppEvent::Connect).await {
-                            log::error!(
-                                "Failed to send connect event from connection_panel_controls: {}",
-                                e
-                            );
-                        }
-                    });
-                }
+            // The caller builds the connection settings from the panels
+            if connect_response.clicked() && !is_connecting {
+                connect_requested = true;
             }
 
             let tooltip_text = if !inputs_valid {
@@ -297,4 +284,5 @@ pub fn draw_connection_panel_controls(
                 ui.spinner();
             }
         });
+    connect_requested
 }
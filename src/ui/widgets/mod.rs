@@ -1,4 +1,5 @@
 pub mod action_panel;
+pub mod audit_panel;
 pub mod auth_panel;
 pub mod connection_panel;
 pub mod server_panel;
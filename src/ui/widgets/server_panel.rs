@@ -1,5 +1,5 @@
 use crate::ui::events::AppEvent;
-use crate::ui::history::save_connection_history;
+use crate::ui::history::clear_connection_history;
 use crate::ui::models::{AppState, ConnectionEntry};
 use eframe::egui;
 use std::sync::Arc;
@@ -8,8 +8,13 @@ use tokio::sync::mpsc;
 use tokio::sync::Mutex;
 
 /// Draw the server configuration panel
+///
+/// The profile picker is only shown when profiles are configured; choosing one
+/// updates `selected_profile`, with `None` meaning no profile.
 pub fn draw_server_panel(
     ui: &mut egui::Ui,
+    profiles: &[String],
+    selected_profile: &mut Option<String>,
     server_address: &mut String,
     server_port: &mut String,
     use_tls: &mut bool,
@@ -21,6 +26,26 @@ pub fn draw_server_panel(
     egui::CollapsingHeader::new("Server Configuration")
         .default_open(true)
         .show(ui, |ui| {
+            // Profile picker
+            if !profiles.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label("Profile:");
+                    egui::ComboBox::from_id_source("profile_picker")
+                        .selected_text(selected_profile.as_deref().unwrap_or("None"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(selected_profile, None, "None");
+                            for profile in profiles {
+                                ui.selectable_value(
+                                    selected_profile,
+                                    Some(profile.clone()),
+                                    profile,
+                                );
+                            }
+                        });
+                });
+                ui.add_space(5.0);
+            }
+
             // Connection history dropdown
             if !connection_history.is_empty() {
                 ui.horizontal(|ui| {
@@ -49,9 +74,7 @@ pub fn draw_server_panel(
                         .on_hover_text("Clear connection history")
                         .clicked()
                     {
-                        let mut history = connection_history.to_vec();
-                        history.clear();
-                        save_connection_history(&history);
+                        clear_connection_history();
                     }
                 });
                 ui.add_space(5.0);
@@ -175,3 +198,25 @@ pub fn draw_server_panel(
             });
         });
 }
+
+/// Draw the window for pasting an `rcp://` connection link
+///
+/// Returns true if the user asked to open the link.
+pub fn draw_open_link_window(ctx: &egui::Context, open: &mut bool, link: &mut String) -> bool {
+    let mut open_link = false;
+
+    egui::Window::new("Open Link")
+        .open(open)
+        .collapsible(false)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            ui.label("Paste a connection link, such as rcp://alice@rcp.example.com:8717/?auth=psk");
+            let response = ui.add(egui::TextEdit::singleline(link).desired_width(f32::INFINITY));
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Open").clicked() || submitted {
+                open_link = true;
+            }
+        });
+
+    open_link && !link.trim().is_empty()
+}
@@ -0,0 +1,68 @@
+use rust_rcp_client::audit::{AuditEvent, AuditLog, AuditRecord, AuditResult};
+use std::path::PathBuf;
+
+fn temp_log_path() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("rcp-audit-{}", uuid::Uuid::new_v4()))
+        .join("audit.log")
+}
+
+#[test]
+fn test_records_round_trip_as_json_lines() {
+    let path = temp_log_path();
+    let log = AuditLog::new(&path);
+
+    log.record(&AuditRecord::new(
+        AuditEvent::Connect,
+        "rcp.example.com:8717",
+    ))
+    .unwrap();
+    log.record(
+        &AuditRecord::new(AuditEvent::Auth, "rcp.example.com:8717")
+            .with_username("alice")
+            .with_method("password")
+            .failed("rejected by the server"),
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(content.lines().count(), 2);
+    assert!(content.contains(r#""event":"auth""#));
+    assert!(content.contains(r#""result":"failure""#));
+
+    let records = log.read().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].event, AuditEvent::Connect);
+    assert_eq!(records[0].username, None);
+    assert_eq!(records[1].username.as_deref(), Some("alice"));
+    assert_eq!(records[1].result, AuditResult::Failure);
+    assert_eq!(records[1].reason.as_deref(), Some("rejected by the server"));
+
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn test_rotation_keeps_limited_history() {
+    let path = temp_log_path();
+    // Small enough that every record starts a new file
+    let log = AuditLog::new(&path).with_rotation(10, 2);
+
+    for i in 0..5 {
+        log.record(&AuditRecord::new(
+            AuditEvent::Connect,
+            &format!("server-{}:1", i),
+        ))
+        .unwrap();
+    }
+
+    let dir = path.parent().unwrap();
+    assert!(dir.join("audit.log.1").exists());
+    assert!(dir.join("audit.log.2").exists());
+    assert!(!dir.join("audit.log.3").exists());
+
+    // The current file and two rotated files remain, read oldest first
+    let servers: Vec<String> = log.read().unwrap().into_iter().map(|r| r.server).collect();
+    assert_eq!(servers, vec!["server-2:1", "server-3:1", "server-4:1"]);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,77 @@
+use rust_rcp_client::auth::AuthError;
+use rust_rcp_client::cli::{ExecError, FailureClass, OutputFormat, OutputRecord, EXIT_TIMEOUT};
+use rust_rcp_client::protocol::{Message, MessageType, ProtocolError};
+use serde_json::{json, Value};
+
+#[test]
+fn test_error_records_carry_class_kind_and_server_code() {
+    let error = ExecError::Command(
+        ProtocolError::ServerError {
+            code: Some(404),
+            message: "no such command".to_string(),
+        }
+        .into(),
+    );
+    let record: Value =
+        serde_json::from_str(&OutputFormat::Jsonl.render(&OutputRecord::error(&error))).unwrap();
+    assert_eq!(record["type"], "error");
+    assert_eq!(record["class"], "server");
+    assert_eq!(record["kind"], "server_error");
+    assert_eq!(record["code"], 404);
+    assert_eq!(record["exit_code"], 6);
+
+    // Configuration errors are failures from outside the protocol
+    let error = ExecError::Config(anyhow::anyhow!("Invalid configuration"));
+    assert_eq!(error.class(), FailureClass::Failure);
+    assert!(error.protocol_error().is_none());
+
+    // Timeouts are their own class at every step
+    let error = ExecError::Auth(AuthError::Timeout.into());
+    assert_eq!(error.class(), FailureClass::Timeout);
+    assert_eq!(error.exit_code(), EXIT_TIMEOUT);
+}
+
+#[test]
+fn test_structured_formats_tag_responses_and_events() {
+    let response = OutputRecord::Response {
+        command: "status".to_string(),
+        data: json!({ "uptime": 42 }),
+    };
+    let record: Value = serde_json::from_str(&OutputFormat::Json.render(&response)).unwrap();
+    assert_eq!(
+        record,
+        json!({ "type": "response", "command": "status", "data": { "uptime": 42 } })
+    );
+
+    let event = OutputRecord::event(&Message::new(MessageType::Event, json!({ "n": 1 })));
+    let yaml = OutputFormat::Yaml.render(&event);
+    assert!(yaml.starts_with("---\ntype: event\n"), "{}", yaml);
+    assert!(OutputFormat::Yaml.is_structured() && !OutputFormat::Table.is_structured());
+    assert!(!OutputFormat::Jsonl.render(&event).contains('\n'));
+    assert!(
+        !OutputFormat::Json.to_stderr(&OutputRecord::error(&ExecError::InvalidParams(
+            "bad".to_string()
+        )))
+    );
+}
+
+#[test]
+fn test_raw_and_table_output() {
+    let list = OutputRecord::Response {
+        command: "list".to_string(),
+        data: json!([{ "name": "web", "pid": 12 }, { "name": "db" }]),
+    };
+    assert_eq!(
+        OutputFormat::Table.render(&list),
+        "name  pid\n----  ---\nweb   12\ndb"
+    );
+
+    let text = OutputRecord::Response {
+        command: "motd".to_string(),
+        data: json!("hello"),
+    };
+    assert_eq!(OutputFormat::Raw.render(&text), "hello");
+
+    let error = OutputRecord::error(&ExecError::InvalidParams("bad".to_string()));
+    assert!(OutputFormat::Raw.to_stderr(&error));
+}
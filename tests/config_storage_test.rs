@@ -0,0 +1,113 @@
+mod common;
+
+use common::temp_dir;
+use rust_rcp_client::config::{
+    backup_path, bak_path, load_config, lock_file, save_config, write_atomic, ClientConfig,
+    ConfigLayers,
+};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn test_atomic_write_keeps_a_backup_and_no_temporary_files() {
+    let dir = temp_dir("storage");
+    let path = dir.join("state.json");
+
+    write_atomic(&path, b"[1]").unwrap();
+    write_atomic(&path, b"[1, 2]").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[1, 2]");
+    assert_eq!(std::fs::read_to_string(bak_path(&path)).unwrap(), "[1, 2]");
+
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["state.json", "state.json.bak"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lock_waits_for_the_other_holder() {
+    let dir = temp_dir("storage");
+    let path = dir.join("state.json");
+    let lock = lock_file(&path).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let waiting_path = path.clone();
+    let waiter = std::thread::spawn(move || {
+        let _lock = lock_file(&waiting_path).unwrap();
+        tx.send(()).unwrap();
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    drop(lock);
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiter.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_concurrent_saves_leave_a_valid_config() {
+    let dir = temp_dir("storage");
+    let path = dir.join("config.toml");
+
+    let saves: Vec<_> = (0..16u16)
+        .map(|i| {
+            let path = path.clone();
+            tokio::spawn(async move {
+                let mut config = ClientConfig::default();
+                config.server.port = 9000 + i;
+                save_config(&path, &config).await.unwrap();
+            })
+        })
+        .collect();
+    for save in saves {
+        save.await.unwrap();
+    }
+
+    let config = load_config(&path).await.unwrap();
+    assert!((9000..9016).contains(&config.server.port));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_damaged_config_is_recovered_from_its_backup_on_load() {
+    let dir = temp_dir("storage");
+    let path = dir.join("config.toml");
+
+    let mut config = ClientConfig::default();
+    config.server.port = 9100;
+    save_config(&path, &config).await.unwrap();
+    std::fs::write(&path, b"[server]\nport = 91\0\xff").unwrap();
+
+    assert_eq!(load_config(&path).await.unwrap().server.port, 9100);
+    let layers = ConfigLayers::load(Some(&path), None).await.unwrap();
+    assert_eq!(layers.resolve().unwrap().server.port, 9100);
+
+    // A reload while running reports the broken file instead
+    let error = layers.reload().await.unwrap_err();
+    assert!(format!("{:#}", error).contains("Failed to parse"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_backups_are_as_private_as_the_config() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("storage");
+    let path = dir.join("config.toml");
+    let mode =
+        |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+    std::fs::write(&path, "[auth]\nuse_native_auth = true\npsk = \"hunter2\"\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+    // Loading upgrades the file, keeping the original as a backup
+    ConfigLayers::load(Some(&path), None).await.unwrap();
+    assert_eq!(mode(&backup_path(&path, 1)), 0o600);
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(mode(&bak_path(&path)), 0o600);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
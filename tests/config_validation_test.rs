@@ -0,0 +1,83 @@
+use rust_rcp_client::config::{ClientConfig, Profile, Severity};
+
+#[test]
+fn test_default_config_is_valid() {
+    let report = ClientConfig::default().validate();
+    assert!(report.issues.is_empty(), "{}", report);
+    assert_eq!(ClientConfig::default().server.port, 8717);
+}
+
+#[test]
+fn test_all_problems_are_reported_with_keys_and_suggestions() {
+    let mut config = ClientConfig::default();
+    config.server.port = 0;
+    config.server.client_cert_path = Some("/nonexistent/client.pem".to_string());
+    config.server.client_key_path = Some("/nonexistent/client.key".to_string());
+    config.auth.method = "pasword".to_string();
+    config.auth.methods.push("kerberos".to_string());
+    config.auth.store.backend = "vault".to_string();
+
+    let report = config.validate();
+    assert!(!report.is_ok());
+
+    let port = report.issue("server.port").unwrap();
+    assert_eq!(port.severity, Severity::Error);
+    assert!(port.suggestion.as_deref().unwrap().contains("8717"));
+
+    let cert = report.issue("server.client_cert_path").unwrap();
+    assert!(cert.message.contains("does not exist"));
+    assert!(report
+        .warnings()
+        .any(|issue| issue.key == "server.client_key_path" && issue.message.contains("TLS")));
+
+    let method = report.issue("auth.method").unwrap();
+    assert_eq!(
+        method.suggestion.as_deref(),
+        Some("did you mean \"password\"?")
+    );
+    assert!(report.issue("auth.methods[4]").is_some());
+
+    let backend = report.issue("auth.store.backend").unwrap();
+    assert!(backend.suggestion.as_deref().unwrap().contains("keyring"));
+
+    let text = report.to_string();
+    assert!(text.contains("error: server.port: port 0 is not valid"));
+}
+
+#[test]
+fn test_profiles_are_checked() {
+    let mut config = ClientConfig::default();
+    config.default_profile = Some("prod".to_string());
+    let mut lab = Profile::default();
+    lab.server.port = Some(0);
+    lab.auth.method = Some("oauth3".to_string());
+    config.profiles.insert("lab".to_string(), lab);
+
+    let report = config.validate();
+    let default_profile = report.issue("default_profile").unwrap();
+    assert!(default_profile.message.contains("prod"));
+    assert!(report.issue("profiles.lab.server.port").is_some());
+    assert_eq!(
+        report
+            .issue("profiles.lab.auth.method")
+            .unwrap()
+            .suggestion
+            .as_deref(),
+        Some("did you mean \"oauth2\"?")
+    );
+}
+
+#[test]
+fn test_oauth2_without_identity_provider_is_an_error() {
+    let mut config = ClientConfig::default();
+    config.auth.method = "oauth2".to_string();
+
+    let report = config.validate();
+    assert!(report
+        .issue("auth.oauth")
+        .unwrap()
+        .suggestion
+        .as_deref()
+        .unwrap()
+        .contains("[auth.oauth]"));
+}
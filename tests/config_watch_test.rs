@@ -0,0 +1,48 @@
+use rust_rcp_client::config::{ConfigLayers, ConfigUpdate, ConfigWatcher};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+async fn next_update(updates: &mut mpsc::Receiver<ConfigUpdate>) -> ConfigUpdate {
+    tokio::time::timeout(Duration::from_secs(10), updates.recv())
+        .await
+        .expect("no config update within 10 seconds")
+        .expect("config watcher stopped")
+}
+
+#[tokio::test]
+async fn test_valid_edits_are_applied_and_invalid_ones_rejected() {
+    let dir = std::env::temp_dir().join(format!("rcp-watch-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(&path, "[server]\nport = 9000\n").unwrap();
+
+    let layers = ConfigLayers::load(Some(&path), None).await.unwrap();
+    assert_eq!(layers.resolve().unwrap().server.port, 9000);
+    let (_watcher, mut updates) = ConfigWatcher::spawn(layers).unwrap();
+
+    std::fs::write(&path, "[server]\nport = 9001\n\n[ui]\ndark_mode = false\n").unwrap();
+    let update = next_update(&mut updates).await;
+    assert!(update.changes_connection());
+    match update {
+        ConfigUpdate::Applied { config, changed } => {
+            assert_eq!(config.server.port, 9001);
+            assert!(!config.ui.dark_mode);
+            assert_eq!(changed, vec!["server", "ui"]);
+        }
+        ConfigUpdate::Rejected(reason) => panic!("valid edit rejected: {}", reason),
+    }
+
+    std::fs::write(&path, "[server]\nport = 0\n").unwrap();
+    match next_update(&mut updates).await {
+        ConfigUpdate::Rejected(reason) => assert!(reason.contains("server.port")),
+        ConfigUpdate::Applied { .. } => panic!("invalid edit applied"),
+    }
+
+    std::fs::write(&path, "[server\n").unwrap();
+    match next_update(&mut updates).await {
+        ConfigUpdate::Rejected(reason) => assert!(reason.contains("Failed to parse")),
+        ConfigUpdate::Applied { .. } => panic!("unparsable edit applied"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,93 @@
+#![cfg(unix)]
+
+use rust_rcp_client::auth::store::{CredentialStore, HelperStore};
+use rust_rcp_client::auth::Secret;
+
+fn field<'a>(request: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    request
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn test_request_fields() {
+    let request = HelperStore::request("psk:alice@rcp.example.com:8717");
+    assert_eq!(field(&request, "method"), Some("psk"));
+    assert_eq!(field(&request, "username"), Some("alice"));
+    assert_eq!(field(&request, "server"), Some("rcp.example.com"));
+    assert_eq!(field(&request, "port"), Some("8717"));
+
+    let request = HelperStore::request("legacy");
+    assert_eq!(field(&request, "key"), Some("legacy"));
+    assert_eq!(field(&request, "method"), None);
+}
+
+#[test]
+fn test_helper_get_store_erase() {
+    let dir = std::env::temp_dir().join(format!("rcp-helper-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::env::set_var("RCP_TEST_HELPER_DIR", &dir);
+
+    let script = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/helpers/credential-helper.sh"
+    );
+    let store = HelperStore::new(&format!("sh {}", script));
+    let key = "password:alice@rcp.example.com:8717";
+
+    assert_eq!(store.get(key).unwrap(), None);
+
+    store.set(key, "from-vault").unwrap();
+    assert!(dir.join("password_alice_rcp.example.com_8717").exists());
+    assert_eq!(store.get(key).unwrap(), Some(Secret::from("from-vault")));
+
+    store.delete(key).unwrap();
+    assert_eq!(store.get(key).unwrap(), None);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_failing_helper_is_an_error() {
+    let store = HelperStore::new("false");
+    assert!(store.get("psk:alice@server:8717").is_err());
+}
+
+#[test]
+fn test_line_breaks_are_rejected() {
+    // The helper is never run, so a newline cannot inject extra fields
+    let store = HelperStore::new("true");
+    assert!(store
+        .get("password:alice\nserver=evil@server:8717")
+        .is_err());
+    assert!(store.set("password:alice@server:8717", "one\rtwo").is_err());
+    assert!(store.get("password:alice@server:8717").is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_slow_helper_does_not_stall_other_tasks() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    let ticks = Arc::new(AtomicU32::new(0));
+    let ticker = {
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                ticks.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    };
+
+    // Like a helper waiting for the user to unlock a vault
+    let lookup = tokio::spawn(async {
+        HelperStore::new("sleep 0.5; echo secret=unlocked; true")
+            .get("psk:alice@server:8717")
+            .unwrap()
+    });
+    assert_eq!(lookup.await.unwrap(), Some(Secret::from("unlocked")));
+    assert!(ticks.load(Ordering::SeqCst) >= 10);
+    ticker.abort();
+}
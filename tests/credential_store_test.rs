@@ -0,0 +1,122 @@
+use rust_rcp_client::auth::store::{
+    get_or_migrate, CredentialStore, EncryptedFileStore, EnvStore, MemoryStore,
+};
+use rust_rcp_client::auth::{
+    AuthOptions, AuthProvider, AuthTarget, Credentials, PskAuthProvider, Secret,
+};
+use std::sync::Arc;
+
+#[test]
+fn test_memory_store_round_trip() {
+    let store = MemoryStore::new().with_secret("psk", "initial");
+    assert_eq!(store.get("psk").unwrap(), Some(Secret::from("initial")));
+
+    store.set("alice", "secret").unwrap();
+    assert_eq!(store.get("alice").unwrap(), Some(Secret::from("secret")));
+
+    store.delete("alice").unwrap();
+    assert_eq!(store.get("alice").unwrap(), None);
+    store.delete("alice").unwrap();
+}
+
+#[test]
+fn test_env_store_reads_prefixed_variables() {
+    let store = EnvStore::new("RCP_TEST_SECRET_");
+    assert_eq!(store.variable_name("totp:bob"), "RCP_TEST_SECRET_TOTP_BOB");
+
+    std::env::set_var("RCP_TEST_SECRET_PSK", "from-env");
+    assert_eq!(store.get("psk").unwrap(), Some(Secret::from("from-env")));
+    assert_eq!(store.get("missing").unwrap(), None);
+
+    // The environment store is read-only
+    assert!(store.set("psk", "other").is_err());
+    assert!(store.delete("psk").is_err());
+}
+
+#[test]
+fn test_encrypted_file_store_round_trip() {
+    let path = std::env::temp_dir().join(format!("rcp-store-{}.age", uuid::Uuid::new_v4()));
+    std::env::set_var("RCP_TEST_STORE_PASSPHRASE", "correct horse battery staple");
+
+    let store = EncryptedFileStore::new(&path, "RCP_TEST_STORE_PASSPHRASE");
+    assert_eq!(store.get("psk").unwrap(), None);
+
+    store.set("psk", "file-secret").unwrap();
+    store.set("alice", "hunter2").unwrap();
+    assert_eq!(store.get("psk").unwrap(), Some(Secret::from("file-secret")));
+
+    // The secrets must not be readable without the passphrase
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!String::from_utf8_lossy(&raw).contains("file-secret"));
+
+    let wrong = EncryptedFileStore::new(&path, "RCP_TEST_STORE_MISSING_PASSPHRASE");
+    assert!(wrong.get("psk").is_err());
+
+    store.delete("psk").unwrap();
+    assert_eq!(store.get("psk").unwrap(), None);
+    assert_eq!(store.get("alice").unwrap(), Some(Secret::from("hunter2")));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_legacy_entries_are_migrated() {
+    let store = MemoryStore::new().with_secret("alice", "old-password");
+
+    let key = AuthTarget::new("rcp.example.com", 8717).credential_key("password", "alice");
+    assert_eq!(key, "password:alice@rcp.example.com:8717");
+
+    let secret = get_or_migrate(&store, &key, "alice").unwrap();
+    assert_eq!(secret, Some(Secret::from("old-password")));
+    assert_eq!(store.get(&key).unwrap(), Some(Secret::from("old-password")));
+
+    // The legacy entry belongs to the first server it was used with
+    assert_eq!(store.get("alice").unwrap(), None);
+    let other = AuthTarget::new("other.example.com", 8717).credential_key("password", "alice");
+    assert_eq!(get_or_migrate(&store, &other, "alice").unwrap(), None);
+    assert_eq!(store.get(&other).unwrap(), None);
+}
+
+#[test]
+fn test_legacy_entries_are_read_from_read_only_stores() {
+    let store = EnvStore::new("RCP_TEST_LEGACY_");
+    std::env::set_var("RCP_TEST_LEGACY_PSK", "from-env");
+
+    let key = AuthTarget::new("rcp.example.com", 8717).credential_key("psk", "alice");
+    let secret = get_or_migrate(&store, &key, "psk").unwrap();
+    assert_eq!(secret, Some(Secret::from("from-env")));
+}
+
+#[tokio::test]
+async fn test_psk_is_stored_per_server() {
+    let store = Arc::new(MemoryStore::new());
+    let provider = |address: &str| {
+        PskAuthProvider::new().with_username("alice").with_options(
+            AuthOptions::default()
+                .with_store(store.clone())
+                .with_target(AuthTarget::new(address, 8717)),
+        )
+    };
+
+    provider("one")
+        .save_key_to_store(&Secret::from("key-one"))
+        .await
+        .unwrap();
+    provider("two")
+        .save_key_to_store(&Secret::from("key-two"))
+        .await
+        .unwrap();
+
+    match provider("one").get_credentials().await.unwrap() {
+        Credentials::Psk { key } => assert_eq!(key.expose(), "key-one"),
+        _ => panic!("Expected PSK credentials"),
+    }
+    match provider("two").get_credentials().await.unwrap() {
+        Credentials::Psk { key } => assert_eq!(key.expose(), "key-two"),
+        _ => panic!("Expected PSK credentials"),
+    }
+
+    provider("one").forget_key().await.unwrap();
+    assert_eq!(store.get("psk:alice@one:8717").unwrap(), None);
+    assert!(store.get("psk:alice@two:8717").unwrap().is_some());
+}
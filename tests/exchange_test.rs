@@ -0,0 +1,169 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{auth_reply, serve_exchanges};
+use rust_rcp_client::auth::exchange::{query_methods, run};
+use rust_rcp_client::auth::{
+    AuthError, AuthOptions, SecondFactorChallenge, SecondFactorHandler, SecondFactorKind,
+};
+use rust_rcp_client::protocol::{Client, Message};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Second factor handler answering with the given codes in turn
+struct ScriptedCodes {
+    codes: Mutex<Vec<&'static str>>,
+    challenges: Mutex<Vec<SecondFactorChallenge>>,
+}
+
+impl ScriptedCodes {
+    fn new(codes: &[&'static str]) -> Arc<Self> {
+        Arc::new(Self {
+            codes: Mutex::new(codes.iter().rev().copied().collect()),
+            challenges: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+#[async_trait]
+impl SecondFactorHandler for ScriptedCodes {
+    async fn one_time_code(&self, challenge: &SecondFactorChallenge) -> Result<String, AuthError> {
+        self.challenges.lock().unwrap().push(challenge.clone());
+        self.codes
+            .lock()
+            .unwrap()
+            .pop()
+            .map(str::to_string)
+            .ok_or(AuthError::SecondFactorUnavailable)
+    }
+}
+
+fn first_factor() -> Value {
+    json!({ "username": "alice", "method": "psk", "key": "shared-key" })
+}
+
+fn second_factor_request(request: &Message, attempts_remaining: u32) -> Message {
+    auth_reply(
+        request,
+        json!({
+            "success": false,
+            "second_factor": {
+                "type": "totp",
+                "message": "Enter the code from your app",
+                "attempts_remaining": attempts_remaining,
+            },
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_second_factor_round_trip() {
+    let (port, server) = serve_exchanges(2, |step, request| match step {
+        0 => second_factor_request(request, 3),
+        _ if request.payload["code"] == "123456" => auth_reply(request, json!({ "success": true })),
+        _ => auth_reply(request, json!({ "success": false })),
+    })
+    .await;
+    let codes = ScriptedCodes::new(&["123456"]);
+    let options = AuthOptions::default().with_second_factor(codes.clone());
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    assert!(run(&client, first_factor(), "alice", &options)
+        .await
+        .unwrap());
+
+    let challenges = codes.challenges.lock().unwrap().clone();
+    assert_eq!(challenges.len(), 1);
+    assert_eq!(challenges[0].kind, SecondFactorKind::Totp);
+    assert_eq!(challenges[0].username, "alice");
+    assert_eq!(
+        challenges[0].message.as_deref(),
+        Some("Enter the code from your app")
+    );
+    assert_eq!(challenges[0].attempts_remaining, Some(3));
+
+    let received = server.await.unwrap();
+    assert_eq!(received[1].payload["step"], "second_factor");
+    assert_eq!(received[1].payload["method"], "totp");
+    assert_eq!(received[1].payload["username"], "alice");
+}
+
+#[tokio::test]
+async fn test_exchange_gives_up_after_three_wrong_codes() {
+    // The first factor and each wrong code are answered with another challenge
+    let (port, server) = serve_exchanges(4, |step, request| {
+        second_factor_request(request, 3 - step as u32)
+    })
+    .await;
+    let codes = ScriptedCodes::new(&["000001", "000002", "000003", "000004"]);
+    let options = AuthOptions::default().with_second_factor(codes.clone());
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    assert!(!run(&client, first_factor(), "alice", &options)
+        .await
+        .unwrap());
+
+    // The fourth code is never asked for or sent
+    assert_eq!(codes.challenges.lock().unwrap().len(), 3);
+    let received = server.await.unwrap();
+    let sent: Vec<&Value> = received[1..]
+        .iter()
+        .map(|message| &message.payload["code"])
+        .collect();
+    assert_eq!(sent, vec!["000001", "000002", "000003"]);
+}
+
+#[tokio::test]
+async fn test_rejected_first_factor_skips_the_second() {
+    let (port, server) = serve_exchanges(1, |_, request| {
+        auth_reply(request, json!({ "success": false, "message": "bad key" }))
+    })
+    .await;
+    let codes = ScriptedCodes::new(&["123456"]);
+    let options = AuthOptions::default().with_second_factor(codes.clone());
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    assert!(!run(&client, first_factor(), "alice", &options)
+        .await
+        .unwrap());
+    assert!(codes.challenges.lock().unwrap().is_empty());
+    assert_eq!(server.await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_second_factor_without_a_handler_is_an_error() {
+    let (port, _server) = serve_exchanges(1, |_, request| second_factor_request(request, 3)).await;
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    let error = run(&client, first_factor(), "alice", &AuthOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(AuthError::SecondFactorUnavailable)
+    ));
+}
+
+#[tokio::test]
+async fn test_query_methods() {
+    let (port, server) = serve_exchanges(2, |step, request| match step {
+        0 => auth_reply(
+            request,
+            json!({ "success": true, "methods": ["psk", "password"] }),
+        ),
+        _ => Message::error(Some(request.id), 400, "unknown step"),
+    })
+    .await;
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    assert_eq!(
+        query_methods(&client, "alice").await.unwrap(),
+        Some(vec!["psk".to_string(), "password".to_string()])
+    );
+    // Servers that do not know the step do not advertise their methods
+    assert_eq!(query_methods(&client, "alice").await.unwrap(), None);
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0].payload["step"], "methods");
+    assert_eq!(received[0].payload["username"], "alice");
+}
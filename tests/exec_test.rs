@@ -0,0 +1,124 @@
+mod common;
+
+use common::{read_message, write_message};
+use rust_rcp_client::auth::Secret;
+use rust_rcp_client::cli::{self, EXIT_AUTH, EXIT_CONNECT, EXIT_FAILURE, EXIT_SERVER, EXIT_USAGE};
+use rust_rcp_client::config::ClientConfig;
+use rust_rcp_client::protocol::{Message, MessageType};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+fn config(port: u16) -> ClientConfig {
+    let mut config = ClientConfig::default();
+    config.server.address = "127.0.0.1".to_string();
+    config.server.port = port;
+    config.server.use_tls = false;
+    config.auth.method = "psk".to_string();
+    config.auth.username = Some("alice".to_string());
+    config.auth.psk = Some(Secret::new("shared-key").into());
+    config.audit.enabled = false;
+    config
+}
+
+/// Server that accepts the login if `accept` is set and answers one command with `answer`
+async fn serve(
+    accept: bool,
+    answer: fn(&Message) -> Message,
+) -> (u16, tokio::task::JoinHandle<Option<Message>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let login = read_message(&mut stream).await;
+        assert_eq!(login.message_type, MessageType::Auth);
+        write_message(&mut stream, &Message::response(login.id, accept, json!({}))).await;
+        if !accept {
+            return None;
+        }
+
+        let request = read_message(&mut stream).await;
+        write_message(&mut stream, &answer(&request)).await;
+        Some(request)
+    });
+    (port, server)
+}
+
+#[tokio::test]
+async fn test_exec_prints_the_response_data() {
+    let (port, server) = serve(true, |request| {
+        Message::response(request.id, true, json!({ "uptime": 42 }))
+    })
+    .await;
+
+    let params = cli::parse_params(Some(r#"{"verbose": true}"#)).unwrap();
+    let data = cli::exec(&config(port), "status", params, 5).await.unwrap();
+    assert_eq!(data, json!({ "uptime": 42 }));
+
+    let request = server.await.unwrap().unwrap();
+    assert_eq!(request.message_type, MessageType::Command);
+    assert_eq!(request.payload["command"], "status");
+    assert_eq!(request.payload["params"], json!({ "verbose": true }));
+}
+
+#[tokio::test]
+async fn test_exec_exit_codes_tell_failures_apart() {
+    let (port, _server) = serve(true, |request| {
+        Message::error(Some(request.id), 404, "no such command")
+    })
+    .await;
+    let err = cli::exec(&config(port), "nope", json!({}), 5)
+        .await
+        .unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_SERVER);
+    assert!(err.to_string().contains("no such command"), "{}", err);
+
+    let (port, _server) = serve(false, |request| Message::pong(request.id)).await;
+    let err = cli::exec(&config(port), "status", json!({}), 5)
+        .await
+        .unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_AUTH);
+
+    let port = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let err = cli::exec(&config(port), "status", json!({}), 5)
+        .await
+        .unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_CONNECT);
+
+    let err = cli::parse_params(Some("{not json")).unwrap_err();
+    assert_eq!(err.exit_code(), EXIT_USAGE);
+}
+
+#[test]
+fn test_no_gui_without_a_subcommand_is_a_usage_error() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rust_rcp_client"))
+        .arg("--no-gui")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_USAGE));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--no-gui needs a subcommand"));
+}
+
+#[test]
+fn test_configuration_errors_are_printed_as_records() {
+    let dir = common::temp_dir("exec");
+    let path = dir.join("config.toml");
+    std::fs::write(&path, "[server]\nport = \"not a port\"\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rust_rcp_client"))
+        .args(["--output", "jsonl", "--config"])
+        .arg(&path)
+        .args(["exec", "status"])
+        .current_dir(&dir)
+        .env("RCP_HOME", &dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_FAILURE));
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["type"], "error");
+    assert_eq!(record["class"], "failure");
+    assert_eq!(record["exit_code"], EXIT_FAILURE);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
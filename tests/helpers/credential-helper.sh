@@ -0,0 +1,34 @@
+#!/bin/sh
+# Credential helper used by the tests: keeps each secret in a file named
+# after the request fields under $RCP_TEST_HELPER_DIR.
+
+operation="$1"
+while IFS= read -r line && [ -n "$line" ]; do
+    case "$line" in
+        method=*) method="${line#method=}" ;;
+        username=*) username="${line#username=}" ;;
+        server=*) server="${line#server=}" ;;
+        port=*) port="${line#port=}" ;;
+        secret=*) secret="${line#secret=}" ;;
+    esac
+done
+
+file="$RCP_TEST_HELPER_DIR/${method}_${username}_${server}_${port}"
+
+case "$operation" in
+    get)
+        if [ -f "$file" ]; then
+            printf 'secret=%s\n' "$(cat "$file")"
+        fi
+        ;;
+    store)
+        printf '%s' "$secret" > "$file"
+        ;;
+    erase)
+        rm -f "$file"
+        ;;
+    *)
+        echo "unknown operation: $operation" >&2
+        exit 1
+        ;;
+esac
@@ -0,0 +1,99 @@
+use rust_rcp_client::auth::ScriptedPrompter;
+use rust_rcp_client::protocol::known_servers::{self, HostStatus, KnownServers};
+use rust_rcp_client::protocol::tls;
+
+fn temp_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("rcp-known-servers-{}", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn test_known_servers_round_trip() {
+    let path = temp_path();
+    let fingerprint = tls::fingerprint(b"certificate");
+    assert!(fingerprint.starts_with("sha256:"));
+
+    let mut known = KnownServers::load(&path).unwrap();
+    assert_eq!(
+        known.check("server", 8717, &fingerprint),
+        HostStatus::Unknown
+    );
+
+    known.trust("server", 8717, &fingerprint);
+    known.save().unwrap();
+
+    let known = KnownServers::load(&path).unwrap();
+    assert_eq!(
+        known.check("server", 8717, &fingerprint),
+        HostStatus::Trusted
+    );
+    assert_eq!(
+        known.check("server", 8718, &fingerprint),
+        HostStatus::Unknown
+    );
+    assert_eq!(
+        known.check("server", 8717, "sha256:other"),
+        HostStatus::Changed {
+            expected: fingerprint
+        }
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_verify_pins_on_first_use_and_rejects_changes() {
+    let path = temp_path();
+
+    // First use is pinned without asking when there is no prompter
+    known_servers::verify(&path, "server", 8717, "sha256:aa", false, None)
+        .await
+        .unwrap();
+    known_servers::verify(&path, "server", 8717, "sha256:aa", false, None)
+        .await
+        .unwrap();
+
+    // A changed identity is a hard failure
+    let err = known_servers::verify(&path, "server", 8717, "sha256:bb", false, None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("has changed"));
+
+    // ...unless the user declines or accepts it explicitly
+    let prompter = ScriptedPrompter::new().with_confirmation(false);
+    assert!(
+        known_servers::verify(&path, "server", 8717, "sha256:bb", false, Some(&prompter))
+            .await
+            .is_err()
+    );
+    known_servers::verify(&path, "server", 8717, "sha256:bb", true, None)
+        .await
+        .unwrap();
+
+    let known = KnownServers::load(&path).unwrap();
+    assert_eq!(
+        known.check("server", 8717, "sha256:bb"),
+        HostStatus::Trusted
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_save_keeps_servers_pinned_by_other_instances() {
+    let path = temp_path();
+    let mut first = KnownServers::load(&path).unwrap();
+    let mut second = KnownServers::load(&path).unwrap();
+
+    first.trust("one", 8717, "sha256:aa");
+    first.save().unwrap();
+    second.trust("two", 8717, "sha256:bb");
+    second.save().unwrap();
+
+    let known = KnownServers::load(&path).unwrap();
+    assert_eq!(known.check("one", 8717, "sha256:aa"), HostStatus::Trusted);
+    assert_eq!(known.check("two", 8717, "sha256:bb"), HostStatus::Trusted);
+
+    for extension in ["", ".bak", ".lock"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), extension));
+    }
+}
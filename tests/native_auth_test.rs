@@ -0,0 +1,106 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::read_message;
+use rust_rcp_client::auth::{create_provider_with_options, AuthMethod, AuthOptions, AuthTarget};
+use rust_rcp_client::protocol::{Client, Message};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, UnixListener};
+
+fn socket_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("rcp-{}-{}.sock", name, uuid::Uuid::new_v4()))
+}
+
+async fn reply<S: AsyncWrite + Unpin>(stream: &mut S, request: &Message, success: bool) {
+    let data = serde_json::to_vec(&Message::response(request.id, success, json!({}))).unwrap();
+    stream
+        .write_all(&(data.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&data).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_peer_credentials_over_unix_socket() {
+    let path = socket_path("server");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    // Accept only if the claimed uid matches what the kernel reports
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let peer = stream.peer_cred().unwrap();
+        let request = read_message(&mut stream).await;
+        let payload = request.payload.clone();
+        let accepted = payload["mechanism"] == "peercred"
+            && payload["uid"].as_u64() == Some(peer.uid() as u64)
+            && payload["gid"].as_u64() == Some(peer.gid() as u64);
+        reply(&mut stream, &request, accepted).await;
+        payload
+    });
+
+    let client = Client::connect_unix(&path).await.unwrap();
+    let provider = create_provider_with_options(AuthMethod::Native, "", AuthOptions::default());
+    assert!(provider.authenticate(&client).await.unwrap());
+
+    let payload = server.await.unwrap();
+    assert_eq!(payload["method"], "native");
+    assert!(payload.get("credentials").is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_peer_credentials_reject_other_user() {
+    let path = socket_path("server");
+    let _listener = UnixListener::bind(&path).unwrap();
+
+    let client = Client::connect_unix(&path).await.unwrap();
+    let provider = create_provider_with_options(
+        AuthMethod::Native,
+        "not-the-current-user",
+        AuthOptions::default(),
+    );
+    assert!(provider.authenticate(&client).await.is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_assertion_for_remote_server() {
+    let daemon_path = socket_path("authd");
+    let daemon = UnixListener::bind(&daemon_path).unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = daemon.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let request: Value = serde_json::from_str(&line).unwrap();
+        let assertion = format!("signed:{}@{}", request["username"], request["port"]);
+        let reply = json!({ "assertion": assertion }).to_string() + "\n";
+        stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let request = read_message(&mut stream).await;
+        reply(&mut stream, &request, true).await;
+        request.payload
+    });
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    let options = AuthOptions::default()
+        .with_target(AuthTarget::new("127.0.0.1", port))
+        .with_native_helper(&daemon_path);
+    let provider = create_provider_with_options(AuthMethod::Native, "alice", options);
+    assert!(provider.authenticate(&client).await.unwrap());
+
+    let payload = server.await.unwrap();
+    assert_eq!(payload["mechanism"], "assertion");
+    assert_eq!(
+        payload["credentials"],
+        format!("signed:\"alice\"@{}", port).as_str()
+    );
+    let _ = std::fs::remove_file(&daemon_path);
+}
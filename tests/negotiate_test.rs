@@ -0,0 +1,55 @@
+use rust_rcp_client::auth::negotiate::{
+    parse_preference, AttemptOutcome, MethodAttempt, NegotiationReport,
+};
+use rust_rcp_client::auth::AuthMethod;
+use rust_rcp_client::config::ClientConfig;
+
+#[test]
+fn test_parse_preference() {
+    let methods = vec![
+        "publickey".to_string(),
+        "PSK".to_string(),
+        "password".to_string(),
+    ];
+    assert_eq!(
+        parse_preference(&methods).unwrap(),
+        vec![AuthMethod::PublicKey, AuthMethod::Psk, AuthMethod::Password]
+    );
+
+    // Unknown methods are an error rather than a silent fallback
+    assert!(parse_preference(&["kerberos".to_string()]).is_err());
+}
+
+#[test]
+fn test_default_config_negotiates() {
+    let config = ClientConfig::default();
+    assert_eq!(config.auth.method, "auto");
+    assert!(parse_preference(&config.auth.methods).is_ok());
+}
+
+#[test]
+fn test_report_lists_every_attempt() {
+    let report = NegotiationReport {
+        attempts: vec![
+            MethodAttempt {
+                method: AuthMethod::PublicKey,
+                outcome: AttemptOutcome::Unsupported,
+            },
+            MethodAttempt {
+                method: AuthMethod::Native,
+                outcome: AttemptOutcome::NotOffered,
+            },
+            MethodAttempt {
+                method: AuthMethod::Password,
+                outcome: AttemptOutcome::Rejected,
+            },
+        ],
+    };
+
+    assert_eq!(report.succeeded(), None);
+    assert_eq!(
+        report.to_string(),
+        "publickey: not supported by this client; native: not offered by the server; \
+         password: rejected by the server"
+    );
+}
@@ -0,0 +1,189 @@
+use rust_rcp_client::auth::store::{CredentialStore, EnvStore, MemoryStore};
+use rust_rcp_client::auth::{
+    AuthOptions, AuthProvider, AuthTarget, Credentials, OAuthAuthProvider, Prompter,
+    ScriptedPrompter, Secret,
+};
+use rust_rcp_client::config::OAuthConfig;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Answer one HTTP request with `handler(method, path, body)`
+async fn serve_one<F>(stream: tokio::net::TcpStream, handler: &F)
+where
+    F: Fn(&str, &str, &str) -> (u16, Value),
+{
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).await.unwrap();
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await.unwrap();
+
+    let (status, reply) = handler(&method, &path, &String::from_utf8_lossy(&body));
+    let reply = reply.to_string();
+    let response = format!(
+        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reply.len(),
+        reply
+    );
+    reader
+        .get_mut()
+        .write_all(response.as_bytes())
+        .await
+        .unwrap();
+}
+
+/// Stand-in identity provider; the first device code poll is still pending
+async fn start_identity_provider() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let issuer = base.clone();
+    let polls = Arc::new(AtomicUsize::new(0));
+
+    tokio::spawn(async move {
+        let handler = move |method: &str, path: &str, body: &str| match (method, path) {
+            ("GET", "/.well-known/openid-configuration") => (
+                200,
+                json!({
+                    "device_authorization_endpoint": format!("{}/device", base),
+                    "token_endpoint": format!("{}/token", base),
+                }),
+            ),
+            ("POST", "/device") => (
+                200,
+                json!({
+                    "device_code": "device-123",
+                    "user_code": "WDJB-MJHT",
+                    "verification_uri": format!("{}/activate", base),
+                    "expires_in": 60,
+                    "interval": 1,
+                }),
+            ),
+            ("POST", "/token") if body.contains("device_code=device-123") => {
+                if polls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    (400, json!({ "error": "authorization_pending" }))
+                } else {
+                    (
+                        200,
+                        json!({ "access_token": "access-1", "refresh_token": "refresh-1" }),
+                    )
+                }
+            }
+            ("POST", "/token") if body.contains("refresh_token=refresh-1") => (
+                200,
+                json!({ "access_token": "access-2", "refresh_token": "refresh-2" }),
+            ),
+            _ => (400, json!({ "error": "invalid_grant" })),
+        };
+
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_one(stream, &handler).await;
+        }
+    });
+
+    issuer
+}
+
+fn access_token(credentials: Credentials) -> Secret {
+    match credentials {
+        Credentials::OAuth2 { access_token, .. } => access_token,
+        other => panic!("unexpected credentials: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_device_flow_then_refresh() {
+    let issuer = start_identity_provider().await;
+    let store = Arc::new(MemoryStore::new());
+    let prompter = Arc::new(ScriptedPrompter::new());
+
+    let options = AuthOptions::default()
+        .with_store(store.clone())
+        .with_prompter(prompter.clone() as Arc<dyn Prompter>)
+        .with_target(AuthTarget::new("rcp.example.com", 8717))
+        .with_oauth(OAuthConfig {
+            issuer_url: issuer.clone(),
+            client_id: "rcp-client".to_string(),
+            scope: Some("openid".to_string()),
+            device_authorization_endpoint: None,
+            token_endpoint: None,
+        });
+    let provider = OAuthAuthProvider::new("alice").with_options(options);
+
+    // No refresh token yet, so the user is sent to the verification page
+    let credentials = provider.get_credentials().await.unwrap();
+    assert_eq!(access_token(credentials), Secret::from("access-1"));
+    assert_eq!(
+        prompter.asked(),
+        vec![format!(
+            "To sign in, open {}/activate and enter the code WDJB-MJHT",
+            issuer
+        )]
+    );
+
+    let key = "oauth2-refresh:alice@rcp.example.com:8717";
+    assert_eq!(store.get(key).unwrap(), Some(Secret::from("refresh-1")));
+
+    // The stored refresh token is used without asking again
+    let credentials = provider.get_credentials().await.unwrap();
+    assert_eq!(access_token(credentials), Secret::from("access-2"));
+    assert_eq!(prompter.asked().len(), 1);
+    assert_eq!(store.get(key).unwrap(), Some(Secret::from("refresh-2")));
+}
+
+#[tokio::test]
+async fn test_missing_identity_provider_is_an_error() {
+    let provider = OAuthAuthProvider::new("alice")
+        .with_options(AuthOptions::default().with_store(Arc::new(MemoryStore::new())));
+    assert!(provider.get_credentials().await.is_err());
+}
+
+#[tokio::test]
+async fn test_device_flow_with_read_only_store() {
+    let issuer = start_identity_provider().await;
+    let store = Arc::new(EnvStore::new("RCP_TEST_OAUTH_"));
+    let prompter = Arc::new(ScriptedPrompter::new());
+
+    // A refresh token the identity provider no longer accepts
+    let key = "oauth2-refresh:alice@rcp.example.com:8717";
+    std::env::set_var(store.variable_name(key), "revoked");
+
+    let options = AuthOptions::default()
+        .with_store(store.clone())
+        .with_prompter(prompter.clone() as Arc<dyn Prompter>)
+        .with_target(AuthTarget::new("rcp.example.com", 8717))
+        .with_oauth(OAuthConfig {
+            issuer_url: issuer,
+            client_id: "rcp-client".to_string(),
+            scope: None,
+            device_authorization_endpoint: None,
+            token_endpoint: None,
+        });
+    let provider = OAuthAuthProvider::new("alice").with_options(options);
+
+    // Neither removing the old token nor saving the new one fails the sign-in
+    let credentials = provider.get_credentials().await.unwrap();
+    assert_eq!(access_token(credentials), Secret::from("access-1"));
+    assert_eq!(prompter.asked().len(), 1);
+}
@@ -0,0 +1,103 @@
+use rust_rcp_client::config::{load_config, save_config, ClientConfig};
+
+const PROFILES: &str = r#"
+default_profile = "work"
+
+[profiles.work]
+description = "Office server"
+
+[profiles.work.server]
+address = "rcp.work.example.com"
+port = 9000
+use_tls = true
+
+[profiles.work.auth]
+method = "oauth2"
+username = "alice"
+
+[profiles.lab.server]
+address = "10.0.0.5"
+"#;
+
+/// Default configuration with the profiles above added
+fn config_with_profiles() -> ClientConfig {
+    let defaults = ClientConfig::default();
+    let mut content = String::from(PROFILES);
+    content.push_str(&toml::to_string_pretty(&defaults).unwrap());
+    toml::from_str(&content).unwrap()
+}
+
+#[test]
+fn test_profile_overrides_only_the_fields_it_sets() {
+    let config = config_with_profiles();
+    assert_eq!(config.profile_names(), vec!["lab", "work"]);
+
+    let work = config.with_profile("work").unwrap();
+    assert_eq!(work.active_profile.as_deref(), Some("work"));
+    assert_eq!(work.server.address, "rcp.work.example.com");
+    assert_eq!(work.server.port, 9000);
+    assert!(work.server.use_tls);
+    assert_eq!(work.auth.method, "oauth2");
+    assert_eq!(work.auth.username.as_deref(), Some("alice"));
+    assert_eq!(work.server.verify_server, config.server.verify_server);
+
+    // Switching replaces the previous profile instead of combining with it
+    let lab = work.with_profile("lab").unwrap();
+    assert_eq!(lab.server.address, "10.0.0.5");
+    assert_eq!(lab.server.port, config.server.port);
+    assert_eq!(lab.auth.method, config.auth.method);
+
+    let none = lab.without_profile();
+    assert_eq!(none.active_profile, None);
+    assert_eq!(none.server.address, config.server.address);
+}
+
+#[test]
+fn test_select_profile_falls_back_to_default_profile() {
+    let config = config_with_profiles();
+
+    let selected = config.select_profile(None).unwrap();
+    assert_eq!(selected.active_profile.as_deref(), Some("work"));
+
+    let selected = config.select_profile(Some("lab")).unwrap();
+    assert_eq!(selected.active_profile.as_deref(), Some("lab"));
+
+    let error = config.select_profile(Some("home")).unwrap_err();
+    assert!(error.to_string().contains("lab, work"));
+
+    let plain = ClientConfig::default().select_profile(None).unwrap();
+    assert_eq!(plain.active_profile, None);
+}
+
+#[tokio::test]
+async fn test_saving_keeps_profiles_out_of_top_level_sections() {
+    let path = std::env::temp_dir()
+        .join(format!("rcp-profile-{}", uuid::Uuid::new_v4()))
+        .join("config.toml");
+    let config = config_with_profiles().with_profile("work").unwrap();
+
+    save_config(&path, &config).await.unwrap();
+    let loaded = load_config(&path).await.unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(
+        loaded.server.address,
+        ClientConfig::default().server.address
+    );
+    assert_eq!(loaded.default_profile.as_deref(), Some("work"));
+    assert_eq!(loaded.profile_names(), vec!["lab", "work"]);
+    assert_eq!(
+        loaded.profiles["work"].server.address.as_deref(),
+        Some("rcp.work.example.com")
+    );
+}
+
+#[test]
+fn test_profile_address_replaces_inherited_socket() {
+    let mut config = config_with_profiles();
+    config.server.socket_path = Some("/run/rcp/server.sock".to_string());
+
+    let lab = config.with_profile("lab").unwrap();
+    assert_eq!(lab.server.address, "10.0.0.5");
+    assert_eq!(lab.server.socket_path, None);
+}
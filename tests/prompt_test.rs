@@ -0,0 +1,49 @@
+use rust_rcp_client::auth::store::MemoryStore;
+use rust_rcp_client::auth::{
+    AuthOptions, AuthProvider, Credentials, PasswordAuthProvider, ScriptedPrompter,
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_stored_password_skips_prompt() {
+    let store = Arc::new(MemoryStore::new().with_secret("alice", "stored"));
+    let prompter = Arc::new(ScriptedPrompter::new().with_secret("typed"));
+    let provider = PasswordAuthProvider::new("alice").with_options(
+        AuthOptions::default()
+            .with_store(store)
+            .with_prompter(prompter.clone()),
+    );
+
+    match provider.get_credentials().await.unwrap() {
+        Credentials::Password { password, .. } => assert_eq!(password.expose(), "stored"),
+        _ => panic!("Expected password credentials"),
+    }
+    assert!(prompter.asked().is_empty());
+}
+
+#[tokio::test]
+async fn test_missing_password_is_prompted() {
+    let prompter = Arc::new(ScriptedPrompter::new().with_secret("typed"));
+    let provider = PasswordAuthProvider::new("alice").with_options(
+        AuthOptions::default()
+            .with_store(Arc::new(MemoryStore::new()))
+            .with_prompter(prompter.clone()),
+    );
+
+    match provider.get_credentials().await.unwrap() {
+        Credentials::Password { password, .. } => assert_eq!(password.expose(), "typed"),
+        _ => panic!("Expected password credentials"),
+    }
+    assert_eq!(prompter.asked(), vec!["Password for alice".to_string()]);
+}
+
+#[tokio::test]
+async fn test_cancelled_prompt_fails() {
+    let provider = PasswordAuthProvider::new("alice").with_options(
+        AuthOptions::default()
+            .with_store(Arc::new(MemoryStore::new()))
+            .with_prompter(Arc::new(ScriptedPrompter::new())),
+    );
+
+    assert!(provider.get_credentials().await.is_err());
+}
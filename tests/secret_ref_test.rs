@@ -0,0 +1,107 @@
+use rust_rcp_client::auth::{Secret, SecretRef};
+use rust_rcp_client::config::{load_config, save_config, ClientConfig};
+use std::path::PathBuf;
+
+#[test]
+fn test_references_are_parsed_and_written_back_unchanged() {
+    let cases = [
+        (
+            "keyring:rcp-client/prod-psk",
+            SecretRef::Keyring {
+                service: "rcp-client".to_string(),
+                account: "prod-psk".to_string(),
+            },
+        ),
+        ("env:RCP_PSK", SecretRef::Env("RCP_PSK".to_string())),
+        (
+            "file:/run/secrets/psk",
+            SecretRef::File(PathBuf::from("/run/secrets/psk")),
+        ),
+        (
+            "cmd:pass show rcp",
+            SecretRef::Command("pass show rcp".to_string()),
+        ),
+    ];
+    for (text, expected) in cases {
+        let parsed: SecretRef = text.parse().unwrap();
+        assert_eq!(parsed, expected);
+        assert!(parsed.is_reference());
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    assert!("keyring:rcp-client".parse::<SecretRef>().is_err());
+    assert!("env:".parse::<SecretRef>().is_err());
+    assert_eq!(
+        "http://x".parse::<SecretRef>().unwrap(),
+        SecretRef::Value(Secret::new("http://x"))
+    );
+}
+
+#[test]
+fn test_literal_secrets_that_look_like_references_round_trip() {
+    let mut config = ClientConfig::default();
+    config.auth.psk = Some("literal:env:HOME".parse().unwrap());
+    assert_eq!(
+        config.auth.psk,
+        Some(SecretRef::Value(Secret::new("env:HOME")))
+    );
+
+    let serialized = toml::to_string(&config).unwrap();
+    assert!(serialized.contains("psk = \"literal:env:HOME\""));
+    let parsed: ClientConfig = toml::from_str(&serialized).unwrap();
+    assert_eq!(parsed.auth.psk, config.auth.psk);
+}
+
+#[tokio::test]
+async fn test_references_resolve() {
+    std::env::set_var("RCP_SECRET_REF_TEST", "from-env");
+    let env: SecretRef = "env:RCP_SECRET_REF_TEST".parse().unwrap();
+    assert_eq!(env.resolve().await.unwrap().expose(), "from-env");
+    let unset: SecretRef = "env:RCP_SECRET_REF_UNSET".parse().unwrap();
+    assert!(unset.resolve().await.is_err());
+
+    let path = std::env::temp_dir().join(format!("rcp-secret-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "from-file\n").unwrap();
+    let file = SecretRef::File(path.clone());
+    assert_eq!(file.resolve().await.unwrap().expose(), "from-file");
+    std::fs::remove_file(&path).unwrap();
+
+    let command: SecretRef = "cmd:printf 'from-cmd\\n'".parse().unwrap();
+    assert_eq!(command.resolve().await.unwrap().expose(), "from-cmd");
+    let failing: SecretRef = "cmd:exit 3".parse().unwrap();
+    assert!(failing.resolve().await.is_err());
+}
+
+#[tokio::test]
+async fn test_saved_config_keeps_the_reference() {
+    std::env::set_var("RCP_SECRET_REF_SAVE", "resolved-secret");
+    let path = std::env::temp_dir().join(format!("rcp-secret-{}.toml", uuid::Uuid::new_v4()));
+
+    let mut config = ClientConfig::default();
+    config.auth.psk = Some("env:RCP_SECRET_REF_SAVE".parse().unwrap());
+    save_config(&path, &config).await.unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("psk = \"env:RCP_SECRET_REF_SAVE\""));
+    assert!(!content.contains("resolved-secret"));
+
+    let loaded = load_config(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.auth.psk, config.auth.psk);
+}
+
+#[test]
+fn test_values_are_redacted_and_checked() {
+    let value = SecretRef::Value(Secret::new("hunter2"));
+    assert!(!format!("{}", value).contains("hunter2"));
+    assert!(!format!("{:?}", value).contains("hunter2"));
+
+    let mut config = ClientConfig::default();
+    config.auth.psk = Some(value);
+    let report = config.validate();
+    assert!(report.is_ok());
+    assert!(report.issue("auth.psk").is_some());
+
+    config.auth.psk = Some("file:/nonexistent/rcp-psk".parse().unwrap());
+    assert!(!config.validate().is_ok());
+}
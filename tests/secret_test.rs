@@ -0,0 +1,38 @@
+use rust_rcp_client::auth::{Credentials, Secret};
+use rust_rcp_client::config::ClientConfig;
+use rust_rcp_client::protocol::Message;
+
+#[test]
+fn test_secret_is_redacted() {
+    let secret = Secret::new("hunter2");
+    assert_eq!(secret.expose(), "hunter2");
+    assert!(!format!("{:?}", secret).contains("hunter2"));
+    assert!(!format!("{}", secret).contains("hunter2"));
+
+    let credentials = Credentials::Password {
+        username: "alice".to_string(),
+        password: secret,
+    };
+    let debug = format!("{:?}", credentials);
+    assert!(debug.contains("alice"));
+    assert!(!debug.contains("hunter2"));
+}
+
+#[test]
+fn test_config_psk_round_trips_but_is_redacted() {
+    let mut config = ClientConfig::default();
+    config.auth.psk = Some(Secret::new("shared-key").into());
+    assert!(!format!("{:?}", config).contains("shared-key"));
+
+    let serialized = toml::to_string(&config).unwrap();
+    let parsed: ClientConfig = toml::from_str(&serialized).unwrap();
+    assert_eq!(parsed.auth.psk, Some(Secret::new("shared-key").into()));
+}
+
+#[test]
+fn test_auth_message_payload_is_redacted() {
+    let message = Message::auth("alice", b"hunter2", "password");
+    let debug = format!("{:?}", message);
+    assert!(!debug.contains("104, 117, 110")); // "hun" as bytes
+    assert!(!debug.contains("alice"));
+}
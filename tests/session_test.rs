@@ -0,0 +1,156 @@
+mod common;
+
+use common::{auth_reply, serve_exchanges};
+use rust_rcp_client::auth::negotiate::authenticate;
+use rust_rcp_client::auth::store::MemoryStore;
+use rust_rcp_client::auth::{AuthOptions, AuthTarget, Secret, SessionStore, SessionToken};
+use rust_rcp_client::cli;
+use rust_rcp_client::config::ClientConfig;
+use rust_rcp_client::protocol::Client;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn token(value: &str, expires_in: u64) -> SessionToken {
+    SessionToken {
+        token: Secret::new(value),
+        expires_at: Some(now() + expires_in),
+    }
+}
+
+fn config(port: u16) -> ClientConfig {
+    let mut config = ClientConfig::default();
+    config.server.address = "127.0.0.1".to_string();
+    config.server.port = port;
+    config.server.use_tls = false;
+    config.auth.method = "psk".to_string();
+    config.auth.username = Some("alice".to_string());
+    config.auth.psk = Some(Secret::new("shared-key").into());
+    config.audit.enabled = false;
+    config
+}
+
+fn options(port: u16, sessions: &Arc<SessionStore>) -> AuthOptions {
+    AuthOptions::default()
+        .with_store(Arc::new(MemoryStore::new()))
+        .with_target(AuthTarget::new("127.0.0.1", port))
+        .with_sessions(sessions.clone())
+        .with_psk("shared-key".parse().unwrap())
+}
+
+#[test]
+fn test_tokens_are_kept_in_the_credential_store() {
+    let store = Arc::new(MemoryStore::new());
+    let target = AuthTarget::new("rcp.example.com", 8717);
+    SessionStore::new()
+        .persist_to(store.clone())
+        .insert("alice", &target, token("t1", 3600));
+
+    // A new client process finds the token
+    let sessions = SessionStore::new().persist_to(store.clone());
+    let found = sessions.get("alice", &target).unwrap();
+    assert_eq!(found.token.expose(), "t1");
+    assert!(sessions
+        .get("alice", &AuthTarget::new("other.example.com", 8717))
+        .is_none());
+
+    // Expired tokens are dropped
+    let expired = SessionToken {
+        token: Secret::new("old"),
+        expires_at: Some(now() - 1),
+    };
+    sessions.insert("alice", &target, expired);
+    assert!(SessionStore::new()
+        .persist_to(store)
+        .get("alice", &target)
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_valid_token_resumes_the_session() {
+    let (port, server) = serve_exchanges(1, |_, request| {
+        auth_reply(request, json!({ "success": true }))
+    })
+    .await;
+    let sessions = Arc::new(SessionStore::new());
+    let target = AuthTarget::new("127.0.0.1", port);
+    sessions.insert("alice", &target, token("t1", 3600));
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    let config = config(port);
+    assert!(
+        authenticate(&client, &config.auth, "alice", &options(port, &sessions))
+            .await
+            .unwrap()
+    );
+
+    // The provider flow did not run
+    let received = server.await.unwrap();
+    assert_eq!(received[0].payload["method"], "session");
+    assert_eq!(received[0].payload["token"], "t1");
+}
+
+#[tokio::test]
+async fn test_rejected_token_falls_back_to_full_authentication() {
+    let (port, server) = serve_exchanges(2, |step, request| match step {
+        0 => auth_reply(
+            request,
+            json!({ "success": false, "message": "session expired" }),
+        ),
+        _ => auth_reply(
+            request,
+            json!({ "success": true, "session": { "token": "t2", "expires_at": now() + 3600 } }),
+        ),
+    })
+    .await;
+    let sessions = Arc::new(SessionStore::new());
+    let target = AuthTarget::new("127.0.0.1", port);
+    sessions.insert("alice", &target, token("t1", 3600));
+
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    let config = config(port);
+    assert!(
+        authenticate(&client, &config.auth, "alice", &options(port, &sessions))
+            .await
+            .unwrap()
+    );
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0].payload["method"], "session");
+    assert_eq!(received[1].payload["method"], "psk");
+    // The rejected token is replaced by the one issued for the new login
+    assert_eq!(sessions.get("alice", &target).unwrap().token.expose(), "t2");
+}
+
+#[tokio::test]
+async fn test_token_about_to_expire_is_refreshed() {
+    let (port, server) = serve_exchanges(1, |_, request| {
+        auth_reply(
+            request,
+            json!({ "success": true, "session": { "token": "t2", "expires_at": now() + 3600 } }),
+        )
+    })
+    .await;
+    let config = config(port);
+    let sessions = Arc::new(SessionStore::new());
+    let target = AuthTarget::new("127.0.0.1", port);
+
+    // As before each command of exec and shell
+    let client = Client::connect("127.0.0.1", port).await.unwrap();
+    sessions.insert("alice", &target, token("t1", 60));
+    cli::refresh_session(&client, &config, sessions.clone()).await;
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0].payload["step"], "refresh");
+    assert_eq!(received[0].payload["token"], "t1");
+    let refreshed = sessions.get("alice", &target).unwrap();
+    assert_eq!(refreshed.token.expose(), "t2");
+    assert!(!refreshed.needs_refresh());
+}
@@ -0,0 +1,46 @@
+use rust_rcp_client::ui::shell::{completions, is_complete, parse_input, ShellInput};
+use serde_json::json;
+
+#[test]
+fn test_parse_commands_and_meta_commands() {
+    assert_eq!(
+        parse_input("restart {\"service\": \"web\"}").unwrap(),
+        ShellInput::Command {
+            name: "restart".to_string(),
+            params: json!({ "service": "web" }),
+        }
+    );
+    assert_eq!(
+        parse_input("  status ").unwrap(),
+        ShellInput::Command {
+            name: "status".to_string(),
+            params: json!({}),
+        }
+    );
+    assert_eq!(parse_input(":q").unwrap(), ShellInput::Quit);
+    assert_eq!(parse_input(":reconnect").unwrap(), ShellInput::Reconnect);
+    assert_eq!(parse_input("# note").unwrap(), ShellInput::Empty);
+    assert_eq!(
+        parse_input(":bogus").unwrap(),
+        ShellInput::Unknown("bogus".to_string())
+    );
+    assert!(parse_input("status {bad}").is_err());
+}
+
+#[test]
+fn test_open_params_continue_on_the_next_line() {
+    assert!(is_complete("status"));
+    assert!(is_complete("status {\"a\": [1, 2]}"));
+    assert!(!is_complete("status {\"a\":"));
+    assert!(!is_complete("status {\"a\": [1,\n 2"));
+    assert!(is_complete("status {\"brace\": \"}{\"}"));
+    assert!(!is_complete("status {\"quote\": \"\\\""));
+}
+
+#[test]
+fn test_completions_include_meta_and_server_commands() {
+    let commands = vec!["status".to_string(), "restart".to_string()];
+    assert_eq!(completions("st", &commands), vec!["status"]);
+    assert_eq!(completions(":re", &commands), vec![":reconnect"]);
+    assert_eq!(completions("", &commands).len(), 6);
+}
@@ -0,0 +1,65 @@
+use rust_rcp_client::auth::store::{CredentialStore, MemoryStore};
+use rust_rcp_client::auth::{
+    totp, AuthTarget, SecondFactorChallenge, SecondFactorHandler, SecondFactorKind, StoredTotp,
+};
+use std::sync::Arc;
+
+/// RFC 6238 test secret ("12345678901234567890" in ASCII)
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+#[test]
+fn test_hotp_matches_rfc4226_vectors() {
+    let expected = ["755224", "287082", "359152", "969429", "338314"];
+    for (counter, code) in expected.iter().enumerate() {
+        assert_eq!(totp::hotp(RFC_SECRET, counter as u64, 6), *code);
+    }
+}
+
+#[test]
+fn test_totp_matches_rfc6238_vectors() {
+    let vectors = [
+        (59, "94287082"),
+        (1111111109, "07081804"),
+        (1111111111, "14050471"),
+        (1234567890, "89005924"),
+        (2000000000, "69279037"),
+    ];
+    for (time, code) in vectors {
+        assert_eq!(totp::totp_at(RFC_SECRET, time, 30, 8), code);
+    }
+}
+
+#[test]
+fn test_decode_secret_accepts_authenticator_format() {
+    // Base32 of the RFC secret, lower case with spaces as shown by some apps
+    let key = totp::decode_secret("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+    assert_eq!(key, RFC_SECRET);
+
+    assert!(totp::decode_secret("not base32!").is_err());
+    assert_eq!(totp::generate("GEZDGNBVGY3TQOJQ").unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn test_stored_totp_secrets_are_per_server() {
+    let store = Arc::new(MemoryStore::new().with_secret("totp:alice", "GEZDGNBVGY3TQOJQ"));
+    let handler =
+        |address: &str| StoredTotp::new(store.clone()).with_target(AuthTarget::new(address, 8717));
+    let challenge = SecondFactorChallenge {
+        kind: SecondFactorKind::Totp,
+        username: "alice".to_string(),
+        message: None,
+        attempts_remaining: None,
+    };
+
+    // The legacy secret moves to the first server it is used with
+    assert!(handler("one").one_time_code(&challenge).await.is_ok());
+    assert!(store.get("totp:alice@one:8717").unwrap().is_some());
+    assert_eq!(store.get("totp:alice").unwrap(), None);
+    assert!(handler("two").one_time_code(&challenge).await.is_err());
+
+    handler("two")
+        .store_secret("alice", "MFRGGZDFMZTWQ2LK")
+        .unwrap();
+    assert!(handler("two").one_time_code(&challenge).await.is_ok());
+    assert!(store.get("totp:alice@one:8717").unwrap().is_some());
+}